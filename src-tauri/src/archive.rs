@@ -0,0 +1,258 @@
+// Minimal tar and zip writers for exports, plus a tar reader for imports.
+// Regular files only. Tar paths longer than the 100-byte ustar name field use
+// a GNU long-name entry; zip entries are deflated with flate2 and the format
+// stays within classic (non-zip64) limits.
+
+use std::io::{self, Write};
+
+const BLOCK: usize = 512;
+
+pub struct TarWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> TarWriter<W> {
+    pub fn new(out: W) -> Self {
+        TarWriter { out }
+    }
+
+    // Appends a regular file. `mtime` is in seconds since the epoch.
+    pub fn append(&mut self, path: &str, data: &[u8], mtime: i64) -> io::Result<()> {
+        if path.len() > 100 {
+            let mut long = path.as_bytes().to_vec();
+            long.push(0);
+            self.write_entry("././@LongLink", b'L', &long, 0)?;
+        }
+        self.write_entry(path, b'0', data, mtime)
+    }
+
+    // Writes the end-of-archive marker and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0; BLOCK * 2])?;
+        Ok(self.out)
+    }
+
+    fn write_entry(&mut self, path: &str, kind: u8, data: &[u8], mtime: i64) -> io::Result<()> {
+        let mut header = [0u8; BLOCK];
+        // Truncate at a char boundary; the long-name entry carries the full path
+        let mut name_len = path.len().min(100);
+        while !path.is_char_boundary(name_len) {
+            name_len -= 1;
+        }
+        header[..name_len].copy_from_slice(&path.as_bytes()[..name_len]);
+        octal(&mut header[100..108], 0o644);
+        octal(&mut header[108..116], 0);
+        octal(&mut header[116..124], 0);
+        octal(&mut header[124..136], data.len() as u64);
+        octal(&mut header[136..148], mtime.max(0) as u64);
+        header[156] = kind;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        // Checksum is computed with its own field filled with spaces
+        header[148..156].fill(b' ');
+        let sum: u32 = header.iter().map(|&b| b as u32).sum();
+        octal(&mut header[148..155], sum as u64);
+        header[155] = b' ';
+
+        self.out.write_all(&header)?;
+        self.out.write_all(data)?;
+        let pad = (BLOCK - data.len() % BLOCK) % BLOCK;
+        self.out.write_all(&[0; BLOCK][..pad])
+    }
+}
+
+// Regular files in a tar archive as (path, contents). Understands ustar name
+// prefixes, GNU long names and pax `path` records; other entry types are skipped.
+pub fn read_tar(data: &[u8]) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let mut files = Vec::new();
+    let mut long_name: Option<String> = None;
+    let mut pos = 0;
+    while pos + BLOCK <= data.len() {
+        let header = &data[pos..pos + BLOCK];
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        // The checksum field itself counts as spaces
+        let sum: u32 = header
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| if (148..156).contains(&i) { b' ' } else { b } as u32)
+            .sum();
+        if parse_octal(&header[148..156]) != Some(sum as u64) {
+            return Err(invalid("tar header checksum mismatch"));
+        }
+        let size =
+            parse_octal(&header[124..136]).ok_or_else(|| invalid("bad tar entry size"))? as usize;
+        let start = pos + BLOCK;
+        let end = start
+            .checked_add(size)
+            .filter(|&end| end <= data.len())
+            .ok_or_else(|| invalid("truncated tar entry"))?;
+        let body = &data[start..end];
+        pos = start + size.div_ceil(BLOCK) * BLOCK;
+
+        match header[156] {
+            b'L' => long_name = Some(c_string(body)),
+            b'x' => {
+                if let Some(path) = pax_path(body) {
+                    long_name = Some(path);
+                }
+            }
+            b'0' | 0 => {
+                let path = long_name.take().unwrap_or_else(|| {
+                    let name = c_string(&header[..100]);
+                    let prefix = if &header[257..262] == b"ustar" {
+                        c_string(&header[345..500])
+                    } else {
+                        String::new()
+                    };
+                    if prefix.is_empty() {
+                        name
+                    } else {
+                        format!("{}/{}", prefix, name)
+                    }
+                });
+                files.push((path, body.to_vec()));
+            }
+            _ => long_name = None,
+        }
+    }
+    Ok(files)
+}
+
+fn c_string(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+// Octal digits, optionally space/NUL padded
+fn parse_octal(field: &[u8]) -> Option<u64> {
+    let text = std::str::from_utf8(field).ok()?;
+    let digits = text.trim_matches(|c: char| c == ' ' || c == '\0');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(digits, 8).ok()
+}
+
+// pax records are "<len> key=value\n"
+fn pax_path(records: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(records);
+    text.lines().find_map(|line| {
+        let (_, record) = line.split_once(' ')?;
+        record.strip_prefix("path=").map(str::to_string)
+    })
+}
+
+// Zero-padded octal with a trailing NUL, filling the field.
+fn octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    let digits = &digits.as_bytes()[digits.len() + 1 - field.len()..];
+    field[..digits.len()].copy_from_slice(digits);
+    field[field.len() - 1] = 0;
+}
+
+pub struct ZipWriter<W: Write> {
+    out: W,
+    offset: u64,
+    central: Vec<u8>,
+    entries: u16,
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub fn new(out: W) -> Self {
+        ZipWriter {
+            out,
+            offset: 0,
+            central: Vec::new(),
+            entries: 0,
+        }
+    }
+
+    // Appends a deflated file. `mtime` is in seconds since the epoch.
+    pub fn append(&mut self, path: &str, data: &[u8], mtime: i64) -> io::Result<()> {
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+        let mut crc = flate2::Crc::new();
+        crc.update(data);
+
+        let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "zip entry too large");
+        let size = u32::try_from(data.len()).map_err(|_| too_large())?;
+        let csize = u32::try_from(compressed.len()).map_err(|_| too_large())?;
+        let offset = u32::try_from(self.offset).map_err(|_| too_large())?;
+        let name_len = u16::try_from(path.len()).map_err(|_| too_large())?;
+        self.entries = self.entries.checked_add(1).ok_or_else(too_large)?;
+        let (time, date) = dos_datetime(mtime);
+
+        // Fields shared by the local header and the central directory record:
+        // version needed, flags (bit 11 = UTF-8 names), method 8 (deflate),
+        // time, date, crc, sizes, name length
+        let mut common = Vec::with_capacity(26);
+        common.extend_from_slice(&20u16.to_le_bytes());
+        common.extend_from_slice(&0x0800u16.to_le_bytes());
+        common.extend_from_slice(&8u16.to_le_bytes());
+        common.extend_from_slice(&time.to_le_bytes());
+        common.extend_from_slice(&date.to_le_bytes());
+        common.extend_from_slice(&crc.sum().to_le_bytes());
+        common.extend_from_slice(&csize.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&name_len.to_le_bytes());
+
+        let mut local = Vec::with_capacity(30 + path.len());
+        local.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        local.extend_from_slice(&common);
+        local.extend_from_slice(&0u16.to_le_bytes()); // extra length
+        local.extend_from_slice(path.as_bytes());
+        self.out.write_all(&local)?;
+        self.out.write_all(&compressed)?;
+        self.offset += (local.len() + compressed.len()) as u64;
+
+        self.central.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        self.central.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        self.central.extend_from_slice(&common);
+        // extra length, comment length, disk number, internal attributes
+        self.central.extend_from_slice(&[0; 8]);
+        self.central.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+        self.central.extend_from_slice(&offset.to_le_bytes());
+        self.central.extend_from_slice(path.as_bytes());
+        Ok(())
+    }
+
+    // Writes the central directory and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "zip archive too large");
+        let cd_offset = u32::try_from(self.offset).map_err(|_| too_large())?;
+        let cd_size = u32::try_from(self.central.len()).map_err(|_| too_large())?;
+        self.out.write_all(&self.central)?;
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        end.extend_from_slice(&[0; 4]); // disk numbers
+        end.extend_from_slice(&self.entries.to_le_bytes());
+        end.extend_from_slice(&self.entries.to_le_bytes());
+        end.extend_from_slice(&cd_size.to_le_bytes());
+        end.extend_from_slice(&cd_offset.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        self.out.write_all(&end)?;
+        Ok(self.out)
+    }
+}
+
+// MS-DOS (time, date) in UTC, clamped to the representable 1980..=2107 range.
+fn dos_datetime(secs: i64) -> (u16, u16) {
+    use chrono::{Datelike, Timelike};
+    let Some(t) = chrono::DateTime::from_timestamp(secs, 0) else {
+        return (0, 0x21);
+    };
+    if t.year() < 1980 {
+        return (0, 0x21);
+    }
+    if t.year() > 2107 {
+        return (0xbf7d, 0xff9f);
+    }
+    let time = (t.hour() << 11) | (t.minute() << 5) | (t.second() / 2);
+    let date = (((t.year() - 1980) as u32) << 9) | (t.month() << 5) | t.day();
+    (time as u16, date as u16)
+}
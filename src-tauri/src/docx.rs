@@ -0,0 +1,292 @@
+// Word (.docx) export: the note's markdown blocks mapped onto built-in style
+// names (Title, Heading1-6, Quote, plus a Code style) inside a minimal
+// WordprocessingML package, zipped with the archive writer.
+
+use std::fmt::Write as _;
+use std::io;
+
+use crate::archive::ZipWriter;
+use crate::markdown::{self, Block, Span};
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/><Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/><Override PartName="/word/numbering.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.numbering+xml"/></Types>"#;
+
+const PACKAGE_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/></Relationships>"#;
+
+const DOCUMENT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/numbering" Target="numbering.xml"/></Relationships>"#;
+
+const NAMESPACE: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+const MONO: &str = r#"<w:rFonts w:ascii="Consolas" w:hAnsi="Consolas" w:cs="Consolas"/>"#;
+
+// Abstract numbering ids: bullets and decimal lists
+const BULLET: usize = 0;
+const DECIMAL: usize = 1;
+
+pub fn build(title: &str, body: &str) -> io::Result<Vec<u8>> {
+    let mut doc = String::new();
+    let mut ordered_lists = 0;
+    if !title.trim().is_empty() {
+        paragraph(&mut doc, Some("Title"), "", &[plain(title.trim())]);
+    }
+
+    // numId of the ordered list currently being written
+    let mut list_num = None;
+    for block in markdown::blocks(body) {
+        if !matches!(block, Block::ListItem { .. }) {
+            list_num = None;
+        }
+        match block {
+            Block::Heading { level, text } => {
+                let style = format!("Heading{}", level);
+                paragraph(&mut doc, Some(&style), "", &markdown::spans(&text));
+            }
+            Block::Paragraph(text) => paragraph(&mut doc, None, "", &markdown::spans(&text)),
+            Block::Quote(text) => paragraph(&mut doc, Some("Quote"), "", &markdown::spans(&text)),
+            Block::Code(lines) => {
+                for line in lines {
+                    paragraph(&mut doc, Some("Code"), "", &[plain(&line)]);
+                }
+            }
+            Block::ListItem {
+                depth,
+                ordered,
+                text,
+            } => {
+                // Each ordered list gets its own num so numbering restarts at 1
+                let num_id = if ordered {
+                    *list_num.get_or_insert_with(|| {
+                        ordered_lists += 1;
+                        1 + ordered_lists
+                    })
+                } else {
+                    1
+                };
+                let props = format!(
+                    r#"<w:numPr><w:ilvl w:val="{}"/><w:numId w:val="{}"/></w:numPr>"#,
+                    depth.min(8),
+                    num_id
+                );
+                paragraph(&mut doc, Some("ListParagraph"), &props, &markdown::spans(&text));
+            }
+            Block::Table(rows) => table(&mut doc, &rows),
+            Block::Rule => doc.push_str(
+                r#"<w:p><w:pPr><w:pBdr><w:bottom w:val="single" w:sz="6" w:space="1" w:color="auto"/></w:pBdr></w:pPr></w:p>"#,
+            ),
+        }
+    }
+
+    let document = format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="{}"><w:body>{}<w:sectPr><w:pgSz w:w="12240" w:h="15840"/><w:pgMar w:top="1440" w:right="1440" w:bottom="1440" w:left="1440" w:header="720" w:footer="720" w:gutter="0"/></w:sectPr></w:body></w:document>"#,
+        NAMESPACE, doc
+    );
+
+    let mut zip = ZipWriter::new(Vec::new());
+    let now = chrono::Utc::now().timestamp();
+    zip.append("[Content_Types].xml", CONTENT_TYPES.as_bytes(), now)?;
+    zip.append("_rels/.rels", PACKAGE_RELS.as_bytes(), now)?;
+    zip.append(
+        "word/_rels/document.xml.rels",
+        DOCUMENT_RELS.as_bytes(),
+        now,
+    )?;
+    zip.append("word/document.xml", document.as_bytes(), now)?;
+    zip.append("word/styles.xml", styles().as_bytes(), now)?;
+    zip.append(
+        "word/numbering.xml",
+        numbering(ordered_lists).as_bytes(),
+        now,
+    )?;
+    zip.finish()
+}
+
+fn plain(text: &str) -> Span {
+    Span {
+        text: text.to_string(),
+        bold: false,
+        italic: false,
+        code: false,
+        link: None,
+    }
+}
+
+fn paragraph(doc: &mut String, style: Option<&str>, props: &str, spans: &[Span]) {
+    doc.push_str("<w:p>");
+    if style.is_some() || !props.is_empty() {
+        doc.push_str("<w:pPr>");
+        if let Some(style) = style {
+            let _ = write!(doc, r#"<w:pStyle w:val="{}"/>"#, style);
+        }
+        doc.push_str(props);
+        doc.push_str("</w:pPr>");
+    }
+    for span in spans {
+        run(doc, span, false);
+    }
+    doc.push_str("</w:p>");
+}
+
+fn run(doc: &mut String, span: &Span, force_bold: bool) {
+    if span.text.is_empty() {
+        return;
+    }
+    doc.push_str("<w:r>");
+    let bold = span.bold || force_bold;
+    if bold || span.italic || span.code || span.link.is_some() {
+        doc.push_str("<w:rPr>");
+        if span.code {
+            doc.push_str(MONO);
+        }
+        if bold {
+            doc.push_str("<w:b/>");
+        }
+        if span.italic {
+            doc.push_str("<w:i/>");
+        }
+        // Links are styled but not clickable; targets would need relationships
+        if span.link.is_some() {
+            doc.push_str(r#"<w:color w:val="0563C1"/><w:u w:val="single"/>"#);
+        }
+        doc.push_str("</w:rPr>");
+    }
+    let _ = write!(
+        doc,
+        r#"<w:t xml:space="preserve">{}</w:t></w:r>"#,
+        escape(&span.text)
+    );
+}
+
+fn table(doc: &mut String, rows: &[Vec<String>]) {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0).max(1);
+    let width = 9360 / columns;
+    doc.push_str(r#"<w:tbl><w:tblPr><w:tblW w:w="0" w:type="auto"/><w:tblBorders>"#);
+    for side in ["top", "left", "bottom", "right", "insideH", "insideV"] {
+        let _ = write!(
+            doc,
+            r#"<w:{} w:val="single" w:sz="4" w:space="0" w:color="auto"/>"#,
+            side
+        );
+    }
+    doc.push_str("</w:tblBorders></w:tblPr><w:tblGrid>");
+    for _ in 0..columns {
+        let _ = write!(doc, r#"<w:gridCol w:w="{}"/>"#, width);
+    }
+    doc.push_str("</w:tblGrid>");
+    for (i, row) in rows.iter().enumerate() {
+        doc.push_str("<w:tr>");
+        for c in 0..columns {
+            let _ = write!(
+                doc,
+                r#"<w:tc><w:tcPr><w:tcW w:w="{}" w:type="dxa"/></w:tcPr><w:p>"#,
+                width
+            );
+            if let Some(cell) = row.get(c) {
+                for span in markdown::spans(cell) {
+                    run(doc, &span, i == 0);
+                }
+            }
+            doc.push_str("</w:p></w:tc>");
+        }
+        doc.push_str("</w:tr>");
+    }
+    doc.push_str("</w:tbl>");
+}
+
+fn styles() -> String {
+    let mut s = format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:styles xmlns:w="{}"><w:docDefaults><w:rPrDefault><w:rPr><w:rFonts w:ascii="Calibri" w:hAnsi="Calibri" w:cs="Calibri"/><w:sz w:val="22"/></w:rPr></w:rPrDefault><w:pPrDefault><w:pPr><w:spacing w:after="120" w:line="276" w:lineRule="auto"/></w:pPr></w:pPrDefault></w:docDefaults>"#,
+        NAMESPACE
+    );
+    s.push_str(r#"<w:style w:type="paragraph" w:default="1" w:styleId="Normal"><w:name w:val="Normal"/><w:qFormat/></w:style>"#);
+    s.push_str(r#"<w:style w:type="paragraph" w:styleId="Title"><w:name w:val="Title"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:spacing w:after="240"/></w:pPr><w:rPr><w:b/><w:sz w:val="48"/></w:rPr></w:style>"#);
+    // Heading sizes in half-points, largest first
+    for (level, size) in (1..=6).zip([36, 30, 26, 24, 22, 22]) {
+        let _ = write!(
+            s,
+            r#"<w:style w:type="paragraph" w:styleId="Heading{0}"><w:name w:val="heading {0}"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="240" w:after="80"/><w:outlineLvl w:val="{1}"/></w:pPr><w:rPr><w:b/><w:sz w:val="{2}"/></w:rPr></w:style>"#,
+            level,
+            level - 1,
+            size
+        );
+    }
+    s.push_str(r#"<w:style w:type="paragraph" w:styleId="ListParagraph"><w:name w:val="List Paragraph"/><w:basedOn w:val="Normal"/><w:pPr><w:spacing w:after="40"/></w:pPr></w:style>"#);
+    s.push_str(r#"<w:style w:type="paragraph" w:styleId="Quote"><w:name w:val="Quote"/><w:basedOn w:val="Normal"/><w:pPr><w:ind w:left="720"/><w:pBdr><w:left w:val="single" w:sz="12" w:space="8" w:color="999999"/></w:pBdr></w:pPr><w:rPr><w:i/><w:color w:val="555555"/></w:rPr></w:style>"#);
+    let _ = write!(
+        s,
+        r#"<w:style w:type="paragraph" w:styleId="Code"><w:name w:val="Code"/><w:basedOn w:val="Normal"/><w:pPr><w:spacing w:after="0" w:line="240" w:lineRule="auto"/><w:shd w:val="clear" w:color="auto" w:fill="F2F2F2"/></w:pPr><w:rPr>{}<w:sz w:val="20"/></w:rPr></w:style>"#,
+        MONO
+    );
+    s.push_str("</w:styles>");
+    s
+}
+
+// numId 1 is the shared bullet list; numIds 2.. are one per ordered list
+fn numbering(ordered_lists: usize) -> String {
+    let mut s = format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:numbering xmlns:w="{}">"#,
+        NAMESPACE
+    );
+    for (id, format, text) in [(BULLET, "bullet", "\u{2022}"), (DECIMAL, "decimal", "")] {
+        let _ = write!(s, r#"<w:abstractNum w:abstractNumId="{}">"#, id);
+        for level in 0..9 {
+            let text = if id == DECIMAL {
+                format!("%{}.", level + 1)
+            } else {
+                text.to_string()
+            };
+            let _ = write!(
+                s,
+                r#"<w:lvl w:ilvl="{}"><w:start w:val="1"/><w:numFmt w:val="{}"/><w:lvlText w:val="{}"/><w:lvlJc w:val="left"/><w:pPr><w:ind w:left="{}" w:hanging="360"/></w:pPr></w:lvl>"#,
+                level,
+                format,
+                text,
+                720 * (level + 1)
+            );
+        }
+        s.push_str("</w:abstractNum>");
+    }
+    let _ = write!(
+        s,
+        r#"<w:num w:numId="1"><w:abstractNumId w:val="{}"/></w:num>"#,
+        BULLET
+    );
+    for k in 0..ordered_lists {
+        let _ = write!(
+            s,
+            r#"<w:num w:numId="{}"><w:abstractNumId w:val="{}"/>"#,
+            k + 2,
+            DECIMAL
+        );
+        for level in 0..9 {
+            let _ = write!(
+                s,
+                r#"<w:lvlOverride w:ilvl="{}"><w:startOverride w:val="1"/></w:lvlOverride>"#,
+                level
+            );
+        }
+        s.push_str("</w:num>");
+    }
+    s.push_str("</w:numbering>");
+    s
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            // Control characters are not allowed in XML 1.0
+            '\t' | '\n' | '\r' => out.push(c),
+            c if c < ' ' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,186 @@
+// HTML rendering for the static site export: markdown blocks and spans
+// mapped onto plain semantic tags, styled by a shared stylesheet.
+
+use std::fmt::Write as _;
+
+use crate::markdown::{self, Block, Span};
+
+pub const STYLE: &str = "\
+body { margin: 0; font: 16px/1.6 -apple-system, BlinkMacSystemFont, 'Segoe UI', sans-serif; color: #222; display: flex; }
+nav { width: 260px; min-height: 100vh; padding: 16px; background: #f5f5f4; box-sizing: border-box; font-size: 14px; }
+nav ul { list-style: none; padding-left: 14px; margin: 2px 0; }
+nav > ul { padding-left: 0; }
+nav .folder { font-weight: 600; }
+main { flex: 1; max-width: 760px; padding: 32px 48px; }
+a { color: #0563c1; }
+a.broken { color: #b91c1c; text-decoration: line-through; }
+pre { background: #f5f5f4; padding: 12px; overflow-x: auto; }
+code { font-family: Consolas, Menlo, monospace; font-size: 0.9em; }
+blockquote { margin: 0; padding-left: 12px; border-left: 3px solid #ddd; color: #555; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; }
+";
+
+// Renders a note body. `wiki_href` maps a `[[target]]` to the page it links
+// to; unresolved targets are rendered as broken links.
+pub fn render(body: &str, wiki_href: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    // Open lists, innermost last, as (depth, ordered)
+    let mut lists: Vec<(usize, bool)> = Vec::new();
+    // Heading ids match the anchors generate_toc_note links to
+    let mut slugger = markdown::Slugger::default();
+    for block in markdown::blocks(body) {
+        let item = match &block {
+            Block::ListItem { depth, ordered, .. } => Some((*depth, *ordered)),
+            _ => None,
+        };
+        while let Some(&(depth, ordered)) = lists.last() {
+            let keep = item.is_some_and(|(d, o)| d > depth || (d == depth && o == ordered));
+            if keep {
+                break;
+            }
+            out.push_str(if ordered {
+                "</li>\n</ol>\n"
+            } else {
+                "</li>\n</ul>\n"
+            });
+            lists.pop();
+        }
+        match block {
+            Block::Heading { level, text } => {
+                let id = escape(&slugger.anchor(&text));
+                let _ = writeln!(
+                    out,
+                    "<h{0} id=\"{1}\">{2}</h{0}>",
+                    level,
+                    id,
+                    inline(&text, wiki_href)
+                );
+            }
+            Block::Paragraph(text) => {
+                let _ = writeln!(out, "<p>{}</p>", inline(&text, wiki_href));
+            }
+            Block::Quote(text) => {
+                let _ = writeln!(out, "<blockquote>{}</blockquote>", inline(&text, wiki_href));
+            }
+            Block::Code(lines) => {
+                let _ = writeln!(out, "<pre><code>{}</code></pre>", escape(&lines.join("\n")));
+            }
+            Block::ListItem {
+                depth,
+                ordered,
+                text,
+            } => {
+                let nested = match lists.last() {
+                    Some(&(d, _)) => depth > d,
+                    None => true,
+                };
+                // Items stay open so a nested list lands inside its parent item
+                if nested {
+                    out.push_str(if ordered { "<ol>\n" } else { "<ul>\n" });
+                    lists.push((depth, ordered));
+                } else {
+                    out.push_str("</li>\n");
+                }
+                let _ = write!(out, "<li>{}", inline(&text, wiki_href));
+            }
+            Block::Table(rows) => {
+                out.push_str("<table>\n");
+                for (i, row) in rows.iter().enumerate() {
+                    let tag = if i == 0 { "th" } else { "td" };
+                    out.push_str("<tr>");
+                    for cell in row {
+                        let _ = write!(out, "<{0}>{1}</{0}>", tag, inline(cell, wiki_href));
+                    }
+                    out.push_str("</tr>\n");
+                }
+                out.push_str("</table>\n");
+            }
+            Block::Rule => out.push_str("<hr>\n"),
+        }
+    }
+    for (_, ordered) in lists.into_iter().rev() {
+        out.push_str(if ordered {
+            "</li>\n</ol>\n"
+        } else {
+            "</li>\n</ul>\n"
+        });
+    }
+    out
+}
+
+fn inline(text: &str, wiki_href: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    for span in markdown::spans(text) {
+        out.push_str(&span_html(&span, wiki_href));
+    }
+    out
+}
+
+fn span_html(span: &Span, wiki_href: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut html = escape(&span.text);
+    if span.code {
+        html = format!("<code>{}</code>", html);
+    }
+    if span.italic {
+        html = format!("<em>{}</em>", html);
+    }
+    if span.bold {
+        html = format!("<strong>{}</strong>", html);
+    }
+    match &span.link {
+        Some(link) => match link.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+            Some(target) => match wiki_href(target) {
+                Some(href) => format!("<a href=\"{}\">{}</a>", escape(&href), html),
+                None => format!("<a class=\"broken\">{}</a>", html),
+            },
+            None if is_safe_href(link) => format!("<a href=\"{}\">{}</a>", escape(link), html),
+            None => html,
+        },
+        None => html,
+    }
+}
+
+// Published pages shouldn't run script from note content, so links keep
+// their href only for http, https and mailto, fragments and relative paths.
+// Browsers ignore control characters and whitespace inside a scheme
+// ("java\tscript:"), so those are dropped before looking at it.
+fn is_safe_href(link: &str) -> bool {
+    let cleaned: String = link
+        .chars()
+        .filter(|c| !c.is_ascii_control() && !c.is_ascii_whitespace())
+        .collect();
+    match cleaned.find([':', '/', '?', '#']) {
+        Some(i) if cleaned[i..].starts_with(':') => {
+            let scheme = cleaned[..i].to_ascii_lowercase();
+            matches!(scheme.as_str(), "http" | "https" | "mailto")
+        }
+        _ => true,
+    }
+}
+
+// A complete page: the shared nav on the left and `content` as the main column
+pub fn page(title: &str, nav: &str, content: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{}</title>\n<link rel=\"stylesheet\" href=\"style.css\">\n</head>\n\
+         <body>\n<nav>\n{}</nav>\n<main>\n{}</main>\n</body>\n</html>\n",
+        escape(title),
+        nav,
+        content
+    )
+}
+
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
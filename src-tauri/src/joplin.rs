@@ -0,0 +1,79 @@
+// Joplin JEX import. A .jex is a tar of `<id>.md` items (plus a resources/
+// directory). Each item is its title, a blank line, the body, then a block
+// of `key: value` metadata lines such as `id`, `parent_id` and `type_`.
+
+pub const TYPE_NOTE: i64 = 1;
+pub const TYPE_FOLDER: i64 = 2;
+
+pub struct Item {
+    pub id: String,
+    pub parent_id: String,
+    pub kind: i64,
+    pub title: String,
+    pub body: String,
+    // Milliseconds since the epoch
+    pub created: Option<i64>,
+    pub updated: Option<i64>,
+}
+
+// Parses one item file. Returns None when the metadata footer lacks an id or type.
+pub fn parse(text: &str) -> Option<Item> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut end = lines.len();
+    while end > 0 && lines[end - 1].trim().is_empty() {
+        end -= 1;
+    }
+    let mut start = end;
+    while start > 0 && metadata_line(lines[start - 1]).is_some() {
+        start -= 1;
+    }
+    let meta = |key: &str| {
+        lines[start..end]
+            .iter()
+            .filter_map(|line| metadata_line(line))
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v)
+    };
+    let id = meta("id").filter(|id| !id.is_empty())?.to_string();
+    let kind = meta("type_")?.parse().ok()?;
+
+    let content = &lines[..start];
+    let title = content.first().map_or("", |l| l.trim()).to_string();
+    let mut rest = content.get(1..).unwrap_or(&[]);
+    // The body is separated from the title by one blank line and from the
+    // metadata by another
+    if rest.first().is_some_and(|l| l.is_empty()) {
+        rest = &rest[1..];
+    }
+    if rest.last().is_some_and(|l| l.is_empty()) {
+        rest = &rest[..rest.len() - 1];
+    }
+    Some(Item {
+        id,
+        parent_id: meta("parent_id").unwrap_or("").to_string(),
+        kind,
+        title,
+        body: rest.join("\n"),
+        created: meta("created_time").and_then(timestamp),
+        updated: meta("updated_time").and_then(timestamp),
+    })
+}
+
+// `key: value` with a lowercase snake_case key; the value may be empty
+fn metadata_line(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let valid = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid || !(value.is_empty() || value.starts_with(' ')) {
+        return None;
+    }
+    Some((key, value.trim()))
+}
+
+fn timestamp(value: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|t| t.timestamp_millis())
+}
@@ -1,9 +1,73 @@
-use rusqlite::Connection;
+mod archive;
+mod base64;
+mod docx;
+mod html;
+mod joplin;
+mod markdown;
+mod pdf;
+mod sha256;
+mod thumbnail;
+
+#[cfg(test)]
+mod tests;
+
+use rusqlite::functions::FunctionFlags;
+use rusqlite::{Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
 use tauri::{Manager, State};
 
-struct Db(Mutex<Connection>);
+// The connection, plus whether maintenance mode is on
+struct Db(Mutex<Connection>, AtomicBool);
+
+impl Db {
+    // Locks the connection for a write; refused while maintenance is running
+    fn write(&self) -> Result<MutexGuard<'_, Connection>, String> {
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        if self.1.load(Ordering::SeqCst) {
+            return Err("maintenance in progress".to_string());
+        }
+        Ok(conn)
+    }
+
+    // Holds maintenance mode for the guard's lifetime, so it ends even if the
+    // operation fails. Inside a begin/end_maintenance window it's a no-op.
+    fn maintenance(&self) -> MaintenanceGuard<'_> {
+        let owned = !self.1.swap(true, Ordering::SeqCst);
+        MaintenanceGuard {
+            flag: &self.1,
+            owned,
+        }
+    }
+}
+
+struct MaintenanceGuard<'a> {
+    flag: &'a AtomicBool,
+    owned: bool,
+}
+
+impl Drop for MaintenanceGuard<'_> {
+    fn drop(&mut self) {
+        if self.owned {
+            self.flag.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+// Previous positions of bulk-moved notes by undo token, oldest first. In
+// memory only: an undo offer doesn't outlive the session, and only the last
+// MAX_MOVE_UNDOS are kept.
+#[derive(Default)]
+struct MoveUndo(Mutex<std::collections::VecDeque<(String, Vec<NotePosition>)>>);
+
+const MAX_MOVE_UNDOS: usize = 20;
+
+// (note id, folder id, sort_order)
+type NotePosition = (String, String, i32);
+
+// (updated_at, id) of the last note on a feed page
+type FeedCursor = (i64, String);
 
 #[derive(Serialize, Deserialize, Clone)]
 struct Folder {
@@ -11,8 +75,25 @@ struct Folder {
     name: String,
     created_at: i64,
     parent_id: Option<String>,
+    #[serde(default)]
+    sort_order: i32,
+    #[serde(default = "default_sort_mode")]
+    sort_mode: String,
+}
+
+fn default_sort_mode() -> String {
+    "manual".to_string()
 }
 
+const FOLDER_SORT_MODES: [&str; 4] = ["manual", "updated_desc", "created_desc", "title_asc"];
+
+// ORDER BY for notes joined as `n` with their folder as `f`: grouped by folder,
+// pinned first, then by the folder's sort_mode (manual falls through to sort_order)
+const FOLDER_NOTES_ORDER: &str = "n.folder_id, n.pinned DESC, \
+     CASE f.sort_mode WHEN 'updated_desc' THEN -n.updated_at WHEN 'created_desc' THEN -n.created_at END, \
+     CASE WHEN f.sort_mode = 'title_asc' THEN n.title COLLATE NOCASE END, \
+     n.sort_order";
+
 #[derive(Serialize, Deserialize, Clone)]
 struct Note {
     id: String,
@@ -27,6 +108,30 @@ struct Note {
     pinned: i32,
     #[serde(default)]
     sort_order: i32,
+    #[serde(default)]
+    starred: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Tag {
+    id: String,
+    name: String,
+    #[serde(default)]
+    color: Option<String>,
+    created_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct NoteTag {
+    note_id: String,
+    tag_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct NoteMeta {
+    note_id: String,
+    key: String,
+    value: String,
 }
 
 #[derive(Serialize, Clone)]
@@ -39,12 +144,324 @@ struct NoteMetadata {
     updated_at: i64,
     pinned: i32,
     sort_order: i32,
+    starred: i32,
+    content_hash: Option<String>,
+}
+
+// A folder with its notes in display order, for rendering the sidebar in one call
+#[derive(Serialize)]
+struct FolderGroup {
+    folder_id: String,
+    folder_name: String,
+    notes: Vec<NoteMetadata>,
+}
+
+// Note metadata plus the name of the folder it sits in
+#[derive(Serialize)]
+struct NoteInFolder {
+    #[serde(flatten)]
+    note: NoteMetadata,
+    folder_name: String,
+}
+
+#[derive(Serialize)]
+struct TitleCollision {
+    title: String,
+    note_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct GraphNode {
+    id: String,
+    title: String,
+    folder_id: String,
+}
+
+#[derive(Serialize)]
+struct GraphEdge {
+    source: String,
+    target: String,
+}
+
+#[derive(Serialize)]
+struct NoteGraph {
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+}
+
+#[derive(Serialize)]
+struct ScoredNote {
+    metadata: NoteMetadata,
+    score: i64,
+}
+
+#[derive(Serialize)]
+struct NoteWithAttachmentCount {
+    metadata: NoteMetadata,
+    attachment_count: i64,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(untagged)]
+enum SearchEvent {
+    Note(NoteMetadata),
+    Done { done: bool, total: usize },
+}
+
+#[derive(Serialize)]
+struct HistogramBucket {
+    bucket_start_ms: i64,
+    count: i64,
+}
+
+#[derive(Serialize)]
+struct GroupedSearch {
+    title_matches: Vec<NoteMetadata>,
+    // Notes matching elsewhere, minus the title matches
+    body_matches: Vec<NoteMetadata>,
+}
+
+// Lightweight projection for list rendering that never touches the body column
+#[derive(Serialize, Clone)]
+struct NoteIndex {
+    id: String,
+    folder_id: String,
+    title: String,
+    updated_at: i64,
+    pinned: i32,
+    sort_order: i32,
+}
+
+#[derive(Serialize, Clone)]
+struct BrokenLink {
+    note_id: String,
+    link_text: String,
+}
+
+#[derive(Serialize, Clone)]
+struct DbDiagnostics {
+    schema_version: i32,
+    db_path: Option<String>,
+    db_size_bytes: u64,
+    wal_size_bytes: u64,
+    page_count: i64,
+    fts_ok: bool,
+}
+
+#[derive(Serialize, Clone)]
+struct FtsStats {
+    index_pages: i64,
+    index_bytes: i64,
+    doc_count: i64,
+    // False when dbstat is unavailable and the size is estimated from the
+    // stored index blocks instead of counted pages
+    exact: bool,
+}
+
+#[derive(Serialize)]
+struct NoteSize {
+    id: String,
+    title: String,
+    folder_id: String,
+    byte_size: i64,
+}
+
+#[derive(Serialize)]
+struct ExportEstimate {
+    note_count: i64,
+    total_body_bytes: i64,
+    estimated_pdf_pages: i64,
+}
+
+#[derive(Serialize)]
+struct TreeStats {
+    max_depth: i64,
+    folder_count: i64,
+    max_children: i64,
+    avg_children: f64,
+}
+
+#[derive(Serialize, Clone)]
+struct FolderCount {
+    folder_id: String,
+    direct_count: i64,
+    total_count: i64,
+    updated_at: i64,
+}
+
+#[derive(Serialize, Clone)]
+struct ImportConflict {
+    id: String,
+    kind: String,
+    // Folders compare by name, notes by body
+    differs: bool,
+}
+
+#[derive(Serialize)]
+struct ArchiveExport {
+    path: String,
+    note_count: usize,
+}
+
+#[derive(Serialize)]
+struct FolderMarkdownExport {
+    path: String,
+    note_count: usize,
+    attachment_count: usize,
+    missing_attachments: Vec<String>,
+}
+
+#[derive(Serialize, Clone)]
+struct IncrementalResult {
+    path: String,
+    note_count: usize,
+    folder_count: usize,
+    // None when the label had no previous export and everything was written
+    since: Option<i64>,
+    exported_at: i64,
+}
+
+#[derive(Serialize)]
+struct UndoToken {
+    token: String,
+    moved: usize,
+}
+
+#[derive(Serialize)]
+struct MoveReport {
+    moved: usize,
+    ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct TagReport {
+    tag: String,
+    note_count: i64,
+    total_words: i64,
+}
+
+#[derive(Serialize)]
+struct TagRename {
+    from: String,
+    to: String,
+    renamed: bool,
+    merged: bool,
+    not_found: bool,
+}
+
+#[derive(Serialize)]
+struct ExtractReport {
+    tags_created: usize,
+    notes_affected: usize,
+}
+
+#[derive(Serialize)]
+struct LintIssue {
+    line: usize,
+    severity: String,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct Attachment {
+    id: String,
+    note_id: String,
+    file_name: String,
+    size: i64,
+    created_at: i64,
+}
+
+#[derive(Serialize)]
+struct BrokenAttachment {
+    note_id: String,
+    attachment_ref: String,
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct IngestReport {
+    attachments: Vec<Attachment>,
+    missing: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct EmptyReport {
+    empty_notes: Vec<String>,
+    empty_folders: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct CleanupReport {
+    scanned: usize,
+    changed: usize,
+    note_ids: Vec<String>,
+}
+
+#[derive(Serialize, Clone)]
+struct ImportReport {
+    inserted_folders: usize,
+    inserted_notes: usize,
+    skipped_conflicts: Vec<ImportConflict>,
+}
+
+#[derive(Serialize)]
+struct MergeReport {
+    folders: usize,
+    notes: usize,
+    tags: usize,
+    // Notes present in both databases whose content differs
+    conflicts: Vec<String>,
+    // Conflicting notes overwritten by the other copy (newer-wins only)
+    replaced: usize,
+}
+
+// Newest schema this build knows about: the last `user_version` set by
+// init_db's migrations. Bump it with each new migration.
+const REQUIRED_SCHEMA_VERSION: i32 = 14;
+
+// Opens and migrates the database at `path`. A database already migrated
+// past REQUIRED_SCHEMA_VERSION by a newer build is refused, since this build
+// could write rows that break that schema's assumptions.
+fn open_initialized_db(path: &std::path::Path) -> Result<Connection, String> {
+    let conn = Connection::open(path).map_err(|e| e.to_string())?;
+    let version: i32 = conn
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if version > REQUIRED_SCHEMA_VERSION {
+        return Err(format!(
+            "database schema version {} is newer than this build supports ({}); update anote",
+            version, REQUIRED_SCHEMA_VERSION
+        ));
+    }
+    init_db(&conn);
+    Ok(conn)
 }
 
 fn init_db(conn: &Connection) {
+    // Used by the content_hash triggers created below
+    conn.create_scalar_function(
+        "note_hash",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let title = ctx.get::<String>(0)?;
+            let body = ctx.get::<String>(1)?;
+            Ok(sha256::note_hash(&title, &body))
+        },
+    )
+    .unwrap();
+    // WAL is the default; a journal mode chosen with set_journal_mode wins.
+    // It's read before anything else runs so a database kept in DELETE mode
+    // never grows WAL files. (A new database has no app_settings table yet.)
+    let journal_mode = get_setting(conn, JOURNAL_MODE_SETTING)
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| "WAL".to_string());
+    if let Err(e) = apply_journal_mode(conn, &journal_mode) {
+        log::warn!("failed to set journal mode {}: {}", journal_mode, e);
+    }
     conn.execute_batch(
         "
-        PRAGMA journal_mode = WAL;
         PRAGMA synchronous = NORMAL;
         PRAGMA cache_size = -2000;
         PRAGMA foreign_keys = ON;
@@ -146,7 +563,409 @@ fn init_db(conn: &Connection) {
         }
         conn.pragma_update(None, "user_version", 2).unwrap();
     }
-    // Future migrations: if version < 3 { ... conn.pragma_update(None, "user_version", 3).unwrap(); }
+    if version < 3 {
+        let has_sort_order = conn
+            .prepare("SELECT sort_order FROM folders LIMIT 0")
+            .is_ok();
+        if !has_sort_order {
+            conn.execute(
+                "ALTER TABLE folders ADD COLUMN sort_order INTEGER NOT NULL DEFAULT 0",
+                [],
+            )
+            .unwrap();
+            // Siblings keep their current creation-time order
+            let _ = conn.execute_batch(
+                "
+                WITH ranked AS (
+                    SELECT id, ROW_NUMBER() OVER (PARTITION BY parent_id ORDER BY created_at) - 1 AS rn
+                    FROM folders
+                )
+                UPDATE folders SET sort_order = (SELECT rn FROM ranked WHERE ranked.id = folders.id)
+                ",
+            );
+        }
+        conn.pragma_update(None, "user_version", 3).unwrap();
+    }
+    if version < 4 {
+        let has_sort_mode = conn
+            .prepare("SELECT sort_mode FROM folders LIMIT 0")
+            .is_ok();
+        if !has_sort_mode {
+            conn.execute(
+                "ALTER TABLE folders ADD COLUMN sort_mode TEXT NOT NULL DEFAULT 'manual'",
+                [],
+            )
+            .unwrap();
+        }
+        conn.pragma_update(None, "user_version", 4).unwrap();
+    }
+    if version < 5 {
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS tags (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                color TEXT,
+                created_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS note_tags (
+                note_id TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+                tag_id TEXT NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+                PRIMARY KEY (note_id, tag_id)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_note_tags_tag ON note_tags(tag_id);
+            ",
+        )
+        .unwrap();
+        conn.pragma_update(None, "user_version", 5).unwrap();
+    }
+    if version < 6 {
+        // Cached per-folder note counts. direct_count covers the folder itself,
+        // total_count rolls up the whole subtree; triggers keep both current.
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS folder_counts (
+                folder_id TEXT PRIMARY KEY REFERENCES folders(id) ON DELETE CASCADE,
+                direct_count INTEGER NOT NULL DEFAULT 0,
+                total_count INTEGER NOT NULL DEFAULT 0,
+                updated_at INTEGER NOT NULL DEFAULT 0
+            );
+
+            CREATE TRIGGER IF NOT EXISTS folder_counts_folder_ai AFTER INSERT ON folders BEGIN
+                INSERT OR IGNORE INTO folder_counts (folder_id, updated_at)
+                VALUES (new.id, strftime('%s', 'now') * 1000);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS folder_counts_note_ai AFTER INSERT ON notes BEGIN
+                UPDATE folder_counts SET direct_count = direct_count + 1
+                WHERE folder_id = new.folder_id;
+                UPDATE folder_counts SET total_count = total_count + 1, updated_at = strftime('%s', 'now') * 1000
+                WHERE folder_id IN (
+                    WITH RECURSIVE anc(id) AS (
+                        SELECT new.folder_id
+                        UNION ALL
+                        SELECT f.parent_id FROM folders f JOIN anc ON f.id = anc.id WHERE f.parent_id IS NOT NULL
+                    ) SELECT id FROM anc
+                );
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS folder_counts_note_ad AFTER DELETE ON notes BEGIN
+                UPDATE folder_counts SET direct_count = direct_count - 1
+                WHERE folder_id = old.folder_id;
+                UPDATE folder_counts SET total_count = total_count - 1, updated_at = strftime('%s', 'now') * 1000
+                WHERE folder_id IN (
+                    WITH RECURSIVE anc(id) AS (
+                        SELECT old.folder_id
+                        UNION ALL
+                        SELECT f.parent_id FROM folders f JOIN anc ON f.id = anc.id WHERE f.parent_id IS NOT NULL
+                    ) SELECT id FROM anc
+                );
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS folder_counts_note_au AFTER UPDATE OF folder_id ON notes
+            WHEN old.folder_id != new.folder_id BEGIN
+                UPDATE folder_counts SET direct_count = direct_count - 1
+                WHERE folder_id = old.folder_id;
+                UPDATE folder_counts SET direct_count = direct_count + 1
+                WHERE folder_id = new.folder_id;
+                UPDATE folder_counts SET total_count = total_count - 1, updated_at = strftime('%s', 'now') * 1000
+                WHERE folder_id IN (
+                    WITH RECURSIVE anc(id) AS (
+                        SELECT old.folder_id
+                        UNION ALL
+                        SELECT f.parent_id FROM folders f JOIN anc ON f.id = anc.id WHERE f.parent_id IS NOT NULL
+                    ) SELECT id FROM anc
+                );
+                UPDATE folder_counts SET total_count = total_count + 1, updated_at = strftime('%s', 'now') * 1000
+                WHERE folder_id IN (
+                    WITH RECURSIVE anc(id) AS (
+                        SELECT new.folder_id
+                        UNION ALL
+                        SELECT f.parent_id FROM folders f JOIN anc ON f.id = anc.id WHERE f.parent_id IS NOT NULL
+                    ) SELECT id FROM anc
+                );
+            END;
+
+            -- Reparenting moves the folder's whole subtree total between ancestor chains
+            CREATE TRIGGER IF NOT EXISTS folder_counts_folder_au AFTER UPDATE OF parent_id ON folders
+            WHEN old.parent_id IS NOT new.parent_id BEGIN
+                UPDATE folder_counts SET total_count = total_count - (SELECT total_count FROM folder_counts WHERE folder_id = new.id),
+                    updated_at = strftime('%s', 'now') * 1000
+                WHERE folder_id IN (
+                    WITH RECURSIVE anc(id) AS (
+                        SELECT old.parent_id
+                        UNION ALL
+                        SELECT f.parent_id FROM folders f JOIN anc ON f.id = anc.id WHERE f.parent_id IS NOT NULL
+                    ) SELECT id FROM anc
+                );
+                UPDATE folder_counts SET total_count = total_count + (SELECT total_count FROM folder_counts WHERE folder_id = new.id),
+                    updated_at = strftime('%s', 'now') * 1000
+                WHERE folder_id IN (
+                    WITH RECURSIVE anc(id) AS (
+                        SELECT new.parent_id
+                        UNION ALL
+                        SELECT f.parent_id FROM folders f JOIN anc ON f.id = anc.id WHERE f.parent_id IS NOT NULL
+                    ) SELECT id FROM anc
+                );
+            END;
+            ",
+        )
+        .unwrap();
+        rebuild_folder_counts(conn).unwrap();
+        conn.pragma_update(None, "user_version", 6).unwrap();
+    }
+
+    if version < 7 {
+        // Starred is independent of pinned: importance vs. position in the folder
+        let has_starred: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('notes') WHERE name = 'starred'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        if !has_starred {
+            conn.execute(
+                "ALTER TABLE notes ADD COLUMN starred INTEGER NOT NULL DEFAULT 0",
+                [],
+            )
+            .unwrap();
+        }
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_notes_starred ON notes(starred) WHERE starred = 1",
+            [],
+        )
+        .unwrap();
+        conn.pragma_update(None, "user_version", 7).unwrap();
+    }
+
+    if version < 8 {
+        // Small key-value store for app-level settings (e.g. the inbox folder)
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS app_settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+            ",
+        )
+        .unwrap();
+        conn.pragma_update(None, "user_version", 8).unwrap();
+    }
+    if version < 9 {
+        // Keyset pagination for the global feed
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_notes_updated ON notes(updated_at, id);",
+        )
+        .unwrap();
+        conn.pragma_update(None, "user_version", 9).unwrap();
+    }
+    if version < 10 {
+        // Saved copies of notes; `reason` is 'edit', 'manual' or 'restore'
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS note_versions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                note_id TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+                title TEXT NOT NULL,
+                body TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                reason TEXT NOT NULL DEFAULT 'edit'
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_note_versions_note ON note_versions(note_id, id);
+            ",
+        )
+        .unwrap();
+        conn.pragma_update(None, "user_version", 10).unwrap();
+    }
+    if version < 11 {
+        // SHA-256 of title + "\0" + body for cheap change detection, kept
+        // current by TEMP triggers (see below). The FTS update trigger is
+        // narrowed to title/body so the hash write (and pin/order changes)
+        // don't re-index the note.
+        if conn
+            .prepare("SELECT content_hash FROM notes LIMIT 0")
+            .is_err()
+        {
+            conn.execute("ALTER TABLE notes ADD COLUMN content_hash TEXT", [])
+                .unwrap();
+        }
+        conn.execute_batch(
+            "
+            DROP TRIGGER IF EXISTS notes_au;
+            CREATE TRIGGER notes_au AFTER UPDATE OF title, body ON notes BEGIN
+                INSERT INTO notes_fts(notes_fts, rowid, title, body) VALUES('delete', old.rowid, old.title, old.body);
+                INSERT INTO notes_fts(rowid, title, body) VALUES (new.rowid, new.title, new.body);
+            END;
+
+            UPDATE notes SET content_hash = note_hash(title, body);
+            ",
+        )
+        .unwrap();
+        conn.pragma_update(None, "user_version", 11).unwrap();
+    }
+    if version < 12 {
+        // When each tag was put on each note, for recent-tag suggestions.
+        // Existing links are dated by their note's last edit.
+        if conn
+            .prepare("SELECT applied_at FROM note_tags LIMIT 0")
+            .is_err()
+        {
+            conn.execute("ALTER TABLE note_tags ADD COLUMN applied_at INTEGER", [])
+                .unwrap();
+        }
+        conn.execute_batch(
+            "
+            CREATE TRIGGER IF NOT EXISTS note_tags_applied AFTER INSERT ON note_tags
+            WHEN new.applied_at IS NULL BEGIN
+                UPDATE note_tags SET applied_at = CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)
+                WHERE note_id = new.note_id AND tag_id = new.tag_id;
+            END;
+
+            UPDATE note_tags SET applied_at = (SELECT updated_at FROM notes WHERE notes.id = note_tags.note_id)
+            WHERE applied_at IS NULL;
+            ",
+        )
+        .unwrap();
+        conn.pragma_update(None, "user_version", 12).unwrap();
+    }
+    if version < 13 {
+        // Files copied into ~/.anote/attachments/, stored as `<id>.<ext>` and
+        // referenced from note bodies as anote://attachment/<id>
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS attachments (
+                id TEXT PRIMARY KEY,
+                note_id TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+                file_name TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_attachments_note ON attachments(note_id);
+            ",
+        )
+        .unwrap();
+        conn.pragma_update(None, "user_version", 13).unwrap();
+    }
+    if version < 14 {
+        // Free-form key/value fields per note (status, priority, ...)
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS note_meta (
+                note_id TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (note_id, key)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_note_meta_key ON note_meta(key, value);
+            ",
+        )
+        .unwrap();
+        conn.pragma_update(None, "user_version", 14).unwrap();
+    }
+    // Future migrations: if version < 15 { ... conn.pragma_update(None, "user_version", 15).unwrap(); }
+    // (and bump REQUIRED_SCHEMA_VERSION to match)
+
+    // content_hash is kept current by triggers that live only on this
+    // connection, since note_hash() doesn't exist for other SQLite clients.
+    // Notes they insert get a hash here; notes they edit keep a stale one
+    // until rehash_notes runs.
+    conn.execute_batch(
+        "
+        CREATE TEMP TRIGGER IF NOT EXISTS notes_hash_ai AFTER INSERT ON main.notes BEGIN
+            UPDATE notes SET content_hash = note_hash(new.title, new.body) WHERE rowid = new.rowid;
+        END;
+
+        CREATE TEMP TRIGGER IF NOT EXISTS notes_hash_au AFTER UPDATE OF title, body ON main.notes BEGIN
+            UPDATE notes SET content_hash = note_hash(new.title, new.body) WHERE rowid = new.rowid;
+        END;
+
+        UPDATE notes SET content_hash = note_hash(title, body) WHERE content_hash IS NULL;
+        ",
+    )
+    .unwrap();
+}
+
+// IDs are app-generated alphanumeric (base36)
+fn is_safe_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+// Mirrors the frontend's generateId(): base36 millis + 5 random base36 chars
+fn generate_id() -> String {
+    use std::hash::{BuildHasher, Hasher};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    fn base36(mut n: u64) -> String {
+        const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+        let mut out = Vec::new();
+        loop {
+            out.push(DIGITS[(n % 36) as usize]);
+            n /= 36;
+            if n == 0 {
+                break;
+            }
+        }
+        out.reverse();
+        String::from_utf8(out).unwrap()
+    }
+
+    let now = chrono::Utc::now().timestamp_millis() as u64;
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    let suffix = base36(hasher.finish() % 36u64.pow(5));
+    format!("{}{:0>5}", base36(now), suffix)
+}
+
+const DEFAULT_PREVIEW_LEN: i64 = 200;
+
+// Preview projection over `n.body`, clamped to 0..=1000 chars. A length of 0
+// projects an empty string so the body column is never read.
+fn preview_column(preview_len: Option<i64>) -> String {
+    match preview_len.unwrap_or(DEFAULT_PREVIEW_LEN).clamp(0, 1000) {
+        0 => "''".to_string(),
+        len => format!("substr(n.body, 1, {})", len),
+    }
+}
+
+// The first `len` characters of a body's plain text, cut back to a word
+// boundary with an ellipsis when truncated.
+fn excerpt(body: &str, len: usize) -> String {
+    let text = markdown::plain_text(body);
+    if text.chars().count() <= len {
+        return text;
+    }
+    let cut: String = text.chars().take(len).collect();
+    let next_is_space = text.chars().nth(len).is_some_and(char::is_whitespace);
+    let cut = match cut.rfind(' ') {
+        Some(i) if !next_is_space => &cut[..i],
+        _ => &cut[..],
+    };
+    format!("{}…", cut.trim_end())
+}
+
+// Maps the standard metadata projection:
+// id, folder_id, title, preview, created_at, updated_at, pinned, sort_order, starred,
+// content_hash
+fn note_metadata_from_row(row: &rusqlite::Row) -> rusqlite::Result<NoteMetadata> {
+    Ok(NoteMetadata {
+        id: row.get(0)?,
+        folder_id: row.get(1)?,
+        title: row.get(2)?,
+        preview: row.get(3)?,
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+        pinned: row.get(6)?,
+        sort_order: row.get(7)?,
+        starred: row.get(8)?,
+        content_hash: row.get(9)?,
+    })
 }
 
 // ===== Folder commands =====
@@ -155,7 +974,9 @@ fn init_db(conn: &Connection) {
 fn get_folders(db: State<Db>) -> Result<Vec<Folder>, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
     let mut stmt = conn
-        .prepare("SELECT id, name, created_at, parent_id FROM folders ORDER BY created_at")
+        .prepare(
+            "SELECT id, name, created_at, parent_id, sort_order, sort_mode FROM folders ORDER BY created_at",
+        )
         .map_err(|e| e.to_string())?;
     let folders = stmt
         .query_map([], |row| {
@@ -164,6 +985,8 @@ fn get_folders(db: State<Db>) -> Result<Vec<Folder>, String> {
                 name: row.get(1)?,
                 created_at: row.get(2)?,
                 parent_id: row.get(3)?,
+                sort_order: row.get(4)?,
+                sort_mode: row.get(5)?,
             })
         })
         .map_err(|e| e.to_string())?
@@ -180,263 +1003,6223 @@ fn create_folder(
     created_at: i64,
     parent_id: Option<String>,
 ) -> Result<(), String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
-    conn.execute(
-        "INSERT INTO folders (id, name, created_at, parent_id) VALUES (?1, ?2, ?3, ?4)",
-        rusqlite::params![id, name, created_at, parent_id],
+    let conn = db.write()?;
+    insert_folder(&conn, &id, &name, created_at, parent_id.as_deref())
+}
+
+// Inserts a folder after its existing siblings
+fn insert_folder(
+    conn: &Connection,
+    id: &str,
+    name: &str,
+    created_at: i64,
+    parent_id: Option<&str>,
+) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO folders (id, name, created_at, parent_id, sort_order) \
+         VALUES (?1, ?2, ?3, ?4, (SELECT COALESCE(MAX(sort_order) + 1, 0) FROM folders WHERE parent_id IS ?4))",
+        rusqlite::params![id, name, created_at, parent_id],
     )
     .map_err(|e| e.to_string())?;
     Ok(())
 }
 
+// Resolves a "A/B/C" path to the leaf folder id, reusing folders that already
+// exist by name under each parent and creating the missing segments.
 #[tauri::command]
-fn rename_folder(db: State<Db>, id: String, name: String) -> Result<(), String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
-    conn.execute(
+fn ensure_folder_path(db: State<Db>, path: String) -> Result<String, String> {
+    let segments: Vec<&str> = path
+        .split('/')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+    if segments.is_empty() {
+        return Err("folder path is empty".to_string());
+    }
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut parent: Option<String> = None;
+    for name in segments {
+        let existing: Option<String> = tx
+            .query_row(
+                "SELECT id FROM folders WHERE parent_id IS ?1 AND name = ?2 \
+                 ORDER BY created_at LIMIT 1",
+                rusqlite::params![parent, name],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let id = match existing {
+            Some(id) => id,
+            None => {
+                let id = generate_id();
+                insert_folder(&tx, &id, name, now, parent.as_deref())?;
+                id
+            }
+        };
+        parent = Some(id);
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    parent.ok_or_else(|| "folder path is empty".to_string())
+}
+
+#[tauri::command]
+fn rename_folder(
+    db: State<Db>,
+    id: String,
+    name: String,
+    bump_notes: Option<bool>,
+) -> Result<(), String> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute(
         "UPDATE folders SET name = ?1 WHERE id = ?2",
         rusqlite::params![name, id],
     )
     .map_err(|e| e.to_string())?;
+    // Opt-in: touching every descendant note is a mass update
+    if bump_notes.unwrap_or(false) {
+        bump_subtree_updated_at(&tx, &id)?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+// Advance updated_at on all notes under the folder's subtree so path-dependent
+// consumers (breadcrumb caches, delta sync) notice the folder changed.
+fn bump_subtree_updated_at(conn: &Connection, folder_id: &str) -> Result<usize, String> {
+    let now = chrono::Utc::now().timestamp_millis();
+    conn.execute(
+        "WITH RECURSIVE subtree(id) AS ( \
+             SELECT ?1 \
+             UNION ALL \
+             SELECT f.id FROM folders f JOIN subtree s ON f.parent_id = s.id \
+         ) \
+         UPDATE notes SET updated_at = MAX(updated_at + 1, ?2) \
+         WHERE folder_id IN (SELECT id FROM subtree)",
+        rusqlite::params![folder_id, now],
+    )
+    .map_err(|e| e.to_string())
+}
+
+// Deletes a folder and its subfolders. Their notes go to the inbox unless
+// `notes_action` is "delete".
+#[tauri::command]
+fn delete_folder(db: State<Db>, id: String, notes_action: Option<String>) -> Result<(), String> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let exists: bool = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM folders WHERE id = ?1)",
+            rusqlite::params![id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err("folder not found".to_string());
+    }
+    match notes_action.as_deref().unwrap_or("move_to_inbox") {
+        "delete" => {}
+        "move_to_inbox" => move_subtree_notes_to_inbox(&tx, &id)?,
+        other => return Err(format!("unknown notes action: {}", other)),
+    }
+    let attachments = delete_folder_recursive(&tx, &id)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    remove_attachment_files(&attachments);
+    Ok(())
+}
+
+// Rescues the notes of a folder about to be deleted (and of its subfolders)
+// by appending them to the inbox. A new top-level "Inbox" is created when
+// there is none, or when the inbox itself is among the deleted folders.
+fn move_subtree_notes_to_inbox(conn: &Connection, folder_id: &str) -> Result<(), String> {
+    let subtree = subtree_folder_ids(conn, folder_id)?;
+    let mut note_count = 0;
+    for id in &subtree {
+        note_count += conn
+            .query_row(
+                "SELECT COUNT(*) FROM notes WHERE folder_id = ?1",
+                rusqlite::params![id],
+                |row| row.get::<_, i64>(0),
+            )
+            .map_err(|e| e.to_string())?;
+    }
+    if note_count == 0 {
+        return Ok(());
+    }
+    let inbox = match inbox_folder_id(conn)? {
+        Some(inbox) if !subtree.contains(&inbox) => inbox,
+        _ => {
+            let inbox = generate_id();
+            let now = chrono::Utc::now().timestamp_millis();
+            insert_folder(conn, &inbox, "Inbox", now, None)?;
+            inbox
+        }
+    };
+    for id in &subtree {
+        let mut stmt = conn
+            .prepare("SELECT id, pinned FROM notes WHERE folder_id = ?1 ORDER BY sort_order, rowid")
+            .map_err(|e| e.to_string())?;
+        let notes: Vec<(String, i32)> = stmt
+            .query_map(rusqlite::params![id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        for (note_id, pinned) in &notes {
+            conn.execute(
+                "UPDATE notes SET folder_id = ?1, sort_order = ( \
+                     SELECT COALESCE(MAX(sort_order) + 1, 0) FROM notes \
+                     WHERE folder_id = ?1 AND pinned = ?2 \
+                 ) WHERE id = ?3",
+                rusqlite::params![inbox, pinned, note_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
     Ok(())
 }
 
+// Deletes a folder, its subfolders and their notes. Returns the ids of the
+// attachments that went with the notes, whose files the caller removes.
+fn delete_folder_recursive(conn: &Connection, id: &str) -> Result<Vec<String>, String> {
+    let mut attachments = Vec::new();
+    for folder_id in subtree_folder_ids(conn, id)?.iter().rev() {
+        let mut stmt = conn
+            .prepare(
+                "SELECT a.id FROM attachments a JOIN notes n ON n.id = a.note_id \
+                 WHERE n.folder_id = ?1",
+            )
+            .map_err(|e| e.to_string())?;
+        let ids = stmt
+            .query_map(rusqlite::params![folder_id], |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        attachments.extend(ids);
+        conn.execute(
+            "DELETE FROM notes WHERE folder_id = ?1",
+            rusqlite::params![folder_id],
+        )
+        .map_err(|e| e.to_string())?;
+        conn.execute(
+            "DELETE FROM folders WHERE id = ?1",
+            rusqlite::params![folder_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(attachments)
+}
+
 #[tauri::command]
-fn delete_folder(db: State<Db>, id: String) -> Result<(), String> {
+fn get_subtree_folder_ids(db: State<Db>, folder_id: String) -> Result<Vec<String>, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
-    delete_folder_recursive(&conn, &id)?;
+    subtree_folder_ids(&conn, &folder_id)
+}
+
+// The folder itself followed by all of its descendants, parents before
+// children. Empty when the folder doesn't exist.
+fn subtree_folder_ids(conn: &Connection, folder_id: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(
+            "WITH RECURSIVE subtree(id, depth) AS ( \
+                 SELECT id, 0 FROM folders WHERE id = ?1 \
+                 UNION \
+                 SELECT f.id, s.depth + 1 FROM folders f JOIN subtree s ON f.parent_id = s.id \
+             ) \
+             SELECT id FROM subtree ORDER BY depth, id",
+        )
+        .map_err(|e| e.to_string())?;
+    let ids = stmt
+        .query_map(rusqlite::params![folder_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(ids)
+}
+
+// Copies a folder (and with `deep`, its whole subtree) under `new_parent_id`,
+// or to the top level when None. Notes get new ids and fresh timestamps but
+// keep their content, pin state, order and tags. Returns the new root's id.
+#[tauri::command]
+fn clone_folder(
+    db: State<Db>,
+    folder_id: String,
+    new_parent_id: Option<String>,
+    deep: bool,
+) -> Result<String, String> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let source_ids = if deep {
+        subtree_folder_ids(&tx, &folder_id)?
+    } else {
+        vec![folder_id.clone()]
+    };
+    if source_ids.is_empty() {
+        return Err("folder not found".to_string());
+    }
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut id_map: std::collections::HashMap<String, String> = std::collections::HashMap::new();
+    for source_id in &source_ids {
+        let (name, parent_id, sort_order, sort_mode): (String, Option<String>, i32, String) = tx
+            .query_row(
+                "SELECT name, parent_id, sort_order, sort_mode FROM folders WHERE id = ?1",
+                rusqlite::params![source_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        let id = generate_id();
+        if id_map.is_empty() {
+            // The root goes after its new siblings
+            insert_folder(&tx, &id, &name, now, new_parent_id.as_deref())?;
+            tx.execute(
+                "UPDATE folders SET sort_mode = ?1 WHERE id = ?2",
+                rusqlite::params![sort_mode, id],
+            )
+            .map_err(|e| e.to_string())?;
+        } else {
+            let parent = parent_id.and_then(|p| id_map.get(&p).cloned());
+            tx.execute(
+                "INSERT INTO folders (id, name, created_at, parent_id, sort_order, sort_mode) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                rusqlite::params![id, name, now, parent, sort_order, sort_mode],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        id_map.insert(source_id.clone(), id);
+    }
+
+    for source_id in &source_ids {
+        let mut stmt = tx
+            .prepare("SELECT id FROM notes WHERE folder_id = ?1 ORDER BY rowid")
+            .map_err(|e| e.to_string())?;
+        let note_ids = stmt
+            .query_map(rusqlite::params![source_id], |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        for note_id in note_ids {
+            let new_id = generate_id();
+            tx.execute(
+                "INSERT INTO notes (id, folder_id, title, body, created_at, updated_at, pinned, sort_order, starred) \
+                 SELECT ?1, ?2, title, body, ?3, ?3, pinned, sort_order, starred FROM notes WHERE id = ?4",
+                rusqlite::params![new_id, id_map[source_id], now, note_id],
+            )
+            .map_err(|e| e.to_string())?;
+            tx.execute(
+                "INSERT INTO note_tags (note_id, tag_id) SELECT ?1, tag_id FROM note_tags WHERE note_id = ?2",
+                rusqlite::params![new_id, note_id],
+            )
+            .map_err(|e| e.to_string())?;
+            tx.execute(
+                "INSERT INTO note_meta (note_id, key, value) SELECT ?1, key, value FROM note_meta WHERE note_id = ?2",
+                rusqlite::params![new_id, note_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+    let root = id_map[&folder_id].clone();
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(root)
+}
+
+#[tauri::command]
+fn set_folder_sort_mode(db: State<Db>, id: String, mode: String) -> Result<(), String> {
+    if !FOLDER_SORT_MODES.contains(&mode.as_str()) {
+        return Err(format!("invalid sort mode: {}", mode));
+    }
+    let conn = db.write()?;
+    let changed = conn
+        .execute(
+            "UPDATE folders SET sort_mode = ?1 WHERE id = ?2",
+            rusqlite::params![mode, id],
+        )
+        .map_err(|e| e.to_string())?;
+    if changed == 0 {
+        return Err("folder not found".to_string());
+    }
+    Ok(())
+}
+
+// Renumbers the children of `parent_id` (roots when None) to 0..n, keeping
+// their current relative order. With `recursive`, every group below is renumbered too.
+#[tauri::command]
+fn normalize_folder_order(
+    db: State<Db>,
+    parent_id: Option<String>,
+    recursive: Option<bool>,
+) -> Result<(), String> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut parents = vec![parent_id];
+    if recursive.unwrap_or(false) {
+        let mut stmt = tx
+            .prepare(
+                "WITH RECURSIVE subtree(id) AS ( \
+                     SELECT id FROM folders WHERE parent_id IS ?1 \
+                     UNION ALL \
+                     SELECT f.id FROM folders f JOIN subtree s ON f.parent_id = s.id \
+                 ) \
+                 SELECT id FROM subtree",
+            )
+            .map_err(|e| e.to_string())?;
+        let descendants = stmt
+            .query_map(rusqlite::params![parents[0]], |row| row.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|e| e.to_string())?;
+        parents.extend(descendants.into_iter().map(Some));
+    }
+    for parent in &parents {
+        normalize_folder_siblings(&tx, parent.as_deref())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn normalize_folder_siblings(conn: &Connection, parent_id: Option<&str>) -> Result<(), String> {
+    conn.execute(
+        "WITH ranked AS ( \
+             SELECT id, ROW_NUMBER() OVER (ORDER BY sort_order, created_at, id) - 1 AS rn \
+             FROM folders WHERE parent_id IS ?1 \
+         ) \
+         UPDATE folders SET sort_order = (SELECT rn FROM ranked WHERE ranked.id = folders.id) \
+         WHERE id IN (SELECT id FROM ranked)",
+        rusqlite::params![parent_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Reparents a folder and places it at `position` among its new siblings
+// (clamped to the end), renumbering both the new and the old sibling groups.
+#[tauri::command]
+fn move_folder_to_position(
+    db: State<Db>,
+    id: String,
+    parent_id: Option<String>,
+    position: i32,
+) -> Result<(), String> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let old_parent: Option<String> = tx
+        .query_row(
+            "SELECT parent_id FROM folders WHERE id = ?1",
+            rusqlite::params![id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or("folder not found")?;
+    if let Some(parent) = &parent_id {
+        // subtree_folder_ids includes the folder itself
+        let subtree = subtree_folder_ids(&tx, &id)?;
+        if subtree.contains(parent) {
+            return Err("cannot move a folder into itself or one of its subfolders".to_string());
+        }
+        let exists: bool = tx
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM folders WHERE id = ?1)",
+                rusqlite::params![parent],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if !exists {
+            return Err("parent folder not found".to_string());
+        }
+    }
+
+    let mut stmt = tx
+        .prepare(
+            "SELECT id FROM folders WHERE parent_id IS ?1 AND id != ?2 \
+             ORDER BY sort_order, created_at, id",
+        )
+        .map_err(|e| e.to_string())?;
+    let mut siblings = stmt
+        .query_map(rusqlite::params![parent_id, id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+    let index = (position.max(0) as usize).min(siblings.len());
+    siblings.insert(index, id.clone());
+
+    tx.execute(
+        "UPDATE folders SET parent_id = ?1 WHERE id = ?2",
+        rusqlite::params![parent_id, id],
+    )
+    .map_err(|e| e.to_string())?;
+    for (i, sibling) in siblings.iter().enumerate() {
+        tx.execute(
+            "UPDATE folders SET sort_order = ?1 WHERE id = ?2",
+            rusqlite::params![i as i32, sibling],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    if old_parent != parent_id {
+        normalize_folder_siblings(&tx, old_parent.as_deref())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Moves a folder's subfolders and notes up into its parent, then deletes the
+// folder. Subfolders and notes are appended after the parent's own, keeping
+// their relative order. Fails if a subfolder's name is already taken there.
+#[tauri::command]
+fn dissolve_folder(db: State<Db>, folder_id: String) -> Result<(), String> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let parent_id: Option<String> = tx
+        .query_row(
+            "SELECT parent_id FROM folders WHERE id = ?1",
+            rusqlite::params![folder_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or("folder not found")?;
+    let Some(parent_id) = parent_id else {
+        return Err("cannot dissolve a top-level folder".to_string());
+    };
+
+    let mut stmt = tx
+        .prepare(
+            "SELECT id, name FROM folders WHERE parent_id = ?1 \
+             ORDER BY sort_order, created_at, id",
+        )
+        .map_err(|e| e.to_string())?;
+    let children: Vec<(String, String)> = stmt
+        .query_map(rusqlite::params![folder_id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+    for (id, name) in &children {
+        let taken: bool = tx
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM folders WHERE parent_id = ?1 AND id != ?2 AND name = ?3)",
+                rusqlite::params![parent_id, folder_id, name],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if taken {
+            return Err(format!("a folder named \"{}\" already exists there", name));
+        }
+        tx.execute(
+            "UPDATE folders SET parent_id = ?1, sort_order = ( \
+                 SELECT COALESCE(MAX(sort_order) + 1, 0) FROM folders \
+                 WHERE parent_id = ?1 AND id != ?2 \
+             ) WHERE id = ?3",
+            rusqlite::params![parent_id, folder_id, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    let mut stmt = tx
+        .prepare("SELECT id, pinned FROM notes WHERE folder_id = ?1 ORDER BY sort_order, rowid")
+        .map_err(|e| e.to_string())?;
+    let notes: Vec<(String, i32)> = stmt
+        .query_map(rusqlite::params![folder_id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+    for (id, pinned) in &notes {
+        tx.execute(
+            "UPDATE notes SET folder_id = ?1, sort_order = ( \
+                 SELECT COALESCE(MAX(sort_order) + 1, 0) FROM notes \
+                 WHERE folder_id = ?1 AND pinned = ?2 \
+             ) WHERE id = ?3",
+            rusqlite::params![parent_id, pinned, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    tx.execute(
+        "DELETE FROM folders WHERE id = ?1",
+        rusqlite::params![folder_id],
+    )
+    .map_err(|e| e.to_string())?;
+    normalize_folder_siblings(&tx, Some(&parent_id))?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Breadcrumb path ("Parent / Child") for every folder id
+fn folder_paths(conn: &Connection) -> Result<std::collections::HashMap<String, String>, String> {
+    let mut stmt = conn
+        .prepare(
+            "WITH RECURSIVE paths(id, path) AS ( \
+                 SELECT id, name FROM folders WHERE parent_id IS NULL \
+                 UNION ALL \
+                 SELECT f.id, p.path || ' / ' || f.name FROM folders f JOIN paths p ON f.parent_id = p.id \
+             ) \
+             SELECT id, path FROM paths",
+        )
+        .map_err(|e| e.to_string())?;
+    let paths = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(paths)
+}
+
+// ===== Folder count cache =====
+
+fn rebuild_folder_counts(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "
+        DELETE FROM folder_counts;
+        INSERT INTO folder_counts (folder_id, direct_count, total_count, updated_at)
+        WITH RECURSIVE direct(id, cnt) AS (
+            SELECT f.id, (SELECT COUNT(*) FROM notes n WHERE n.folder_id = f.id) FROM folders f
+        ),
+        anc(folder_id, ancestor_id) AS (
+            SELECT id, id FROM folders
+            UNION ALL
+            SELECT anc.folder_id, f.parent_id FROM anc
+            JOIN folders f ON f.id = anc.ancestor_id
+            WHERE f.parent_id IS NOT NULL
+        )
+        SELECT d.id, d.cnt,
+            (SELECT SUM(d2.cnt) FROM anc JOIN direct d2 ON d2.id = anc.folder_id WHERE anc.ancestor_id = d.id),
+            strftime('%s', 'now') * 1000
+        FROM direct d;
+        ",
+    )
+    .map_err(|e| e.to_string())
+}
+
+// Safety net for the trigger-maintained cache (e.g. after folders were removed
+// by FK cascade, which the ancestor walk can't see)
+#[tauri::command]
+fn recompute_folder_counts(db: State<Db>) -> Result<(), String> {
+    let _maintenance = db.maintenance();
+    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    rebuild_folder_counts(&tx)?;
+    tx.commit().map_err(|e| e.to_string())?;
     Ok(())
 }
 
-fn delete_folder_recursive(conn: &Connection, id: &str) -> Result<(), String> {
+#[tauri::command]
+fn get_folder_counts(db: State<Db>) -> Result<Vec<FolderCount>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT folder_id, direct_count, total_count, updated_at FROM folder_counts")
+        .map_err(|e| e.to_string())?;
+    let counts = stmt
+        .query_map([], |row| {
+            Ok(FolderCount {
+                folder_id: row.get(0)?,
+                direct_count: row.get(1)?,
+                total_count: row.get(2)?,
+                updated_at: row.get(3)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(counts)
+}
+
+// ===== Note commands =====
+
+// With `plain_preview`, previews are excerpts of the Markdown-free text
+// rather than the raw start of the body.
+#[tauri::command]
+fn get_notes_metadata(
+    db: State<Db>,
+    preview_len: Option<i64>,
+    plain_preview: Option<bool>,
+) -> Result<Vec<NoteMetadata>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let plain = plain_preview.unwrap_or(false);
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT n.id, n.folder_id, n.title, {}, n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+             FROM notes n JOIN folders f ON f.id = n.folder_id \
+             ORDER BY {}",
+            if plain {
+                "n.body".to_string()
+            } else {
+                preview_column(preview_len)
+            },
+            FOLDER_NOTES_ORDER
+        ))
+        .map_err(|e| e.to_string())?;
+    let mut notes = stmt
+        .query_map([], note_metadata_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    if plain {
+        let len = preview_len.unwrap_or(DEFAULT_PREVIEW_LEN).clamp(0, 1000) as usize;
+        for note in &mut notes {
+            note.preview = excerpt(&note.preview, len);
+        }
+    }
+    Ok(notes)
+}
+
+// Notes edited within a calendar range in the user's local timezone:
+// "today", "yesterday", "this_week" (from Monday) or "this_month".
+#[tauri::command]
+fn get_notes_in_local_range(db: State<Db>, range: String) -> Result<Vec<NoteMetadata>, String> {
+    let (start, end) = local_range_bounds(&range, chrono::Local::now())?;
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
     let mut stmt = conn
-        .prepare("SELECT id FROM folders WHERE parent_id = ?1")
+        .prepare(
+            "SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+             n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+             FROM notes n \
+             WHERE n.updated_at >= ?1 AND n.updated_at < ?2 \
+             ORDER BY n.updated_at DESC, n.id",
+        )
         .map_err(|e| e.to_string())?;
-    let children: Vec<String> = stmt
-        .query_map(rusqlite::params![id], |row| row.get(0))
+    let notes = stmt
+        .query_map(rusqlite::params![start, end], note_metadata_from_row)
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+// [start, end) in UTC millis for a named range around `now`, with day
+// boundaries taken at local midnight in `now`'s timezone.
+fn local_range_bounds<Tz: chrono::TimeZone>(
+    range: &str,
+    now: chrono::DateTime<Tz>,
+) -> Result<(i64, i64), String> {
+    use chrono::{Datelike, Days, Months};
+    let today = now.date_naive();
+    let (first, last) = match range {
+        "today" => (today, today + Days::new(1)),
+        "yesterday" => (today - Days::new(1), today),
+        "this_week" => {
+            let monday = today - Days::new(today.weekday().num_days_from_monday() as u64);
+            (monday, monday + Days::new(7))
+        }
+        "this_month" => {
+            let first = today.with_day(1).ok_or("invalid date")?;
+            (first, first + Months::new(1))
+        }
+        _ => return Err(format!("unknown range: {}", range)),
+    };
+    let tz = now.timezone();
+    Ok((local_midnight(&tz, first)?, local_midnight(&tz, last)?))
+}
+
+// Start of `date` in `tz` as UTC millis. Midnight can be skipped by a DST
+// change; take the first valid instant after it.
+fn local_midnight<Tz: chrono::TimeZone>(tz: &Tz, date: chrono::NaiveDate) -> Result<i64, String> {
+    (0..24)
+        .find_map(|hour| {
+            tz.from_local_datetime(&date.and_hms_opt(hour, 0, 0)?)
+                .earliest()
+        })
+        .map(|t| t.timestamp_millis())
+        .ok_or_else(|| "invalid local date".to_string())
+}
+
+// Note counts by creation date, bucketed by local "day", "week" (from
+// Monday) or "month", oldest first. Empty buckets are left out.
+#[tauri::command]
+fn get_notes_histogram(db: State<Db>, bucket: String) -> Result<Vec<HistogramBucket>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    notes_histogram(&conn, &bucket, &chrono::Local)
+}
+
+fn notes_histogram<Tz: chrono::TimeZone>(
+    conn: &Connection,
+    bucket: &str,
+    tz: &Tz,
+) -> Result<Vec<HistogramBucket>, String> {
+    use chrono::{Datelike, Days};
+    if !matches!(bucket, "day" | "week" | "month") {
+        return Err(format!("unknown bucket: {}", bucket));
+    }
+    let mut stmt = conn
+        .prepare("SELECT created_at FROM notes")
+        .map_err(|e| e.to_string())?;
+    let created: Vec<i64> = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut counts: std::collections::BTreeMap<i64, i64> = std::collections::BTreeMap::new();
+    // Many notes share a day; convert each day once
+    let mut starts: std::collections::HashMap<chrono::NaiveDate, i64> =
+        std::collections::HashMap::new();
+    for ms in created {
+        let Some(utc) = chrono::DateTime::from_timestamp_millis(ms) else {
+            continue;
+        };
+        let day = utc.with_timezone(tz).date_naive();
+        let first = match bucket {
+            "day" => day,
+            "week" => day - Days::new(day.weekday().num_days_from_monday() as u64),
+            _ => day.with_day(1).ok_or("invalid date")?,
+        };
+        let start = match starts.get(&first) {
+            Some(&start) => start,
+            None => {
+                let start = local_midnight(tz, first)?;
+                starts.insert(first, start);
+                start
+            }
+        };
+        *counts.entry(start).or_insert(0) += 1;
+    }
+    Ok(counts
+        .into_iter()
+        .map(|(bucket_start_ms, count)| HistogramBucket {
+            bucket_start_ms,
+            count,
+        })
+        .collect())
+}
+
+// Notes not edited in the last `older_than_days` days, least recently
+// edited first, for revisiting neglected notes
+#[tauri::command]
+fn get_stale_notes(
+    db: State<Db>,
+    limit: i64,
+    older_than_days: i64,
+) -> Result<Vec<NoteMetadata>, String> {
+    let limit = limit.clamp(1, 500);
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let cutoff = chrono::Utc::now().timestamp_millis() - older_than_days.max(0) * 86_400_000;
+    let mut stmt = conn
+        .prepare(
+            "SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+             n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+             FROM notes n \
+             WHERE n.updated_at < ?1 \
+             ORDER BY n.updated_at ASC, n.id \
+             LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map(rusqlite::params![cutoff, limit], note_metadata_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+// Every folder (in get_folders order, empty ones included) with its notes in
+// the same order get_notes_metadata uses: pinned first, then the folder's sort mode.
+#[tauri::command]
+fn get_notes_grouped(db: State<Db>) -> Result<Vec<FolderGroup>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT id, name FROM folders ORDER BY created_at")
+        .map_err(|e| e.to_string())?;
+    let mut groups = stmt
+        .query_map([], |row| {
+            Ok(FolderGroup {
+                folder_id: row.get(0)?,
+                folder_name: row.get(1)?,
+                notes: Vec::new(),
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let index: std::collections::HashMap<String, usize> = groups
+        .iter()
+        .enumerate()
+        .map(|(i, g)| (g.folder_id.clone(), i))
+        .collect();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+             n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+             FROM notes n JOIN folders f ON f.id = n.folder_id \
+             ORDER BY {}",
+            FOLDER_NOTES_ORDER
+        ))
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map([], note_metadata_from_row)
+        .map_err(|e| e.to_string())?;
+    for note in notes {
+        let note = note.map_err(|e| e.to_string())?;
+        if let Some(&i) = index.get(&note.folder_id) {
+            groups[i].notes.push(note);
+        }
+    }
+    Ok(groups)
+}
+
+// (id, content_hash) for every note, for sync clients to diff against their
+// last snapshot instead of comparing bodies
+#[tauri::command]
+fn get_note_hashes(db: State<Db>) -> Result<Vec<(String, String)>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT id, COALESCE(content_hash, note_hash(title, body)) FROM notes ORDER BY id")
+        .map_err(|e| e.to_string())?;
+    let hashes = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(hashes)
+}
+
+// Recomputes content_hash for notes whose stored hash doesn't match their
+// text, e.g. after another client edited the database. Returns how many
+// were fixed.
+#[tauri::command]
+fn rehash_notes(db: State<Db>) -> Result<usize, String> {
+    let _maintenance = db.maintenance();
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE notes SET content_hash = note_hash(title, body) \
+         WHERE content_hash IS NOT note_hash(title, body)",
+        [],
+    )
+    .map_err(|e| e.to_string())
+}
+
+// All notes, most recently edited first, one page at a time. Pass the
+// returned cursor (the last row's updated_at and id) to get the next page;
+// it's None once the feed is exhausted.
+#[tauri::command]
+fn get_notes_feed(
+    db: State<Db>,
+    cursor: Option<FeedCursor>,
+    limit: i64,
+) -> Result<(Vec<NoteMetadata>, Option<FeedCursor>), String> {
+    let limit = limit.clamp(1, 500);
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    // Without a cursor, start above every possible key
+    let (updated_at, id) = cursor.unwrap_or((i64::MAX, String::new()));
+    let mut stmt = conn
+        .prepare(
+            "SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+             n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+             FROM notes n \
+             WHERE (n.updated_at, n.id) < (?1, ?2) \
+             ORDER BY n.updated_at DESC, n.id DESC \
+             LIMIT ?3",
+        )
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map(
+            rusqlite::params![updated_at, id, limit],
+            note_metadata_from_row,
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let next = if notes.len() as i64 == limit {
+        notes.last().map(|n| (n.updated_at, n.id.clone()))
+    } else {
+        None
+    };
+    Ok((notes, next))
+}
+
+// Every note in a folder's subtree (the folder itself included), most
+// recently edited first, each with its immediate folder's name.
+#[tauri::command]
+fn get_notes_recursive(
+    db: State<Db>,
+    folder_id: String,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<NoteInFolder>, String> {
+    let limit = limit.clamp(1, 500);
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "WITH RECURSIVE subtree(id) AS ( \
+                 SELECT id FROM folders WHERE id = ?1 \
+                 UNION \
+                 SELECT f.id FROM folders f JOIN subtree s ON f.parent_id = s.id \
+             ) \
+             SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+             n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash, fo.name \
+             FROM notes n \
+             JOIN subtree s ON s.id = n.folder_id \
+             JOIN folders fo ON fo.id = n.folder_id \
+             ORDER BY n.updated_at DESC, n.id \
+             LIMIT ?2 OFFSET ?3",
+        )
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map(rusqlite::params![folder_id, limit, offset.max(0)], |row| {
+            Ok(NoteInFolder {
+                note: note_metadata_from_row(row)?,
+                folder_name: row.get(10)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+// Notes from several folders at once, most recently edited first. With
+// `include_subfolders`, each folder's whole subtree is included.
+#[tauri::command]
+fn get_notes_in_folders(
+    db: State<Db>,
+    folder_ids: Vec<String>,
+    limit: i64,
+    offset: i64,
+    include_subfolders: Option<bool>,
+) -> Result<Vec<NoteMetadata>, String> {
+    let limit = limit.clamp(1, 500);
+    if !folder_ids.iter().all(|id| is_safe_id(id)) {
+        return Err("invalid folder id".to_string());
+    }
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut ids: Vec<String> = Vec::new();
+    for folder_id in &folder_ids {
+        let subtree = if include_subfolders.unwrap_or(false) {
+            subtree_folder_ids(&conn, folder_id)?
+        } else {
+            vec![folder_id.clone()]
+        };
+        for id in subtree {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders: Vec<String> = (0..ids.len()).map(|i| format!("?{}", i + 3)).collect();
+    let sql = format!(
+        "SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+         n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+         FROM notes n \
+         WHERE n.folder_id IN ({}) \
+         ORDER BY n.updated_at DESC, n.id \
+         LIMIT ?1 OFFSET ?2",
+        placeholders.join(",")
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let mut params: Vec<rusqlite::types::Value> = vec![limit.into(), offset.max(0).into()];
+    params.extend(ids.into_iter().map(rusqlite::types::Value::from));
+    let notes = stmt
+        .query_map(rusqlite::params_from_iter(params), note_metadata_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+#[tauri::command]
+fn get_notes_index(db: State<Db>) -> Result<Vec<NoteIndex>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT n.id, n.folder_id, n.title, n.updated_at, n.pinned, n.sort_order \
+             FROM notes n JOIN folders f ON f.id = n.folder_id \
+             ORDER BY {}",
+            FOLDER_NOTES_ORDER
+        ))
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map([], |row| {
+            Ok(NoteIndex {
+                id: row.get(0)?,
+                folder_id: row.get(1)?,
+                title: row.get(2)?,
+                updated_at: row.get(3)?,
+                pinned: row.get(4)?,
+                sort_order: row.get(5)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+// Plain-text preview of a note, at most `len` characters plus an ellipsis
+#[tauri::command]
+fn get_note_excerpt(db: State<Db>, id: String, len: i64) -> Result<String, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let body: String = conn
+        .query_row(
+            "SELECT body FROM notes WHERE id = ?1",
+            rusqlite::params![id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or("note not found")?;
+    Ok(excerpt(&body, len.max(0) as usize))
+}
+
+#[tauri::command]
+fn get_note_body(db: State<Db>, id: String) -> Result<String, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let body: String = conn
+        .query_row(
+            "SELECT body FROM notes WHERE id = ?1",
+            rusqlite::params![id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(body)
+}
+
+#[tauri::command]
+fn get_notes_all(db: State<Db>) -> Result<Vec<Note>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT id, folder_id, title, body, created_at, updated_at, pinned, sort_order, starred FROM notes")
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map([], |row| {
+            Ok(Note {
+                id: row.get(0)?,
+                folder_id: row.get(1)?,
+                title: row.get(2)?,
+                body: row.get(3)?,
+                created_at: row.get(4)?,
+                updated_at: row.get(5)?,
+                pinned: row.get(6)?,
+                sort_order: row.get(7)?,
+                starred: row.get(8)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+#[tauri::command]
+fn search_notes(
+    db: State<Db>,
+    query: String,
+    preview_len: Option<i64>,
+) -> Result<Vec<NoteMetadata>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&search_notes_sql(preview_len))
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map(rusqlite::params![query], note_metadata_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+// Same results as search_notes, but each row is sent as soon as it's read,
+// followed by a final `{ done: true, total }` message.
+#[tauri::command]
+fn search_notes_streamed(
+    db: State<Db>,
+    query: String,
+    preview_len: Option<i64>,
+    channel: tauri::ipc::Channel<SearchEvent>,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&search_notes_sql(preview_len))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![query], note_metadata_from_row)
+        .map_err(|e| e.to_string())?;
+    let mut total = 0;
+    for note in rows {
+        let note = note.map_err(|e| e.to_string())?;
+        channel
+            .send(SearchEvent::Note(note))
+            .map_err(|e| e.to_string())?;
+        total += 1;
+    }
+    channel
+        .send(SearchEvent::Done { done: true, total })
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// EXPLAIN QUERY PLAN for search_notes' SQL, one detail line per plan step
+// (indented by nesting), to check that the FTS index is used. Debug builds only.
+#[cfg(debug_assertions)]
+#[tauri::command]
+fn explain_search(db: State<Db>, query: String) -> Result<Vec<String>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!("EXPLAIN QUERY PLAN {}", search_notes_sql(None)))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![query], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(3)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut depths = std::collections::HashMap::new();
+    let mut plan = Vec::with_capacity(rows.len());
+    for (id, parent, detail) in rows {
+        let depth = depths.get(&parent).map_or(0, |d| d + 1);
+        depths.insert(id, depth);
+        plan.push(format!("{}{}", "  ".repeat(depth), detail));
+    }
+    Ok(plan)
+}
+
+// search_notes split into notes whose title matches and the rest. Each
+// section keeps its own rank order.
+#[tauri::command]
+fn search_notes_grouped(
+    db: State<Db>,
+    query: String,
+    preview_len: Option<i64>,
+) -> Result<GroupedSearch, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let title_query = format!("title : ({})", query);
+    // Body matches leave out the title matches in SQL, so those don't use up
+    // the LIMIT. Running this first also rejects queries FTS5 can't parse on
+    // their own, such as "a) OR (b", which would escape the title filter.
+    let mut stmt = conn
+        .prepare(&search_notes_sql_where(
+            preview_len,
+            "notes_fts MATCH ?1 AND notes_fts.rowid NOT IN \
+             (SELECT rowid FROM notes_fts WHERE notes_fts MATCH ?2)",
+        ))
+        .map_err(|e| e.to_string())?;
+    let body_matches = stmt
+        .query_map(
+            rusqlite::params![query, title_query],
+            note_metadata_from_row,
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&search_notes_sql(preview_len))
+        .map_err(|e| e.to_string())?;
+    let title_matches = stmt
+        .query_map(rusqlite::params![title_query], note_metadata_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(GroupedSearch {
+        title_matches,
+        body_matches,
+    })
+}
+
+// FTS5 MATCH query, joined back to notes for full metadata
+fn search_notes_sql(preview_len: Option<i64>) -> String {
+    search_notes_sql_where(preview_len, "notes_fts MATCH ?1")
+}
+
+fn search_notes_sql_where(preview_len: Option<i64>, condition: &str) -> String {
+    format!(
+        "SELECT n.id, n.folder_id, n.title, {}, \
+         n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+         FROM notes_fts \
+         JOIN notes n ON n.rowid = notes_fts.rowid \
+         WHERE {} \
+         ORDER BY rank \
+         LIMIT 80",
+        preview_column(preview_len),
+        condition
+    )
+}
+
+// Ranked FTS match; queries FTS5 rejects (unbalanced quotes, stray operators)
+// fall back to a case-insensitive substring match on title and body. A blank
+// query is an error rather than a match-everything substring.
+fn search_note_ids(conn: &Connection, query: &str, limit: i64) -> Result<Vec<String>, String> {
+    if query.trim().is_empty() {
+        return Err("search query is empty".to_string());
+    }
+    let fts = conn
+        .prepare(
+            "SELECT n.id FROM notes_fts f JOIN notes n ON n.rowid = f.rowid \
+             WHERE notes_fts MATCH ?1 ORDER BY rank LIMIT ?2",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map(rusqlite::params![query, limit], |row| row.get(0))?
+                .collect::<Result<Vec<String>, _>>()
+        });
+    if let Ok(ids) = fts {
+        return Ok(ids);
+    }
+    let pattern = format!("%{}%", escape_like(query.trim()));
+    let mut stmt = conn
+        .prepare(
+            "SELECT id FROM notes \
+             WHERE title LIKE ?1 ESCAPE '\\' OR body LIKE ?1 ESCAPE '\\' \
+             ORDER BY updated_at DESC LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let ids = stmt
+        .query_map(rusqlite::params![pattern, limit], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(ids)
+}
+
+// Notes matching `include` but not `exclude`, as the FTS query
+// `(include) NOT (exclude)`. If FTS5 rejects it, falls back to substring
+// matching: include must appear in the title or body, exclude in neither.
+#[tauri::command]
+fn search_notes_advanced(
+    db: State<Db>,
+    include: String,
+    exclude: Option<String>,
+    limit: i64,
+) -> Result<Vec<NoteMetadata>, String> {
+    let limit = limit.clamp(1, 500);
+    let include = include.trim();
+    let exclude = exclude.as_deref().map(str::trim).filter(|e| !e.is_empty());
+    if include.is_empty() {
+        return Ok(Vec::new());
+    }
+    let fts_query = match exclude {
+        Some(exclude) => format!("({}) NOT ({})", include, exclude),
+        None => include.to_string(),
+    };
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let fts = conn
+        .prepare(
+            "SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+             n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+             FROM notes_fts \
+             JOIN notes n ON n.rowid = notes_fts.rowid \
+             WHERE notes_fts MATCH ?1 \
+             ORDER BY rank \
+             LIMIT ?2",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map(rusqlite::params![fts_query, limit], note_metadata_from_row)?
+                .collect::<Result<Vec<_>, _>>()
+        });
+    if let Ok(notes) = fts {
+        return Ok(notes);
+    }
+    // Quotes are FTS syntax, not text to look for
+    let pattern = |text: &str| format!("%{}%", escape_like(text.replace('"', "").trim()));
+    let include_pattern = pattern(include);
+    let exclude_pattern = exclude.map(pattern);
+    let mut stmt = conn
+        .prepare(
+            "SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+             n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+             FROM notes n \
+             WHERE (n.title LIKE ?1 ESCAPE '\\' OR n.body LIKE ?1 ESCAPE '\\') \
+             AND NOT (?2 IS NOT NULL AND (n.title LIKE ?2 ESCAPE '\\' OR n.body LIKE ?2 ESCAPE '\\')) \
+             ORDER BY n.updated_at DESC \
+             LIMIT ?3",
+        )
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map(
+            rusqlite::params![include_pattern, exclude_pattern, limit],
+            note_metadata_from_row,
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+// Quick-switcher search over titles only. Every word is a prefix match, and
+// shorter titles win so exact matches float to the top.
+#[tauri::command]
+fn search_titles(db: State<Db>, query: String, limit: i64) -> Result<Vec<NoteMetadata>, String> {
+    let limit = limit.clamp(1, 500);
+    let terms: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| format!("\"{}\"*", t))
+        .collect();
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+    let fts_query = format!("title : ({})", terms.join(" "));
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+             n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+             FROM notes_fts f \
+             JOIN notes n ON n.rowid = f.rowid \
+             WHERE notes_fts MATCH ?1 \
+             ORDER BY length(n.title), rank \
+             LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map(rusqlite::params![fts_query, limit], note_metadata_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+// Above this many notes, fuzzy_find_notes narrows candidates with FTS first
+const FUZZY_SCAN_LIMIT: i64 = 5000;
+
+// Fewest edits (insert, delete, substitute, swap adjacent) turning `query`
+// into some substring of `text`
+fn fuzzy_distance(query: &[char], text: &[char]) -> usize {
+    let mut before: Vec<usize> = vec![0; text.len() + 1];
+    let mut prev: Vec<usize> = vec![0; text.len() + 1];
+    for i in 1..=query.len() {
+        let mut row = vec![i; text.len() + 1];
+        for j in 1..=text.len() {
+            let cost = usize::from(query[i - 1] != text[j - 1]);
+            row[j] = (prev[j - 1] + cost).min(prev[j] + 1).min(row[j - 1] + 1);
+            if i > 1 && j > 1 && query[i - 1] == text[j - 2] && query[i - 2] == text[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut prev, row);
+    }
+    prev.into_iter().min().unwrap_or(0)
+}
+
+// Typo-tolerant title search for the quick switcher. A title matches when
+// the query is within one edit per three characters of part of it; fewer
+// edits score higher, then shorter titles. Large libraries are pre-filtered
+// by an FTS prefix match on each query word's first letters.
+#[tauri::command]
+fn fuzzy_find_notes(db: State<Db>, query: String, limit: i64) -> Result<Vec<ScoredNote>, String> {
+    let limit = limit.clamp(1, 500);
+    let query: Vec<char> = query.trim().to_lowercase().chars().collect();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let total: i64 = conn
+        .query_row("SELECT COUNT(*) FROM notes", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let columns = "n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+                   n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash";
+    let candidates = if total > FUZZY_SCAN_LIMIT {
+        let prefixes: Vec<String> = query
+            .split(|c| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(|w| format!("\"{}\"*", w.iter().take(3).collect::<String>()))
+            .collect();
+        if prefixes.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM notes_fts JOIN notes n ON n.rowid = notes_fts.rowid \
+                 WHERE notes_fts MATCH ?1",
+                columns
+            ))
+            .map_err(|e| e.to_string())?;
+        let notes = stmt
+            .query_map(
+                rusqlite::params![format!("title : ({})", prefixes.join(" OR "))],
+                note_metadata_from_row,
+            )
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        notes
+    } else {
+        let mut stmt = conn
+            .prepare(&format!("SELECT {} FROM notes n", columns))
+            .map_err(|e| e.to_string())?;
+        let notes = stmt
+            .query_map([], note_metadata_from_row)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        notes
+    };
+
+    let allowed = query.len() / 3;
+    let mut scored: Vec<ScoredNote> = candidates
+        .into_iter()
+        .filter_map(|note| {
+            let title: Vec<char> = note.title.to_lowercase().chars().collect();
+            let distance = fuzzy_distance(&query, &title);
+            if distance > allowed {
+                return None;
+            }
+            let closeness = 1000 * (query.len() - distance) / query.len();
+            Some(ScoredNote {
+                score: closeness as i64 - title.len().min(999) as i64,
+                metadata: note,
+            })
+        })
+        .collect();
+    scored.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| a.metadata.title.cmp(&b.metadata.title))
+    });
+    scored.truncate(limit as usize);
+    Ok(scored)
+}
+
+const STOPWORDS: &[&str] = &[
+    "about", "after", "also", "and", "are", "because", "been", "before", "but", "can", "could",
+    "did", "does", "for", "from", "had", "has", "have", "her", "here", "his", "how", "into", "its",
+    "just", "more", "most", "not", "now", "only", "other", "our", "out", "over", "she", "should",
+    "some", "such", "than", "that", "the", "their", "them", "then", "there", "these", "they",
+    "this", "those", "through", "too", "under", "very", "was", "were", "what", "when", "where",
+    "which", "while", "who", "will", "with", "would", "you", "your",
+];
+
+// Most frequent non-stopword terms, title words counted double
+fn top_keywords(title: &str, body: &str, count: usize) -> Vec<String> {
+    let mut freq: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    for (text, weight) in [(title, 2), (body, 1)] {
+        for token in text.split(|c: char| !c.is_alphanumeric()) {
+            let token = token.to_lowercase();
+            if token.chars().count() < 3
+                || token.chars().all(|c| c.is_ascii_digit())
+                || STOPWORDS.contains(&token.as_str())
+            {
+                continue;
+            }
+            *freq.entry(token).or_default() += weight;
+        }
+    }
+    let mut terms: Vec<(String, usize)> = freq.into_iter().collect();
+    terms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    terms.into_iter().take(count).map(|(t, _)| t).collect()
+}
+
+// "See also" suggestions: the note's top keywords OR'ed into an FTS query,
+// ranked by bm25, excluding the note itself.
+#[tauri::command]
+fn get_related_notes(db: State<Db>, id: String, limit: i64) -> Result<Vec<NoteMetadata>, String> {
+    let limit = limit.clamp(1, 500);
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let (title, body): (String, String) = conn
+        .query_row(
+            "SELECT title, body FROM notes WHERE id = ?1",
+            rusqlite::params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    let keywords = top_keywords(&title, &body, 8);
+    if keywords.is_empty() {
+        return Ok(Vec::new());
+    }
+    let fts_query = keywords
+        .iter()
+        .map(|k| format!("\"{}\"", k))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    let mut stmt = conn
+        .prepare(
+            "SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+             n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+             FROM notes_fts f \
+             JOIN notes n ON n.rowid = f.rowid \
+             WHERE notes_fts MATCH ?1 AND n.id != ?2 \
+             ORDER BY rank \
+             LIMIT ?3",
+        )
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map(
+            rusqlite::params![fts_query, id, limit],
+            note_metadata_from_row,
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[tauri::command]
+fn refine_search(
+    db: State<Db>,
+    query: String,
+    within_ids: Vec<String>,
+    preview_len: Option<i64>,
+) -> Result<Vec<NoteMetadata>, String> {
+    if within_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !within_ids.iter().all(|id| is_safe_id(id)) {
+        return Err("invalid note id".to_string());
+    }
+    let placeholders: Vec<String> = (0..within_ids.len())
+        .map(|i| format!("?{}", i + 2))
+        .collect();
+    // Same FTS match as search_notes, constrained to the previous result set
+    let sql = format!(
+        "SELECT n.id, n.folder_id, n.title, {}, \
+         n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+         FROM notes_fts f \
+         JOIN notes n ON n.rowid = f.rowid \
+         WHERE notes_fts MATCH ?1 AND n.id IN ({}) \
+         ORDER BY rank",
+        preview_column(preview_len),
+        placeholders.join(",")
+    );
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let params = std::iter::once(&query).chain(within_ids.iter());
+    let notes = stmt
+        .query_map(rusqlite::params_from_iter(params), note_metadata_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+// A note created with no body gets the default note template. Returns the
+// body that was stored so addNote() can show the expanded template.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn create_note(
+    db: State<Db>,
+    id: String,
+    folder_id: String,
+    title: String,
+    body: String,
+    created_at: i64,
+    updated_at: i64,
+    pinned: i32,
+    sort_order: i32,
+) -> Result<String, String> {
+    let conn = db.write()?;
+    let body = body_or_template(&conn, body)?;
+    conn.execute(
+        "INSERT INTO notes (id, folder_id, title, body, created_at, updated_at, pinned, sort_order) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        rusqlite::params![id, folder_id, title, body, created_at, updated_at, pinned, sort_order],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(body)
+}
+
+// Captures a Markdown blob (clipboard, drag-drop) as a new note at the top of
+// the folder's unpinned group, same as addNote() in the frontend. A capture
+// with no body gets the default note template.
+#[tauri::command]
+fn create_note_from_markdown(
+    db: State<Db>,
+    folder_id: String,
+    markdown: String,
+) -> Result<String, String> {
+    let (title, body) = title_from_markdown(&markdown);
+    let id = generate_id();
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut conn = db.write()?;
+    let body = body_or_template(&conn, body)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute(
+        "UPDATE notes SET sort_order = sort_order + 1 WHERE folder_id = ?1 AND pinned = 0",
+        rusqlite::params![folder_id],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.execute(
+        "INSERT INTO notes (id, folder_id, title, body, created_at, updated_at, pinned, sort_order) VALUES (?1, ?2, ?3, ?4, ?5, ?5, 0, 0)",
+        rusqlite::params![id, folder_id, title, body, now],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+const DEFAULT_TEMPLATE_SETTING: &str = "default_note_template";
+
+// The body itself, or the expanded default template when it's blank
+fn body_or_template(conn: &Connection, body: String) -> Result<String, String> {
+    if !body.trim().is_empty() {
+        return Ok(body);
+    }
+    Ok(match get_setting(conn, DEFAULT_TEMPLATE_SETTING)? {
+        Some(template) => expand_template(&template, chrono::Local::now()),
+        None => body,
+    })
+}
+
+// Fills in "{{date}}" (YYYY-MM-DD) and "{{time}}" (HH:MM), both local
+fn expand_template(template: &str, now: chrono::DateTime<chrono::Local>) -> String {
+    template
+        .replace("{{date}}", &now.format("%Y-%m-%d").to_string())
+        .replace("{{time}}", &now.format("%H:%M").to_string())
+}
+
+#[tauri::command]
+fn get_default_note_template(db: State<Db>) -> Result<Option<String>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    get_setting(&conn, DEFAULT_TEMPLATE_SETTING)
+}
+
+// An empty template clears the setting
+#[tauri::command]
+fn set_default_note_template(db: State<Db>, markdown: String) -> Result<(), String> {
+    let conn = db.write()?;
+    if markdown.trim().is_empty() {
+        conn.execute(
+            "DELETE FROM app_settings WHERE key = ?1",
+            rusqlite::params![DEFAULT_TEMPLATE_SETTING],
+        )
+        .map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+    set_setting(&conn, DEFAULT_TEMPLATE_SETTING, &markdown)
+}
+
+// Today's journal note in the folder, titled with the local date
+// (YYYY-MM-DD). Created at the top of the unpinned group on first call, with
+// `template` as its body (expanded as by expand_template).
+#[tauri::command]
+fn get_or_create_daily_note(
+    db: State<Db>,
+    folder_id: String,
+    template: Option<String>,
+) -> Result<String, String> {
+    let mut conn = db.write()?;
+    daily_note(
+        &mut conn,
+        &folder_id,
+        chrono::Local::now(),
+        template.as_deref(),
+    )
+}
+
+fn daily_note(
+    conn: &mut Connection,
+    folder_id: &str,
+    now: chrono::DateTime<chrono::Local>,
+    template: Option<&str>,
+) -> Result<String, String> {
+    let date = now.format("%Y-%m-%d").to_string();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let existing: Option<String> = tx
+        .query_row(
+            "SELECT id FROM notes WHERE folder_id = ?1 AND title = ?2 ORDER BY created_at, id LIMIT 1",
+            rusqlite::params![folder_id, date],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(id) = existing {
+        return Ok(id);
+    }
+    let exists: bool = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM folders WHERE id = ?1)",
+            rusqlite::params![folder_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err("folder not found".to_string());
+    }
+    let body = expand_template(template.unwrap_or(""), now);
+    let id = generate_id();
+    let now = now.timestamp_millis();
+    tx.execute(
+        "UPDATE notes SET sort_order = sort_order + 1 WHERE folder_id = ?1 AND pinned = 0",
+        rusqlite::params![folder_id],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.execute(
+        "INSERT INTO notes (id, folder_id, title, body, created_at, updated_at, pinned, sort_order) VALUES (?1, ?2, ?3, ?4, ?5, ?5, 0, 0)",
+        rusqlite::params![id, folder_id, date, body, now],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+// A leading heading becomes the title and is removed from the body; otherwise
+// the first non-empty line (up to 80 chars) is used and the body kept whole.
+fn title_from_markdown(markdown: &str) -> (String, String) {
+    let Some(first) = markdown.lines().find(|l| !l.trim().is_empty()) else {
+        return ("Untitled".to_string(), String::new());
+    };
+    if let Some((_, text)) = markdown::atx_heading(first) {
+        let rest = markdown.lines().skip_while(|l| l.trim().is_empty()).skip(1);
+        let body = rest.collect::<Vec<_>>().join("\n");
+        let title = if text.is_empty() { "Untitled" } else { text };
+        return (title.to_string(), body.trim_start_matches('\n').to_string());
+    }
+    let title: String = first.trim().chars().take(80).collect();
+    (title, markdown.to_string())
+}
+
+#[tauri::command]
+fn update_note(
+    db: State<Db>,
+    id: String,
+    title: String,
+    body: String,
+    updated_at: i64,
+) -> Result<(), String> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    save_edit_version(&tx, &id, &title, &body)?;
+    tx.execute(
+        "UPDATE notes SET title = ?1, body = ?2, updated_at = ?3 WHERE id = ?4",
+        rusqlite::params![title, body, updated_at, id],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Corrects a note's timestamps (e.g. after an import) without touching its
+// content. Only the provided values change; the result must keep
+// created_at <= updated_at.
+#[tauri::command]
+fn set_note_timestamps(
+    db: State<Db>,
+    id: String,
+    created_at: Option<i64>,
+    updated_at: Option<i64>,
+) -> Result<(), String> {
+    if created_at.is_some_and(|t| t < 0) || updated_at.is_some_and(|t| t < 0) {
+        return Err("timestamps must be non-negative".to_string());
+    }
+    let conn = db.write()?;
+    let (stored_created, stored_updated): (i64, i64) = conn
+        .query_row(
+            "SELECT created_at, updated_at FROM notes WHERE id = ?1",
+            rusqlite::params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    let created_at = created_at.unwrap_or(stored_created);
+    let updated_at = updated_at.unwrap_or(stored_updated);
+    if created_at > updated_at {
+        return Err("created_at must not be after updated_at".to_string());
+    }
+    conn.execute(
+        "UPDATE notes SET created_at = ?1, updated_at = ?2 WHERE id = ?3",
+        rusqlite::params![created_at, updated_at, id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Title-only update that leaves the body untouched. When `updated_at` is given it
+// acts as an optimistic-concurrency check: the rename is refused if the note was
+// modified after that time. Returns the note's new updated_at.
+#[tauri::command]
+fn rename_note(
+    db: State<Db>,
+    id: String,
+    title: String,
+    updated_at: Option<i64>,
+) -> Result<i64, String> {
+    let conn = db.write()?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let new_updated_at: Option<i64> = conn
+        .query_row(
+            "UPDATE notes SET title = ?1, updated_at = MAX(updated_at + 1, ?2) \
+             WHERE id = ?3 AND (?4 IS NULL OR updated_at <= ?4) \
+             RETURNING updated_at",
+            rusqlite::params![title, now, id, updated_at],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match new_updated_at {
+        Some(ts) => Ok(ts),
+        None => {
+            let exists: bool = conn
+                .query_row(
+                    "SELECT EXISTS(SELECT 1 FROM notes WHERE id = ?1)",
+                    rusqlite::params![id],
+                    |row| row.get(0),
+                )
+                .map_err(|e| e.to_string())?;
+            if exists {
+                Err("note was modified since it was loaded".to_string())
+            } else {
+                Err("note not found".to_string())
+            }
+        }
+    }
+}
+
+#[tauri::command]
+fn delete_note(db: State<Db>, id: String) -> Result<(), String> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let attachments = note_attachment_ids(&tx, &id)?;
+    tx.execute("DELETE FROM notes WHERE id = ?1", rusqlite::params![id])
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    remove_attachment_files(&attachments);
+    Ok(())
+}
+
+// Markdown problems in a note (see markdown::lint), in line order
+#[tauri::command]
+fn lint_note_markdown(db: State<Db>, id: String) -> Result<Vec<LintIssue>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let body: String = conn
+        .query_row(
+            "SELECT body FROM notes WHERE id = ?1",
+            rusqlite::params![id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("note not found: {}", id))?;
+    Ok(markdown::lint(&body)
+        .into_iter()
+        .map(|issue| LintIssue {
+            line: issue.line,
+            severity: issue.severity.to_string(),
+            message: issue.message,
+        })
+        .collect())
+}
+
+// Notes with a blank title and a whitespace-only body
+fn empty_note_ids(conn: &Connection) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, title, body FROM notes ORDER BY id")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(rows
+        .into_iter()
+        .filter(|(_, title, body)| title.trim().is_empty() && body.trim().is_empty())
+        .map(|(id, _, _)| id)
+        .collect())
+}
+
+// Empty notes, and folders with no notes and no subfolders. With `recursive`,
+// a folder whose subfolders hold no notes at any depth counts as empty too.
+#[tauri::command]
+fn find_empty_items(db: State<Db>, recursive: Option<bool>) -> Result<EmptyReport, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let empty_notes = empty_note_ids(&conn)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT f.id, f.parent_id, EXISTS(SELECT 1 FROM notes n WHERE n.folder_id = f.id) \
+             FROM folders f ORDER BY f.created_at, f.id",
+        )
+        .map_err(|e| e.to_string())?;
+    let folders: Vec<(String, Option<String>, bool)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+
+    // A folder is non-empty if it has notes or a child; when recursive, only a
+    // child that is itself non-empty counts. Propagate upward from each
+    // folder that has notes.
+    let parents: std::collections::HashMap<&str, Option<&str>> = folders
+        .iter()
+        .map(|(id, parent, _)| (id.as_str(), parent.as_deref()))
+        .collect();
+    let mut non_empty: std::collections::HashSet<&str> = std::collections::HashSet::new();
+    for (id, parent, has_notes) in &folders {
+        if recursive.unwrap_or(false) {
+            if !has_notes {
+                continue;
+            }
+            let mut current = Some(id.as_str());
+            while let Some(folder) = current {
+                if !non_empty.insert(folder) {
+                    break;
+                }
+                current = parents.get(folder).copied().flatten();
+            }
+        } else {
+            if *has_notes {
+                non_empty.insert(id);
+            }
+            if let Some(parent) = parent {
+                non_empty.insert(parent);
+            }
+        }
+    }
+    let empty_folders = folders
+        .iter()
+        .filter(|(id, _, _)| !non_empty.contains(id.as_str()))
+        .map(|(id, _, _)| id.clone())
+        .collect();
+    Ok(EmptyReport {
+        empty_notes,
+        empty_folders,
+    })
+}
+
+// Deletes every note find_empty_items reports. Returns how many were removed.
+#[tauri::command]
+fn delete_empty_notes(db: State<Db>) -> Result<usize, String> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let ids = empty_note_ids(&tx)?;
+    let mut attachments = Vec::new();
+    for id in &ids {
+        attachments.extend(note_attachment_ids(&tx, id)?);
+        tx.execute("DELETE FROM notes WHERE id = ?1", rusqlite::params![id])
+            .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    remove_attachment_files(&attachments);
+    Ok(ids.len())
+}
+
+const TOC_SOURCE_META: &str = "toc_source";
+
+// Writes a "TOC: <title>" note listing the source note's headings as
+// `[Heading](#anchor)` links, nested by level. The TOC remembers its source
+// in note_meta, so running this again rewrites that note (wherever it has
+// been moved) instead of creating another. Returns the TOC note's id.
+#[tauri::command]
+fn generate_toc_note(
+    db: State<Db>,
+    source_id: String,
+    folder_id: String,
+) -> Result<String, String> {
+    let mut conn = db.write()?;
+    let (title, body): (String, String) = conn
+        .query_row(
+            "SELECT title, body FROM notes WHERE id = ?1",
+            rusqlite::params![source_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or("note not found")?;
+    let headings = markdown::headings(&body);
+    let top = headings.iter().map(|h| h.level).min().unwrap_or(1);
+    let mut slugger = markdown::Slugger::default();
+    // Brackets and pipes would end the wiki link early
+    let link_title = title.trim().replace(['[', ']', '|'], "");
+    let mut toc = format!("Contents of [[{}|{}]]\n\n", source_id, link_title);
+    for heading in &headings {
+        let text: String = markdown::spans(heading.text)
+            .into_iter()
+            .map(|span| span.text)
+            .collect();
+        let anchor = slugger.anchor(heading.text);
+        let label = text.replace('[', "\\[").replace(']', "\\]");
+        let indent = "  ".repeat((heading.level - top) as usize);
+        toc.push_str(&format!("{}- [{}](#{})\n", indent, label, anchor));
+    }
+    let toc_title = format!("TOC: {}", title.trim());
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let existing: Option<String> = tx
+        .query_row(
+            "SELECT m.note_id FROM note_meta m JOIN notes n ON n.id = m.note_id \
+             WHERE m.key = ?1 AND m.value = ?2 ORDER BY n.created_at LIMIT 1",
+            rusqlite::params![TOC_SOURCE_META, source_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let id = match existing {
+        Some(id) => {
+            tx.execute(
+                "UPDATE notes SET title = ?1, body = ?2, updated_at = ?3 WHERE id = ?4",
+                rusqlite::params![toc_title, toc, now, id],
+            )
+            .map_err(|e| e.to_string())?;
+            id
+        }
+        None => {
+            let exists: bool = tx
+                .query_row(
+                    "SELECT EXISTS(SELECT 1 FROM folders WHERE id = ?1)",
+                    rusqlite::params![folder_id],
+                    |row| row.get(0),
+                )
+                .map_err(|e| e.to_string())?;
+            if !exists {
+                return Err("folder not found".to_string());
+            }
+            let id = generate_id();
+            tx.execute(
+                "UPDATE notes SET sort_order = sort_order + 1 WHERE folder_id = ?1 AND pinned = 0",
+                rusqlite::params![folder_id],
+            )
+            .map_err(|e| e.to_string())?;
+            tx.execute(
+                "INSERT INTO notes (id, folder_id, title, body, created_at, updated_at, pinned, sort_order) VALUES (?1, ?2, ?3, ?4, ?5, ?5, 0, 0)",
+                rusqlite::params![id, folder_id, toc_title, toc, now],
+            )
+            .map_err(|e| e.to_string())?;
+            tx.execute(
+                "INSERT INTO note_meta (note_id, key, value) VALUES (?1, ?2, ?3)",
+                rusqlite::params![id, TOC_SOURCE_META, source_id],
+            )
+            .map_err(|e| e.to_string())?;
+            id
+        }
+    };
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+// Splits a note into one note per heading of the given level. New notes land in
+// the same folder and pin group directly after the original, in section order.
+#[tauri::command]
+fn split_note_by_heading(
+    db: State<Db>,
+    id: String,
+    level: u8,
+    keep_original: Option<bool>,
+) -> Result<Vec<String>, String> {
+    if !(1..=6).contains(&level) {
+        return Err("heading level must be between 1 and 6".to_string());
+    }
+    let mut conn = db.write()?;
+    let (folder_id, title, body, pinned, sort_order): (String, String, String, i32, i32) = conn
+        .query_row(
+            "SELECT folder_id, title, body, pinned, sort_order FROM notes WHERE id = ?1",
+            rusqlite::params![id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let sections = markdown::split_sections(&body, level);
+    if !sections.iter().any(|(heading, _)| heading.is_some()) {
+        return Err(format!("note has no level {} headings", level));
+    }
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    // Make room after the original within its pin group
+    tx.execute(
+        "UPDATE notes SET sort_order = sort_order + ?1 \
+         WHERE folder_id = ?2 AND pinned = ?3 AND sort_order > ?4",
+        rusqlite::params![sections.len() as i32, folder_id, pinned, sort_order],
+    )
+    .map_err(|e| e.to_string())?;
+    let mut new_ids = Vec::with_capacity(sections.len());
+    let mut section_bodies = Vec::with_capacity(sections.len());
+    for (i, (heading, content)) in sections.into_iter().enumerate() {
+        let new_id = generate_id();
+        // Text before the first heading keeps the original title
+        let new_title = heading.unwrap_or_else(|| title.clone());
+        tx.execute(
+            "INSERT INTO notes (id, folder_id, title, body, created_at, updated_at, pinned, sort_order) VALUES (?1, ?2, ?3, ?4, ?5, ?5, ?6, ?7)",
+            rusqlite::params![new_id, folder_id, new_title, content, now, pinned, sort_order + 1 + i as i32],
+        )
+        .map_err(|e| e.to_string())?;
+        new_ids.push(new_id);
+        section_bodies.push(content);
+    }
+    if !keep_original.unwrap_or(false) {
+        // Attachments move to the section that references them (the first
+        // one if none does) instead of going with the original
+        for attachment in note_attachment_ids(&tx, &id)? {
+            let reference = format!("{}{}", ATTACHMENT_SCHEME, attachment);
+            let owner = new_ids
+                .iter()
+                .zip(&section_bodies)
+                .find(|(_, body)| body.contains(&reference))
+                .map_or(&new_ids[0], |(new_id, _)| new_id);
+            tx.execute(
+                "UPDATE attachments SET note_id = ?1 WHERE id = ?2",
+                rusqlite::params![owner, attachment],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.execute("DELETE FROM notes WHERE id = ?1", rusqlite::params![id])
+            .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(new_ids)
+}
+
+// Strips trailing whitespace and excess blank lines outside code fences.
+// With `dry_run`, only reports which notes would change.
+#[tauri::command]
+fn clean_note_bodies(db: State<Db>, dry_run: bool) -> Result<CleanupReport, String> {
+    let _maintenance = db.maintenance();
+    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut report = CleanupReport {
+        scanned: 0,
+        changed: 0,
+        note_ids: Vec::new(),
+    };
+    let mut cleaned = Vec::new();
+    {
+        let mut stmt = tx
+            .prepare("SELECT id, body FROM notes ORDER BY id")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|e| e.to_string())?;
+        for row in rows {
+            let (id, body) = row.map_err(|e| e.to_string())?;
+            report.scanned += 1;
+            let clean = markdown::clean_whitespace(&body);
+            if clean != body {
+                report.note_ids.push(id.clone());
+                cleaned.push((id, clean));
+            }
+        }
+    }
+    report.changed = cleaned.len();
+    if dry_run {
+        return Ok(report);
+    }
+    let now = chrono::Utc::now().timestamp_millis();
+    for (id, body) in &cleaned {
+        tx.execute(
+            "UPDATE notes SET body = ?1, updated_at = ?2 WHERE id = ?3",
+            rusqlite::params![body, now, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+// ===== Wiki-link commands =====
+
+// Resolves [[link]] targets to note ids, by exact id or case-insensitive title.
+// When titles collide the first note loaded wins.
+struct LinkResolver {
+    ids: std::collections::HashSet<String>,
+    titles: std::collections::HashMap<String, String>,
+}
+
+impl LinkResolver {
+    fn new<'a>(notes: impl Iterator<Item = (&'a str, &'a str)>) -> Self {
+        let mut ids = std::collections::HashSet::new();
+        let mut titles = std::collections::HashMap::new();
+        for (id, title) in notes {
+            ids.insert(id.to_string());
+            if !title.trim().is_empty() {
+                titles
+                    .entry(title.trim().to_lowercase())
+                    .or_insert_with(|| id.to_string());
+            }
+        }
+        LinkResolver { ids, titles }
+    }
+
+    fn resolve(&self, target: &str) -> Option<&str> {
+        if let Some(id) = self.ids.get(target) {
+            return Some(id);
+        }
+        self.titles
+            .get(&target.to_lowercase())
+            .map(|id| id.as_str())
+    }
+}
+
+// (id, title, body) for every note, in a stable order
+fn load_note_texts(conn: &Connection) -> Result<Vec<(String, String, String)>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, title, body FROM notes ORDER BY created_at, id")
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+// Titles shared by two or more notes, compared the way wiki-links resolve
+// them (trimmed, case-insensitive). Ids are in resolution order, so the
+// first is the note a `[[title]]` link reaches.
+#[tauri::command]
+fn find_title_collisions(db: State<Db>) -> Result<Vec<TitleCollision>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT id, title FROM notes WHERE TRIM(title) != '' ORDER BY created_at, id")
+        .map_err(|e| e.to_string())?;
+    let notes: Vec<(String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut groups: Vec<TitleCollision> = Vec::new();
+    let mut index: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    for (id, title) in notes {
+        let key = title.trim().to_lowercase();
+        match index.get(&key) {
+            Some(&i) => groups[i].note_ids.push(id),
+            None => {
+                index.insert(key, groups.len());
+                groups.push(TitleCollision {
+                    title: title.trim().to_string(),
+                    note_ids: vec![id],
+                });
+            }
+        }
+    }
+    groups.retain(|g| g.note_ids.len() > 1);
+    Ok(groups)
+}
+
+// Ids of notes whose wiki-links resolve to `target_id`, in `notes` order
+fn backlink_ids(notes: &[(String, String, String)], target_id: &str) -> Vec<String> {
+    let resolver = LinkResolver::new(
+        notes
+            .iter()
+            .map(|(id, title, _)| (id.as_str(), title.as_str())),
+    );
+    notes
+        .iter()
+        .filter(|(id, _, body)| {
+            id != target_id
+                && markdown::wiki_links(body)
+                    .iter()
+                    .any(|target| resolver.resolve(target) == Some(target_id))
+        })
+        .map(|(id, _, _)| id.clone())
+        .collect()
+}
+
+// Outgoing wiki-link edges by index into `notes`, deduplicated, in link order
+fn link_graph(notes: &[(String, String, String)]) -> Vec<Vec<usize>> {
+    let resolver = LinkResolver::new(
+        notes
+            .iter()
+            .map(|(id, title, _)| (id.as_str(), title.as_str())),
+    );
+    let index: std::collections::HashMap<&str, usize> = notes
+        .iter()
+        .enumerate()
+        .map(|(i, (id, _, _))| (id.as_str(), i))
+        .collect();
+    notes
+        .iter()
+        .map(|(_, _, body)| {
+            let mut edges = Vec::new();
+            for target in markdown::wiki_links(body) {
+                if let Some(&j) = resolver.resolve(&target).and_then(|id| index.get(id)) {
+                    if !edges.contains(&j) {
+                        edges.push(j);
+                    }
+                }
+            }
+            edges
+        })
+        .collect()
+}
+
+// Every note as a node and every resolved wiki-link as a source -> target
+// edge, for the graph view. Unresolved links are left out.
+#[tauri::command]
+fn get_note_graph(db: State<Db>) -> Result<NoteGraph, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let notes = load_note_texts(&conn)?;
+    let mut stmt = conn
+        .prepare("SELECT id, folder_id FROM notes")
+        .map_err(|e| e.to_string())?;
+    let folders: std::collections::HashMap<String, String> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut edges = Vec::new();
+    for (i, targets) in link_graph(&notes).into_iter().enumerate() {
+        for j in targets {
+            edges.push(GraphEdge {
+                source: notes[i].0.clone(),
+                target: notes[j].0.clone(),
+            });
+        }
+    }
+    let nodes = notes
+        .into_iter()
+        .map(|(id, title, _)| GraphNode {
+            folder_id: folders.get(&id).cloned().unwrap_or_default(),
+            id,
+            title,
+        })
+        .collect();
+    Ok(NoteGraph { nodes, edges })
+}
+
+// Cycles in the wiki-link graph, one per back edge found by a depth-first
+// walk, each as the note ids along the cycle. A note linking to itself is a
+// cycle of one.
+#[tauri::command]
+fn find_link_cycles(db: State<Db>) -> Result<Vec<Vec<String>>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let notes = load_note_texts(&conn)?;
+    let graph = link_graph(&notes);
+    // 0 = unvisited, 1 = on the current path, 2 = finished
+    let mut state = vec![0u8; notes.len()];
+    let mut cycles = Vec::new();
+    for start in 0..notes.len() {
+        if state[start] != 0 {
+            continue;
+        }
+        // Iterative so long link chains can't overflow the stack
+        let mut path: Vec<(usize, usize)> = vec![(start, 0)];
+        state[start] = 1;
+        while let Some((node, next)) = path.last_mut() {
+            let node = *node;
+            let Some(&to) = graph[node].get(*next) else {
+                state[node] = 2;
+                path.pop();
+                continue;
+            };
+            *next += 1;
+            match state[to] {
+                0 => {
+                    state[to] = 1;
+                    path.push((to, 0));
+                }
+                1 => {
+                    let from = path.iter().position(|&(n, _)| n == to).unwrap_or(0);
+                    cycles.push(
+                        path[from..]
+                            .iter()
+                            .map(|&(n, _)| notes[n].0.clone())
+                            .collect(),
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(cycles)
+}
+
+#[tauri::command]
+fn find_broken_wiki_links(db: State<Db>) -> Result<Vec<BrokenLink>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let notes = load_note_texts(&conn)?;
+    let resolver = LinkResolver::new(
+        notes
+            .iter()
+            .map(|(id, title, _)| (id.as_str(), title.as_str())),
+    );
+    let mut broken = Vec::new();
+    for (id, _, body) in &notes {
+        for target in markdown::wiki_links(body) {
+            if resolver.resolve(&target).is_none() {
+                broken.push(BrokenLink {
+                    note_id: id.clone(),
+                    link_text: target,
+                });
+            }
+        }
+    }
+    Ok(broken)
+}
+
+// ===== Version history commands =====
+
+// Oldest versions beyond this are dropped whenever a new one is saved
+const MAX_VERSIONS_PER_NOTE: i64 = 50;
+
+// Saves the note's current title and body as a version and returns its id
+fn save_note_version(conn: &Connection, note_id: &str, reason: &str) -> Result<i64, String> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let inserted = conn
+        .execute(
+            "INSERT INTO note_versions (note_id, title, body, created_at, reason) \
+             SELECT id, title, body, ?2, ?3 FROM notes WHERE id = ?1",
+            rusqlite::params![note_id, now, reason],
+        )
+        .map_err(|e| e.to_string())?;
+    if inserted == 0 {
+        return Err("note not found".to_string());
+    }
+    let version_id = conn.last_insert_rowid();
+    trim_note_versions(conn, note_id)?;
+    Ok(version_id)
+}
+
+fn trim_note_versions(conn: &Connection, note_id: &str) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM note_versions WHERE note_id = ?1 AND id NOT IN ( \
+             SELECT id FROM note_versions WHERE note_id = ?1 \
+             ORDER BY created_at DESC, id DESC LIMIT ?2 \
+         )",
+        rusqlite::params![note_id, MAX_VERSIONS_PER_NOTE],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Edits within this long of the newest version don't record another one
+const EDIT_VERSION_INTERVAL_MS: i64 = 5 * 60 * 1000;
+
+// Called before an update lands: keeps the text being replaced as an 'edit'
+// version, unless nothing changed or a version was saved very recently.
+fn save_edit_version(
+    conn: &Connection,
+    note_id: &str,
+    title: &str,
+    body: &str,
+) -> Result<(), String> {
+    let current: Option<(String, String)> = conn
+        .query_row(
+            "SELECT title, body FROM notes WHERE id = ?1",
+            rusqlite::params![note_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match current {
+        Some((t, b)) if t != title || b != body => {}
+        _ => return Ok(()),
+    }
+    let latest: Option<i64> = conn
+        .query_row(
+            "SELECT MAX(created_at) FROM note_versions WHERE note_id = ?1",
+            rusqlite::params![note_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().timestamp_millis();
+    if latest.is_some_and(|t| now - t < EDIT_VERSION_INTERVAL_MS) {
+        return Ok(());
+    }
+    save_note_version(conn, note_id, "edit")?;
+    Ok(())
+}
+
+// Manual or timer-driven snapshot of a note, saved as a 'manual' version
+// even when the text hasn't changed (the frontend timer skips unedited
+// notes). Returns the version id.
+#[tauri::command]
+fn snapshot_note(db: State<Db>, id: String) -> Result<i64, String> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let version_id = save_note_version(&tx, &id, "manual")?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(version_id)
+}
+
+#[derive(Serialize)]
+struct NoteVersion {
+    id: i64,
+    title: String,
+    body: String,
+    created_at: i64,
+    reason: String,
+}
+
+// Saved versions of a note, newest first
+#[tauri::command]
+fn get_note_versions(db: State<Db>, note_id: String) -> Result<Vec<NoteVersion>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, title, body, created_at, reason FROM note_versions \
+             WHERE note_id = ?1 ORDER BY created_at DESC, id DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let versions = stmt
+        .query_map(rusqlite::params![note_id], |row| {
+            Ok(NoteVersion {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                body: row.get(2)?,
+                created_at: row.get(3)?,
+                reason: row.get(4)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(versions)
+}
+
+// Puts a saved version's title and body back into its note. The text it
+// replaces is kept as a 'restore' version so the restore can be undone.
+// Returns the note id.
+#[tauri::command]
+fn restore_note_version(db: State<Db>, version_id: i64) -> Result<String, String> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let (note_id, title, body): (String, String, String) = tx
+        .query_row(
+            "SELECT note_id, title, body FROM note_versions WHERE id = ?1",
+            rusqlite::params![version_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "version not found".to_string())?;
+    save_note_version(&tx, &note_id, "restore")?;
+    let now = chrono::Utc::now().timestamp_millis();
+    tx.execute(
+        "UPDATE notes SET title = ?1, body = ?2, updated_at = ?3 WHERE id = ?4",
+        rusqlite::params![title, body, now, note_id],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(note_id)
+}
+
+// Writes the note's saved versions oldest-first, followed by its current
+// state (reason "current"), as a JSON array. Returns the entry count.
+#[tauri::command]
+fn export_note_history(db: State<Db>, id: String, path: String) -> Result<usize, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let current = conn
+        .query_row(
+            "SELECT title, body, updated_at FROM notes WHERE id = ?1",
+            rusqlite::params![id],
+            |row| {
+                Ok(serde_json::json!({
+                    "title": row.get::<_, String>(0)?,
+                    "body": row.get::<_, String>(1)?,
+                    "created_at": row.get::<_, i64>(2)?,
+                    "reason": "current"
+                }))
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("note not found: {}", id))?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT title, body, created_at, reason FROM note_versions \
+             WHERE note_id = ?1 ORDER BY created_at, id",
+        )
+        .map_err(|e| e.to_string())?;
+    let mut entries: Vec<serde_json::Value> = stmt
+        .query_map(rusqlite::params![id], |row| {
+            Ok(serde_json::json!({
+                "title": row.get::<_, String>(0)?,
+                "body": row.get::<_, String>(1)?,
+                "created_at": row.get::<_, i64>(2)?,
+                "reason": row.get::<_, String>(3)?
+            }))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    entries.push(current);
+    let json_str = serde_json::to_string_pretty(&entries).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json_str).map_err(|e| e.to_string())?;
+    Ok(entries.len())
+}
+
+// Adds the entries of an export_note_history file to a note's versions,
+// keeping their timestamps. The exported current state becomes an 'edit'
+// version. Returns how many of them were kept: the per-note cap drops the
+// oldest versions, which may include imported ones.
+#[tauri::command]
+fn import_note_history(db: State<Db>, id: String, path: String) -> Result<usize, String> {
+    let data = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let entries: Vec<serde_json::Value> = serde_json::from_str(&data).map_err(|e| e.to_string())?;
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let exists: bool = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM notes WHERE id = ?1)",
+            rusqlite::params![id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err(format!("note not found: {}", id));
+    }
+    let mut first_id = None;
+    for entry in &entries {
+        let (Some(title), Some(body), Some(created_at)) = (
+            entry["title"].as_str(),
+            entry["body"].as_str(),
+            entry["created_at"].as_i64(),
+        ) else {
+            return Err("invalid history entry".to_string());
+        };
+        let reason = entry["reason"]
+            .as_str()
+            .filter(|r| ["edit", "manual", "restore"].contains(r))
+            .unwrap_or("edit");
+        tx.execute(
+            "INSERT INTO note_versions (note_id, title, body, created_at, reason) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![id, title, body, created_at, reason],
+        )
+        .map_err(|e| e.to_string())?;
+        first_id.get_or_insert(tx.last_insert_rowid());
+    }
+    trim_note_versions(&tx, &id)?;
+    // Imported rows older than everything kept are trimmed straight away
+    let kept: i64 = tx
+        .query_row(
+            "SELECT COUNT(*) FROM note_versions WHERE note_id = ?1 AND id >= ?2",
+            rusqlite::params![id, first_id.unwrap_or(i64::MAX)],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(kept as usize)
+}
+
+// Thins version history across all notes: keeps each note's newest
+// `keep_per_note` versions, dropping any of those created before
+// `older_than_ms` too, but never a note's latest version. Returns rows removed.
+#[tauri::command]
+fn prune_version_history(
+    db: State<Db>,
+    keep_per_note: i64,
+    older_than_ms: Option<i64>,
+) -> Result<i64, String> {
+    let conn = db.write()?;
+    let removed = conn
+        .execute(
+            "DELETE FROM note_versions WHERE id IN ( \
+                 SELECT id FROM ( \
+                     SELECT id, created_at, \
+                         ROW_NUMBER() OVER (PARTITION BY note_id ORDER BY created_at DESC, id DESC) AS rank \
+                     FROM note_versions \
+                 ) WHERE rank > 1 AND (rank > ?1 OR created_at < ?2) \
+             )",
+            rusqlite::params![keep_per_note.max(1), older_than_ms],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(removed as i64)
+}
+
+// ===== Pin & reorder commands =====
+
+#[tauri::command]
+fn toggle_note_pinned(db: State<Db>, id: String, pinned: i32) -> Result<(), String> {
+    let conn = db.write()?;
+    conn.execute(
+        "UPDATE notes SET pinned = ?1 WHERE id = ?2",
+        rusqlite::params![pinned, id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Pins or unpins a batch of notes and renumbers each affected folder's pin
+// groups, with newly changed notes at the end of their new group. Returns how
+// many notes changed state.
+#[tauri::command]
+fn set_notes_pinned(db: State<Db>, ids: Vec<String>, pinned: i32) -> Result<usize, String> {
+    let pinned = i32::from(pinned != 0);
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut changed = std::collections::HashSet::new();
+    let mut folders: Vec<String> = Vec::new();
+    for id in &ids {
+        let (folder_id, current): (String, i32) = tx
+            .query_row(
+                "SELECT folder_id, pinned FROM notes WHERE id = ?1",
+                rusqlite::params![id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("note not found: {}", id))?;
+        if current != pinned {
+            changed.insert(id.clone());
+            if !folders.contains(&folder_id) {
+                folders.push(folder_id);
+            }
+        }
+    }
+    let mut stmt = tx
+        .prepare("UPDATE notes SET pinned = ?1 WHERE id = ?2")
+        .map_err(|e| e.to_string())?;
+    for id in &changed {
+        stmt.execute(rusqlite::params![pinned, id])
+            .map_err(|e| e.to_string())?;
+    }
+    drop(stmt);
+    for folder_id in &folders {
+        for group_pinned in [1, 0] {
+            let (kept, moved): (Vec<String>, Vec<String>) =
+                pin_group_ids(&tx, folder_id, group_pinned)?
+                    .into_iter()
+                    .partition(|id| group_pinned != pinned || !changed.contains(id));
+            write_group_order(&tx, &[kept, moved].concat())?;
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(changed.len())
+}
+
+#[tauri::command]
+fn reorder_notes(db: State<Db>, updates: Vec<(String, i32)>) -> Result<(), String> {
+    if updates.is_empty() {
+        return Ok(());
+    }
+    // Validate since ids are interpolated into the SQL below
+    if !updates.iter().all(|(id, _)| is_safe_id(id)) {
+        return Err("invalid note id".to_string());
+    }
+    let ids: Vec<String> = updates.iter().map(|(id, _)| format!("'{}'", id)).collect();
+    let conn = db.write()?;
+    // Manual order only applies to folders in manual sort mode
+    let auto_sorted: Option<String> = conn
+        .query_row(
+            &format!(
+                "SELECT f.name FROM notes n JOIN folders f ON f.id = n.folder_id \
+                 WHERE n.id IN ({}) AND f.sort_mode != 'manual' LIMIT 1",
+                ids.join(",")
+            ),
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(name) = auto_sorted {
+        return Err(format!(
+            "folder \"{}\" is sorted automatically; switch it to manual order to reorder notes",
+            name
+        ));
+    }
+    let case_clauses: Vec<String> = updates
+        .iter()
+        .map(|(id, order)| format!("WHEN '{}' THEN {}", id, order))
+        .collect();
+    let sql = format!(
+        "UPDATE notes SET sort_order = CASE id {} END WHERE id IN ({})",
+        case_clauses.join(" "),
+        ids.join(",")
+    );
+    conn.execute(&sql, []).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Notes in one folder's pin group, in display order
+fn pin_group_ids(conn: &Connection, folder_id: &str, pinned: i32) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id FROM notes WHERE folder_id = ?1 AND pinned = ?2 \
+             ORDER BY sort_order, rowid",
+        )
+        .map_err(|e| e.to_string())?;
+    let ids = stmt
+        .query_map(rusqlite::params![folder_id, pinned], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(ids)
+}
+
+// Rewrites sort_order as 0..n following the given order
+fn write_group_order(conn: &Connection, ids: &[String]) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare("UPDATE notes SET sort_order = ?1 WHERE id = ?2")
+        .map_err(|e| e.to_string())?;
+    for (i, id) in ids.iter().enumerate() {
+        stmt.execute(rusqlite::params![i as i32, id])
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn ensure_manual_order(conn: &Connection, folder_id: &str) -> Result<(), String> {
+    let (name, mode): (String, String) = conn
+        .query_row(
+            "SELECT name, sort_mode FROM folders WHERE id = ?1",
+            rusqlite::params![folder_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    if mode != "manual" {
+        return Err(format!(
+            "folder \"{}\" is sorted automatically; switch it to manual order to reorder notes",
+            name
+        ));
+    }
+    Ok(())
+}
+
+// Swaps a note with its neighbor in the pin group. Returns false at the boundary.
+fn step_note(conn: &mut Connection, id: &str, up: bool) -> Result<bool, String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let (folder_id, pinned): (String, i32) = tx
+        .query_row(
+            "SELECT folder_id, pinned FROM notes WHERE id = ?1",
+            rusqlite::params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    ensure_manual_order(&tx, &folder_id)?;
+    let mut group = pin_group_ids(&tx, &folder_id, pinned)?;
+    let idx = group.iter().position(|n| n == id).ok_or("note not found")?;
+    let neighbor = if up {
+        idx.checked_sub(1)
+    } else {
+        Some(idx + 1).filter(|&i| i < group.len())
+    };
+    let Some(neighbor) = neighbor else {
+        return Ok(false);
+    };
+    let order_of = |note_id: &str| -> Result<i32, String> {
+        tx.query_row(
+            "SELECT sort_order FROM notes WHERE id = ?1",
+            rusqlite::params![note_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())
+    };
+    let (own, other) = (order_of(id)?, order_of(&group[neighbor])?);
+    group.swap(idx, neighbor);
+    if own == other {
+        // Tied values can't be swapped; renumber the group instead
+        write_group_order(&tx, &group)?;
+    } else {
+        let mut stmt = tx
+            .prepare("UPDATE notes SET sort_order = ?1 WHERE id = ?2")
+            .map_err(|e| e.to_string())?;
+        stmt.execute(rusqlite::params![other, id])
+            .map_err(|e| e.to_string())?;
+        stmt.execute(rusqlite::params![own, group[idx]])
+            .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+#[tauri::command]
+fn move_note_up(db: State<Db>, id: String) -> Result<bool, String> {
+    let mut conn = db.write()?;
+    step_note(&mut conn, &id, true)
+}
+
+#[tauri::command]
+fn move_note_down(db: State<Db>, id: String) -> Result<bool, String> {
+    let mut conn = db.write()?;
+    step_note(&mut conn, &id, false)
+}
+
+// Moves a note to either end of its pin group and renumbers the group 0..n,
+// so repeated moves never push sort_order values out of range.
+fn send_note(conn: &mut Connection, id: &str, to_top: bool) -> Result<(), String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let (folder_id, pinned): (String, i32) = tx
+        .query_row(
+            "SELECT folder_id, pinned FROM notes WHERE id = ?1",
+            rusqlite::params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    ensure_manual_order(&tx, &folder_id)?;
+    let mut group = pin_group_ids(&tx, &folder_id, pinned)?;
+    let idx = group.iter().position(|n| n == id).ok_or("note not found")?;
+    let note = group.remove(idx);
+    if to_top {
+        group.insert(0, note);
+    } else {
+        group.push(note);
+    }
+    write_group_order(&tx, &group)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Moves notes into a folder, appending each to the end of its pin group,
+// and returns a single-use token that undo_move can revert with.
+#[tauri::command]
+fn move_notes_with_undo(
+    db: State<Db>,
+    undo: State<MoveUndo>,
+    ids: Vec<String>,
+    folder_id: String,
+) -> Result<UndoToken, String> {
+    if !ids.iter().all(|id| is_safe_id(id)) {
+        return Err("invalid note id".to_string());
+    }
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let exists: bool = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM folders WHERE id = ?1)",
+            rusqlite::params![folder_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err("folder not found".to_string());
+    }
+    let mut previous = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    // A repeated id would record its already-moved position on the second pass
+    for id in ids.iter().filter(|id| seen.insert(id.as_str())) {
+        let Some((old_folder, sort_order, pinned)) = tx
+            .query_row(
+                "SELECT folder_id, sort_order, pinned FROM notes WHERE id = ?1",
+                rusqlite::params![id],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, i32>(1)?,
+                        row.get::<_, i32>(2)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+        else {
+            continue;
+        };
+        tx.execute(
+            "UPDATE notes SET folder_id = ?1, sort_order = ( \
+                 SELECT COALESCE(MAX(sort_order) + 1, 0) FROM notes \
+                 WHERE folder_id = ?1 AND pinned = ?2 AND id != ?3 \
+             ) WHERE id = ?3",
+            rusqlite::params![folder_id, pinned, id],
+        )
+        .map_err(|e| e.to_string())?;
+        previous.push((id.clone(), old_folder, sort_order));
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    let token = generate_id();
+    let moved = previous.len();
+    let mut undos = undo.0.lock().map_err(|e| e.to_string())?;
+    undos.push_back((token.clone(), previous));
+    if undos.len() > MAX_MOVE_UNDOS {
+        undos.pop_front();
+    }
+    Ok(UndoToken { token, moved })
+}
+
+// Restores the positions recorded by move_notes_with_undo. Notes deleted in
+// the meantime are skipped. Returns how many notes were restored.
+#[tauri::command]
+fn undo_move(db: State<Db>, undo: State<MoveUndo>, token: String) -> Result<usize, String> {
+    let mut conn = db.write()?;
+    let previous = {
+        let mut undos = undo.0.lock().map_err(|e| e.to_string())?;
+        let index = undos
+            .iter()
+            .position(|(t, _)| *t == token)
+            .ok_or("undo token expired or unknown")?;
+        undos
+            .remove(index)
+            .map(|(_, positions)| positions)
+            .unwrap_or_default()
+    };
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut restored = 0;
+    for (id, folder_id, sort_order) in &previous {
+        restored += tx
+            .execute(
+                "UPDATE notes SET folder_id = ?1, sort_order = ?2 WHERE id = ?3 \
+                 AND EXISTS(SELECT 1 FROM folders WHERE id = ?1)",
+                rusqlite::params![folder_id, sort_order, id],
+            )
+            .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(restored)
+}
+
+// Files the top `limit` search matches under a folder, appended to the end
+// of their pin groups in rank order. Notes already in the folder stay put.
+// The limit is required so a broad query can't sweep up the whole library.
+#[tauri::command]
+fn move_search_results(
+    db: State<Db>,
+    query: String,
+    folder_id: String,
+    limit: i64,
+) -> Result<MoveReport, String> {
+    if limit <= 0 {
+        return Err("limit must be positive".to_string());
+    }
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let exists: bool = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM folders WHERE id = ?1)",
+            rusqlite::params![folder_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err("folder not found".to_string());
+    }
+    let mut ids = Vec::new();
+    for id in search_note_ids(&tx, &query, limit)? {
+        let changed = tx
+            .execute(
+                "UPDATE notes SET folder_id = ?1, sort_order = ( \
+                     SELECT COALESCE(MAX(m.sort_order) + 1, 0) FROM notes m \
+                     WHERE m.folder_id = ?1 AND m.pinned = notes.pinned \
+                 ) WHERE id = ?2 AND folder_id != ?1",
+                rusqlite::params![folder_id, id],
+            )
+            .map_err(|e| e.to_string())?;
+        if changed > 0 {
+            ids.push(id);
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(MoveReport {
+        moved: ids.len(),
+        ids,
+    })
+}
+
+#[tauri::command]
+fn move_note_to_top(db: State<Db>, id: String) -> Result<(), String> {
+    let mut conn = db.write()?;
+    send_note(&mut conn, &id, true)
+}
+
+#[tauri::command]
+fn move_note_to_bottom(db: State<Db>, id: String) -> Result<(), String> {
+    let mut conn = db.write()?;
+    send_note(&mut conn, &id, false)
+}
+
+// Drop onto a note: moves `id` into the target's folder and pin group, right
+// after the target. Both the group it left and the one it joined are
+// renumbered 0..n.
+#[tauri::command]
+fn move_note_after(
+    db: State<Db>,
+    id: String,
+    target_id: String,
+    updated_at: i64,
+) -> Result<(), String> {
+    if id == target_id {
+        return Err("cannot move a note after itself".to_string());
+    }
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let position = |note_id: &str| -> Result<(String, i32), String> {
+        tx.query_row(
+            "SELECT folder_id, pinned FROM notes WHERE id = ?1",
+            rusqlite::params![note_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("note not found: {}", note_id))
+    };
+    let (old_folder, old_pinned) = position(&id)?;
+    let (folder_id, pinned) = position(&target_id)?;
+    ensure_manual_order(&tx, &folder_id)?;
+
+    if (old_folder.as_str(), old_pinned) != (folder_id.as_str(), pinned) {
+        let mut old_group = pin_group_ids(&tx, &old_folder, old_pinned)?;
+        old_group.retain(|n| *n != id);
+        write_group_order(&tx, &old_group)?;
+    }
+    tx.execute(
+        "UPDATE notes SET folder_id = ?1, pinned = ?2, updated_at = ?3 WHERE id = ?4",
+        rusqlite::params![folder_id, pinned, updated_at, id],
+    )
+    .map_err(|e| e.to_string())?;
+    let mut group = pin_group_ids(&tx, &folder_id, pinned)?;
+    group.retain(|n| *n != id);
+    let idx = group
+        .iter()
+        .position(|n| *n == target_id)
+        .ok_or("note not found")?;
+    group.insert(idx + 1, id);
+    write_group_order(&tx, &group)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_note_starred(db: State<Db>, id: String, starred: bool) -> Result<(), String> {
+    let conn = db.write()?;
+    conn.execute(
+        "UPDATE notes SET starred = ?1 WHERE id = ?2",
+        rusqlite::params![starred as i32, id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Starred notes across all folders, regardless of pin state
+#[tauri::command]
+fn get_starred_notes(db: State<Db>) -> Result<Vec<NoteMetadata>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+             n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+             FROM notes n \
+             WHERE n.starred = 1 \
+             ORDER BY n.updated_at DESC, n.id",
+        )
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map([], note_metadata_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+// ===== Tag commands =====
+
+fn tag_from_row(row: &rusqlite::Row) -> rusqlite::Result<Tag> {
+    Ok(Tag {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        color: row.get(2)?,
+        created_at: row.get(3)?,
+    })
+}
+
+// Returns the id of the tag with this name (case-insensitive), creating it if needed
+fn ensure_tag(conn: &Connection, name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("tag name cannot be empty".to_string());
+    }
+    let existing: Option<String> = conn
+        .query_row(
+            "SELECT id FROM tags WHERE name = ?1",
+            rusqlite::params![name],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(id) = existing {
+        return Ok(id);
+    }
+    let id = generate_id();
+    conn.execute(
+        "INSERT INTO tags (id, name, created_at) VALUES (?1, ?2, ?3)",
+        rusqlite::params![id, name, chrono::Utc::now().timestamp_millis()],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+fn note_tags(conn: &Connection, note_id: &str) -> Result<Vec<Tag>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.id, t.name, t.color, t.created_at FROM tags t \
+             JOIN note_tags nt ON nt.tag_id = t.id \
+             WHERE nt.note_id = ?1 ORDER BY t.name",
+        )
+        .map_err(|e| e.to_string())?;
+    let tags = stmt
+        .query_map(rusqlite::params![note_id], tag_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(tags)
+}
+
+#[tauri::command]
+fn get_note_tags(db: State<Db>, note_id: String) -> Result<Vec<Tag>, String> {
+    if !is_safe_id(&note_id) {
+        return Err("invalid note id".to_string());
+    }
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    note_tags(&conn, &note_id)
+}
+
+// Tags ordered by when they were last put on a note, newest first. Tags
+// that aren't on any note are left out.
+#[tauri::command]
+fn get_recent_tags(db: State<Db>, limit: i64) -> Result<Vec<Tag>, String> {
+    let limit = limit.clamp(1, 500);
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.id, t.name, t.color, t.created_at FROM tags t \
+             JOIN note_tags nt ON nt.tag_id = t.id \
+             GROUP BY t.id \
+             ORDER BY MAX(nt.applied_at) DESC, MAX(nt.rowid) DESC \
+             LIMIT ?1",
+        )
+        .map_err(|e| e.to_string())?;
+    let tags = stmt
+        .query_map(rusqlite::params![limit], tag_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(tags)
+}
+
+// Tags a note by tag name, creating the tag on first use. Returns the note's tags.
+#[tauri::command]
+fn add_note_tag(db: State<Db>, note_id: String, tag: String) -> Result<Vec<Tag>, String> {
+    if !is_safe_id(&note_id) {
+        return Err("invalid note id".to_string());
+    }
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let tag_id = ensure_tag(&tx, &tag)?;
+    tx.execute(
+        "INSERT OR IGNORE INTO note_tags (note_id, tag_id) VALUES (?1, ?2)",
+        rusqlite::params![note_id, tag_id],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    note_tags(&conn, &note_id)
+}
+
+#[tauri::command]
+fn remove_note_tag(db: State<Db>, note_id: String, tag: String) -> Result<Vec<Tag>, String> {
+    if !is_safe_id(&note_id) {
+        return Err("invalid note id".to_string());
+    }
+    let conn = db.write()?;
+    conn.execute(
+        "DELETE FROM note_tags WHERE note_id = ?1 \
+         AND tag_id = (SELECT id FROM tags WHERE name = ?2)",
+        rusqlite::params![note_id, tag.trim()],
+    )
+    .map_err(|e| e.to_string())?;
+    note_tags(&conn, &note_id)
+}
+
+// Replaces a note's whole tag set (multi-select tag editor semantics)
+#[tauri::command]
+fn set_note_tags(db: State<Db>, note_id: String, tag_ids: Vec<String>) -> Result<Vec<Tag>, String> {
+    if !is_safe_id(&note_id) {
+        return Err("invalid note id".to_string());
+    }
+    if !tag_ids.iter().all(|id| is_safe_id(id)) {
+        return Err("invalid tag id".to_string());
+    }
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for tag_id in &tag_ids {
+        let exists: bool = tx
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM tags WHERE id = ?1)",
+                rusqlite::params![tag_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if !exists {
+            return Err(format!("tag not found: {}", tag_id));
+        }
+    }
+    tx.execute(
+        "DELETE FROM note_tags WHERE note_id = ?1",
+        rusqlite::params![note_id],
+    )
+    .map_err(|e| e.to_string())?;
+    for tag_id in &tag_ids {
+        tx.execute(
+            "INSERT OR IGNORE INTO note_tags (note_id, tag_id) VALUES (?1, ?2)",
+            rusqlite::params![note_id, tag_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    note_tags(&conn, &note_id)
+}
+
+// Applies exact old-name -> new-name tag renames in order, in one transaction.
+// When the new name is already taken, the old tag's notes move onto that tag
+// and the old tag is deleted.
+#[tauri::command]
+fn bulk_rename_tags(
+    db: State<Db>,
+    replacements: Vec<(String, String)>,
+) -> Result<Vec<TagRename>, String> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut report = Vec::with_capacity(replacements.len());
+    for (from, to) in replacements {
+        let (from, to) = (from.trim().to_string(), to.trim().to_string());
+        if to.is_empty() {
+            return Err("tag name cannot be empty".to_string());
+        }
+        let find = |name: &str| -> Result<Option<String>, String> {
+            tx.query_row(
+                "SELECT id FROM tags WHERE name = ?1",
+                rusqlite::params![name],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())
+        };
+        let mut entry = TagRename {
+            from: from.clone(),
+            to: to.clone(),
+            renamed: false,
+            merged: false,
+            not_found: false,
+        };
+        match (find(&from)?, find(&to)?) {
+            (None, _) => entry.not_found = true,
+            (Some(source), Some(target)) if source != target => {
+                tx.execute(
+                    "INSERT OR IGNORE INTO note_tags (note_id, tag_id) \
+                     SELECT note_id, ?1 FROM note_tags WHERE tag_id = ?2",
+                    rusqlite::params![target, source],
+                )
+                .map_err(|e| e.to_string())?;
+                tx.execute(
+                    "UPDATE tags SET color = COALESCE(color, (SELECT color FROM tags WHERE id = ?2)) \
+                     WHERE id = ?1",
+                    rusqlite::params![target, source],
+                )
+                .map_err(|e| e.to_string())?;
+                tx.execute("DELETE FROM tags WHERE id = ?1", rusqlite::params![source])
+                    .map_err(|e| e.to_string())?;
+                entry.merged = true;
+            }
+            // Includes case-only renames, where the target lookup finds the source
+            (Some(source), _) => {
+                tx.execute(
+                    "UPDATE tags SET name = ?1 WHERE id = ?2",
+                    rusqlite::params![to, source],
+                )
+                .map_err(|e| e.to_string())?;
+                entry.renamed = true;
+            }
+        }
+        report.push(entry);
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+// Whitespace-separated words; Markdown syntax counts like any other text
+fn word_count(text: &str) -> i64 {
+    text.split_whitespace().count() as i64
+}
+
+// Notes and words per tag, most words first. Tags without notes report zeros.
+#[tauri::command]
+fn get_tag_report(db: State<Db>) -> Result<Vec<TagReport>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.id, t.name, n.body FROM tags t \
+             LEFT JOIN note_tags nt ON nt.tag_id = t.id \
+             LEFT JOIN notes n ON n.id = nt.note_id \
+             ORDER BY t.id",
+        )
+        .map_err(|e| e.to_string())?;
+    let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+    let mut report: Vec<TagReport> = Vec::new();
+    let mut last_id = String::new();
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let id: String = row.get(0).map_err(|e| e.to_string())?;
+        if report.is_empty() || id != last_id {
+            report.push(TagReport {
+                tag: row.get(1).map_err(|e| e.to_string())?,
+                note_count: 0,
+                total_words: 0,
+            });
+            last_id = id;
+        }
+        let body: Option<String> = row.get(2).map_err(|e| e.to_string())?;
+        if let (Some(body), Some(entry)) = (body, report.last_mut()) {
+            entry.note_count += 1;
+            entry.total_words += word_count(&body);
+        }
+    }
+    report.sort_by(|a, b| {
+        b.total_words
+            .cmp(&a.total_words)
+            .then_with(|| a.tag.to_lowercase().cmp(&b.tag.to_lowercase()))
+    });
+    Ok(report)
+}
+
+// Notes not yet categorized, most recently edited first
+#[tauri::command]
+fn get_untagged_notes(db: State<Db>, limit: i64, offset: i64) -> Result<Vec<NoteMetadata>, String> {
+    let limit = limit.clamp(1, 500);
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+             n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+             FROM notes n \
+             WHERE NOT EXISTS (SELECT 1 FROM note_tags nt WHERE nt.note_id = n.id) \
+             ORDER BY n.updated_at DESC, n.id \
+             LIMIT ?1 OFFSET ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map(
+            rusqlite::params![limit, offset.max(0)],
+            note_metadata_from_row,
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+// Turns inline `#hashtags` (outside code) into real tags on their notes and
+// removes them from the bodies. With `dry_run`, only reports what would change.
+#[tauri::command]
+fn extract_hashtags_to_tags(db: State<Db>, dry_run: bool) -> Result<ExtractReport, String> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut stmt = tx
+        .prepare("SELECT id, body FROM notes ORDER BY created_at, id")
+        .map_err(|e| e.to_string())?;
+    let notes: Vec<(String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+    let mut report = ExtractReport {
+        tags_created: 0,
+        notes_affected: 0,
+    };
+    let mut new_tags: Vec<String> = Vec::new();
+    let now = chrono::Utc::now().timestamp_millis();
+    for (id, body) in notes {
+        let (tags, stripped) = markdown::extract_hashtags(&body);
+        if tags.is_empty() {
+            continue;
+        }
+        report.notes_affected += 1;
+        for tag in &tags {
+            let exists: bool = tx
+                .query_row(
+                    "SELECT EXISTS(SELECT 1 FROM tags WHERE name = ?1)",
+                    rusqlite::params![tag],
+                    |row| row.get(0),
+                )
+                .map_err(|e| e.to_string())?;
+            if !exists && !new_tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                new_tags.push(tag.clone());
+            }
+            if !dry_run {
+                let tag_id = ensure_tag(&tx, tag)?;
+                tx.execute(
+                    "INSERT OR IGNORE INTO note_tags (note_id, tag_id) VALUES (?1, ?2)",
+                    rusqlite::params![id, tag_id],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+        }
+        if !dry_run {
+            tx.execute(
+                "UPDATE notes SET body = ?1, updated_at = ?2 WHERE id = ?3",
+                rusqlite::params![stripped, now, id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+    report.tags_created = new_tags.len();
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+// Tags each note in the folder (or its whole subtree with `recursive`) with
+// the names of every folder on its path, e.g. Projects/Alpha gives
+// "Projects" and "Alpha". With `target_folder_id` the notes are then moved
+// there, appended to their pin group, flattening the hierarchy.
+#[tauri::command]
+fn folderize_to_tags(
+    db: State<Db>,
+    folder_id: String,
+    recursive: bool,
+    target_folder_id: Option<String>,
+) -> Result<ExtractReport, String> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut stmt = tx
+        .prepare("SELECT id, name, parent_id FROM folders")
+        .map_err(|e| e.to_string())?;
+    let folders: std::collections::HashMap<String, (String, Option<String>)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+    if !folders.contains_key(&folder_id) {
+        return Err("folder not found".to_string());
+    }
+    if let Some(target) = &target_folder_id {
+        if !folders.contains_key(target) {
+            return Err("target folder not found".to_string());
+        }
+    }
+    let folder_ids = if recursive {
+        subtree_folder_ids(&tx, &folder_id)?
+    } else {
+        vec![folder_id]
+    };
+
+    let mut report = ExtractReport {
+        tags_created: 0,
+        notes_affected: 0,
+    };
+    let mut tag_ids: std::collections::HashMap<String, String> = std::collections::HashMap::new();
+    for id in &folder_ids {
+        let mut path = Vec::new();
+        let mut current = Some(id.as_str());
+        while let Some((name, parent)) = current.and_then(|c| folders.get(c)) {
+            // Guard against a corrupt parent cycle
+            if path.len() > folders.len() {
+                break;
+            }
+            path.push(name.trim());
+            current = parent.as_deref();
+        }
+        let mut path_tags = Vec::new();
+        for name in path.into_iter().filter(|n| !n.is_empty()) {
+            if let Some(tag_id) = tag_ids.get(name) {
+                path_tags.push(tag_id.clone());
+                continue;
+            }
+            let exists: bool = tx
+                .query_row(
+                    "SELECT EXISTS(SELECT 1 FROM tags WHERE name = ?1)",
+                    rusqlite::params![name],
+                    |row| row.get(0),
+                )
+                .map_err(|e| e.to_string())?;
+            if !exists {
+                report.tags_created += 1;
+            }
+            let tag_id = ensure_tag(&tx, name)?;
+            tag_ids.insert(name.to_string(), tag_id.clone());
+            path_tags.push(tag_id);
+        }
+
+        let mut stmt = tx
+            .prepare("SELECT id, pinned FROM notes WHERE folder_id = ?1 ORDER BY pinned DESC, sort_order, id")
+            .map_err(|e| e.to_string())?;
+        let notes: Vec<(String, i32)> = stmt
+            .query_map(rusqlite::params![id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        drop(stmt);
+        for (note_id, pinned) in notes {
+            let mut changed = false;
+            for tag_id in &path_tags {
+                changed |= tx
+                    .execute(
+                        "INSERT OR IGNORE INTO note_tags (note_id, tag_id) VALUES (?1, ?2)",
+                        rusqlite::params![note_id, tag_id],
+                    )
+                    .map_err(|e| e.to_string())?
+                    > 0;
+            }
+            if let Some(target) = target_folder_id.as_ref().filter(|t| *t != id) {
+                tx.execute(
+                    "UPDATE notes SET folder_id = ?1, sort_order = ( \
+                         SELECT COALESCE(MAX(sort_order) + 1, 0) FROM notes \
+                         WHERE folder_id = ?1 AND pinned = ?2 AND id != ?3 \
+                     ) WHERE id = ?3",
+                    rusqlite::params![target, pinned, note_id],
+                )
+                .map_err(|e| e.to_string())?;
+                changed = true;
+            }
+            if changed {
+                report.notes_affected += 1;
+            }
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+// ===== Note metadata commands =====
+
+// Sets `key` on a note, replacing any previous value
+#[tauri::command]
+fn set_note_meta(db: State<Db>, note_id: String, key: String, value: String) -> Result<(), String> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("metadata key cannot be empty".to_string());
+    }
+    let conn = db.write()?;
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM notes WHERE id = ?1)",
+            rusqlite::params![note_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err("note not found".to_string());
+    }
+    conn.execute(
+        "INSERT INTO note_meta (note_id, key, value) VALUES (?1, ?2, ?3) \
+         ON CONFLICT(note_id, key) DO UPDATE SET value = excluded.value",
+        rusqlite::params![note_id, key, value],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_note_meta(
+    db: State<Db>,
+    note_id: String,
+) -> Result<std::collections::HashMap<String, String>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT key, value FROM note_meta WHERE note_id = ?1")
+        .map_err(|e| e.to_string())?;
+    let meta = stmt
+        .query_map(rusqlite::params![note_id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(meta)
+}
+
+// Returns whether the key was set
+#[tauri::command]
+fn delete_note_meta(db: State<Db>, note_id: String, key: String) -> Result<bool, String> {
+    let conn = db.write()?;
+    let removed = conn
+        .execute(
+            "DELETE FROM note_meta WHERE note_id = ?1 AND key = ?2",
+            rusqlite::params![note_id, key.trim()],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(removed > 0)
+}
+
+// Notes whose `key` is exactly `value`, most recently edited first
+#[tauri::command]
+fn find_notes_by_meta(
+    db: State<Db>,
+    key: String,
+    value: String,
+) -> Result<Vec<NoteMetadata>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+             n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+             FROM note_meta m JOIN notes n ON n.id = m.note_id \
+             WHERE m.key = ?1 AND m.value = ?2 \
+             ORDER BY n.updated_at DESC, n.id",
+        )
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map(rusqlite::params![key.trim(), value], note_metadata_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+// ===== Attachment commands =====
+
+const ATTACHMENT_SCHEME: &str = "anote://attachment/";
+
+fn attachments_dir() -> Result<std::path::PathBuf, String> {
+    let home = dirs::home_dir().ok_or("failed to get home directory")?;
+    Ok(home.join(".anote").join("attachments"))
+}
+
+// Copies images a note references by local path (relative ones resolved
+// against `base_dir`) into managed storage and points the body at the
+// copies. Files that can't be read are reported in `missing`.
+#[tauri::command]
+fn ingest_note_attachments(
+    db: State<Db>,
+    note_id: String,
+    base_dir: String,
+) -> Result<IngestReport, String> {
+    let mut conn = db.write()?;
+    let store = attachments_dir()?;
+    ingest_attachments(&mut conn, &note_id, std::path::Path::new(&base_dir), &store)
+}
+
+fn ingest_attachments(
+    conn: &mut Connection,
+    note_id: &str,
+    base_dir: &std::path::Path,
+    store: &std::path::Path,
+) -> Result<IngestReport, String> {
+    let body: String = conn
+        .query_row(
+            "SELECT body FROM notes WHERE id = ?1",
+            rusqlite::params![note_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or("note not found")?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut report = IngestReport {
+        attachments: Vec::new(),
+        missing: Vec::new(),
+    };
+    // A path referenced twice is copied once
+    let mut copied: std::collections::HashMap<String, String> = std::collections::HashMap::new();
+    // (stored file name, contents), written once the rows are in place
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    let rewritten = markdown::map_image_destinations(&body, &mut |dest| {
+        if dest.contains("://") || dest.starts_with("data:") {
+            return None;
+        }
+        if let Some(id) = copied.get(dest) {
+            return Some(format!("{}{}", ATTACHMENT_SCHEME, id));
+        }
+        let path = base_dir.join(dest);
+        let Ok(bytes) = std::fs::read(&path) else {
+            if !report.missing.iter().any(|m| m == dest) {
+                report.missing.push(dest.to_string());
+            }
+            return None;
+        };
+        let id = generate_id();
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| id.clone());
+        let stored = match path.extension() {
+            Some(ext) => format!("{}.{}", id, ext.to_string_lossy()),
+            None => id.clone(),
+        };
+        copied.insert(dest.to_string(), id.clone());
+        report.attachments.push(Attachment {
+            id: id.clone(),
+            note_id: note_id.to_string(),
+            file_name,
+            size: bytes.len() as i64,
+            created_at: now,
+        });
+        files.push((stored, bytes));
+        Some(format!("{}{}", ATTACHMENT_SCHEME, id))
+    });
+    if report.attachments.is_empty() {
+        return Ok(report);
+    }
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for a in &report.attachments {
+        tx.execute(
+            "INSERT INTO attachments (id, note_id, file_name, size, created_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![a.id, a.note_id, a.file_name, a.size, a.created_at],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.execute(
+        "UPDATE notes SET body = ?1, updated_at = ?2 WHERE id = ?3",
+        rusqlite::params![rewritten, now, note_id],
+    )
+    .map_err(|e| e.to_string())?;
+    commit_with_attachment_files(tx, store, &files)?;
+    Ok(report)
+}
+
+// Writes the stored files for attachment rows inserted in `tx`, then
+// commits. Files go in last so a failed insert leaves none behind, and any
+// already written are removed if a later write or the commit fails.
+fn commit_with_attachment_files(
+    tx: rusqlite::Transaction,
+    store: &std::path::Path,
+    files: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    let mut written = Vec::with_capacity(files.len());
+    let result = std::fs::create_dir_all(store)
+        .and_then(|_| {
+            for (name, bytes) in files {
+                std::fs::write(store.join(name), bytes)?;
+                written.push(store.join(name));
+            }
+            Ok(())
+        })
+        .map_err(|e| e.to_string())
+        .and_then(|_| tx.commit().map_err(|e| e.to_string()));
+    if result.is_err() {
+        for path in &written {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+    result
+}
+
+// Ids of the attachments owned by a note
+fn note_attachment_ids(conn: &Connection, note_id: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id FROM attachments WHERE note_id = ?1")
+        .map_err(|e| e.to_string())?;
+    let ids = stmt
+        .query_map(rusqlite::params![note_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(ids)
+}
+
+// Removes the stored files of attachments whose rows are gone. Called after
+// the deleting transaction commits; a file that can't be removed is only
+// logged, since the database no longer refers to it.
+fn remove_attachment_files(ids: &[String]) {
+    if ids.is_empty() {
+        return;
+    }
+    match attachments_dir() {
+        Ok(store) => remove_attachment_files_in(&store, ids),
+        Err(e) => log::warn!("failed to remove attachment files: {}", e),
+    }
+}
+
+fn remove_attachment_files_in(store: &std::path::Path, ids: &[String]) {
+    let files = attachment_files(store);
+    for path in ids.iter().filter_map(|id| files.get(id)) {
+        if let Err(e) = std::fs::remove_file(path) {
+            log::warn!("failed to remove attachment {}: {}", path.display(), e);
+        }
+    }
+}
+
+// Stored attachment files by attachment id. Empty when the store doesn't
+// exist yet.
+fn attachment_files(
+    store: &std::path::Path,
+) -> std::collections::HashMap<String, std::path::PathBuf> {
+    let mut files = std::collections::HashMap::new();
+    let Ok(entries) = std::fs::read_dir(store) else {
+        return files;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // Stored as `<id>` or `<id>.<ext>`
+        let id = name.split_once('.').map_or(name.as_str(), |(id, _)| id);
+        files.insert(id.to_string(), entry.path());
+    }
+    files
+}
+
+// Replaces each anote://attachment/<id> reference in `text` with what `f`
+// returns for the id. References `f` returns None for are left as they are.
+fn map_attachment_refs(text: &str, f: &mut dyn FnMut(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(ATTACHMENT_SCHEME) {
+        let after = &rest[start + ATTACHMENT_SCHEME.len()..];
+        let len = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .unwrap_or(after.len());
+        let id = &after[..len];
+        out.push_str(&rest[..start]);
+        match f(id).filter(|_| !id.is_empty()) {
+            Some(replacement) => out.push_str(&replacement),
+            None => out.push_str(&rest[start..start + ATTACHMENT_SCHEME.len() + len]),
+        }
+        rest = &after[len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+// Attachment references that lead nowhere: "no row" when the attachments
+// table doesn't know the id, "file missing" when its stored file is gone.
+// Each reference is reported once per note, in note id order.
+#[tauri::command]
+fn find_broken_attachments(db: State<Db>) -> Result<Vec<BrokenAttachment>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let store = attachments_dir()?;
+    broken_attachments(&conn, &store)
+}
+
+fn broken_attachments(
+    conn: &Connection,
+    store: &std::path::Path,
+) -> Result<Vec<BrokenAttachment>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id FROM attachments")
+        .map_err(|e| e.to_string())?;
+    let known = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<std::collections::HashSet<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let files = attachment_files(store);
+    let mut stmt = conn
+        .prepare("SELECT id, body FROM notes WHERE instr(body, ?1) > 0 ORDER BY id")
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map(rusqlite::params![ATTACHMENT_SCHEME], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut broken = Vec::new();
+    for (note_id, body) in notes {
+        let mut seen = std::collections::HashSet::new();
+        map_attachment_refs(&body, &mut |id| {
+            let reason = if !known.contains(id) {
+                "no row"
+            } else if !files.contains_key(id) {
+                "file missing"
+            } else {
+                return None;
+            };
+            if seen.insert(id.to_string()) {
+                broken.push(BrokenAttachment {
+                    note_id: note_id.clone(),
+                    attachment_ref: id.to_string(),
+                    reason: reason.to_string(),
+                });
+            }
+            None
+        });
+    }
+    Ok(broken)
+}
+
+// Notes that have attachments, the most first
+#[tauri::command]
+fn get_notes_with_attachments(db: State<Db>) -> Result<Vec<NoteWithAttachmentCount>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+             n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash, a.cnt \
+             FROM notes n \
+             JOIN (SELECT note_id, COUNT(*) AS cnt FROM attachments GROUP BY note_id) a ON a.note_id = n.id \
+             ORDER BY a.cnt DESC, n.updated_at DESC, n.id",
+        )
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map([], |row| {
+            Ok(NoteWithAttachmentCount {
+                metadata: note_metadata_from_row(row)?,
+                attachment_count: row.get(10)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+// ===== Inbox commands =====
+
+const INBOX_FOLDER_SETTING: &str = "inbox_folder_id";
+
+fn get_setting(conn: &Connection, key: &str) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        rusqlite::params![key],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+fn set_setting(conn: &Connection, key: &str, value: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?1, ?2) \
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        rusqlite::params![key, value],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// The quick-capture folder: the configured one if it still exists, otherwise
+// a top-level folder named "Inbox".
+fn inbox_folder_id(conn: &Connection) -> Result<Option<String>, String> {
+    if let Some(id) = get_setting(conn, INBOX_FOLDER_SETTING)? {
+        let exists: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM folders WHERE id = ?1)",
+                rusqlite::params![id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if exists {
+            return Ok(Some(id));
+        }
+    }
+    conn.query_row(
+        "SELECT id FROM folders WHERE parent_id IS NULL AND name = 'Inbox' COLLATE NOCASE \
+         ORDER BY created_at LIMIT 1",
+        [],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_inbox_folder(db: State<Db>) -> Result<Option<String>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    inbox_folder_id(&conn)
+}
+
+#[tauri::command]
+fn set_inbox_folder(db: State<Db>, folder_id: String) -> Result<(), String> {
+    let conn = db.write()?;
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM folders WHERE id = ?1)",
+            rusqlite::params![folder_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err("folder not found".to_string());
+    }
+    set_setting(&conn, INBOX_FOLDER_SETTING, &folder_id)
+}
+
+// Renames the inbox folder and pins the inbox setting to it, so captures keep
+// following the folder once it no longer matches the "Inbox" name fallback.
+#[tauri::command]
+fn rename_inbox(db: State<Db>, new_name: String) -> Result<String, String> {
+    let name = new_name.trim();
+    if name.is_empty() {
+        return Err("folder name cannot be empty".to_string());
+    }
+    let conn = db.write()?;
+    let Some(id) = inbox_folder_id(&conn)? else {
+        return Err("no inbox folder".to_string());
+    };
+    conn.execute(
+        "UPDATE folders SET name = ?1 WHERE id = ?2",
+        rusqlite::params![name, id],
+    )
+    .map_err(|e| e.to_string())?;
+    set_setting(&conn, INBOX_FOLDER_SETTING, &id)?;
+    Ok(id)
+}
+
+// Inbox notes newest capture first, ignoring the folder's manual order.
+// Empty when no inbox folder is configured or present.
+#[tauri::command]
+fn get_inbox_notes(db: State<Db>, limit: i64) -> Result<Vec<NoteMetadata>, String> {
+    let limit = limit.clamp(1, 500);
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let Some(folder_id) = inbox_folder_id(&conn)? else {
+        return Ok(Vec::new());
+    };
+    let mut stmt = conn
+        .prepare(
+            "SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+             n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+             FROM notes n \
+             WHERE n.folder_id = ?1 \
+             ORDER BY n.created_at DESC, n.id \
+             LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map(rusqlite::params![folder_id, limit], note_metadata_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+// ===== Shortcut commands =====
+
+// Ordered note ids for the tray menu, kept as a JSON array in app_settings.
+// Independent of folders and pins; ids of deleted notes linger until removed
+// and are skipped when resolved.
+const SHORTCUTS_SETTING: &str = "pinned_shortcuts";
+
+fn shortcut_ids(conn: &Connection) -> Result<Vec<String>, String> {
+    match get_setting(conn, SHORTCUTS_SETTING)? {
+        Some(json) => serde_json::from_str(&json).map_err(|e| e.to_string()),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn set_shortcut_ids(conn: &Connection, ids: &[String]) -> Result<(), String> {
+    let json = serde_json::to_string(ids).map_err(|e| e.to_string())?;
+    set_setting(conn, SHORTCUTS_SETTING, &json)
+}
+
+// Appends the note; a note that's already a shortcut keeps its place
+#[tauri::command]
+fn add_shortcut(db: State<Db>, note_id: String) -> Result<(), String> {
+    let conn = db.write()?;
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM notes WHERE id = ?1)",
+            rusqlite::params![note_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err("note not found".to_string());
+    }
+    let mut ids = shortcut_ids(&conn)?;
+    if !ids.contains(&note_id) {
+        ids.push(note_id);
+        set_shortcut_ids(&conn, &ids)?;
+    }
+    Ok(())
+}
+
+// Returns whether the note was a shortcut
+#[tauri::command]
+fn remove_shortcut(db: State<Db>, note_id: String) -> Result<bool, String> {
+    let conn = db.write()?;
+    let mut ids = shortcut_ids(&conn)?;
+    let before = ids.len();
+    ids.retain(|id| *id != note_id);
+    if ids.len() == before {
+        return Ok(false);
+    }
+    set_shortcut_ids(&conn, &ids)?;
+    Ok(true)
+}
+
+// `ids` must list the current shortcuts exactly once each, in the new order
+#[tauri::command]
+fn reorder_shortcuts(db: State<Db>, ids: Vec<String>) -> Result<(), String> {
+    let conn = db.write()?;
+    let current = shortcut_ids(&conn)?;
+    let mut sorted = ids.clone();
+    sorted.sort();
+    sorted.dedup();
+    let mut expected = current.clone();
+    expected.sort();
+    if sorted.len() != ids.len() || sorted != expected {
+        return Err("ids must match the current shortcuts".to_string());
+    }
+    set_shortcut_ids(&conn, &ids)
+}
+
+#[tauri::command]
+fn get_shortcuts(db: State<Db>) -> Result<Vec<NoteMetadata>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
+             n.created_at, n.updated_at, n.pinned, n.sort_order, n.starred, n.content_hash \
+             FROM notes n WHERE n.id = ?1",
+        )
+        .map_err(|e| e.to_string())?;
+    let mut notes = Vec::new();
+    for id in shortcut_ids(&conn)? {
+        let note = stmt
+            .query_row(rusqlite::params![id], note_metadata_from_row)
+            .optional()
+            .map_err(|e| e.to_string())?;
+        notes.extend(note);
+    }
+    Ok(notes)
+}
+
+// ===== Data migration command =====
+
+#[tauri::command]
+fn import_data(
+    db: State<Db>,
+    folders: Vec<Folder>,
+    notes: Vec<Note>,
+    tags: Option<Vec<Tag>>,
+    note_tags: Option<Vec<NoteTag>>,
+    note_meta: Option<Vec<NoteMeta>>,
+) -> Result<(), String> {
+    let _maintenance = db.maintenance();
+    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+    fast_import(&mut conn, |tx| {
+        import_rows(
+            tx,
+            &folders,
+            &notes,
+            tags.as_deref(),
+            note_tags.as_deref(),
+            note_meta.as_deref(),
+        )
+    })
+}
+
+// Like import_data, but rows may arrive in any order (notes before their
+// folders, children before parents). References are validated at commit.
+#[tauri::command]
+fn import_data_unordered(
+    db: State<Db>,
+    folders: Vec<Folder>,
+    notes: Vec<Note>,
+    tags: Option<Vec<Tag>>,
+    note_tags: Option<Vec<NoteTag>>,
+    note_meta: Option<Vec<NoteMeta>>,
+) -> Result<(), String> {
+    let _maintenance = db.maintenance();
+    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+    with_deferred_fk(&mut conn, |tx| {
+        import_rows(
+            tx,
+            &folders,
+            &notes,
+            tags.as_deref(),
+            note_tags.as_deref(),
+            note_meta.as_deref(),
+        )?;
+        // Count triggers can't see folders that hadn't been inserted yet
+        rebuild_folder_counts(tx)
+    })
+}
+
+fn import_rows(
+    conn: &Connection,
+    folders: &[Folder],
+    notes: &[Note],
+    tags: Option<&[Tag]>,
+    note_tags: Option<&[NoteTag]>,
+    note_meta: Option<&[NoteMeta]>,
+) -> Result<(), String> {
+    for folder in folders {
+        conn.execute(
+            "INSERT OR IGNORE INTO folders (id, name, created_at, parent_id, sort_order, sort_mode) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![folder.id, folder.name, folder.created_at, folder.parent_id, folder.sort_order, folder.sort_mode],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    for note in notes {
+        conn.execute(
+            "INSERT OR IGNORE INTO notes (id, folder_id, title, body, created_at, updated_at, pinned, sort_order, starred) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            rusqlite::params![note.id, note.folder_id, note.title, note.body, note.created_at, note.updated_at, note.pinned, note.sort_order, note.starred],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    // Older backups predate tags. Names are unique, so a tag whose name is
+    // already taken under another id is merged into that one.
+    let mut tag_ids: std::collections::HashMap<&str, String> = std::collections::HashMap::new();
+    for tag in tags.into_iter().flatten() {
+        conn.execute(
+            "INSERT OR IGNORE INTO tags (id, name, color, created_at) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![tag.id, tag.name, tag.color, tag.created_at],
+        )
+        .map_err(|e| e.to_string())?;
+        let id: String = conn
+            .query_row(
+                "SELECT id FROM tags WHERE id = ?1 OR name = ?2 ORDER BY id = ?1 DESC LIMIT 1",
+                rusqlite::params![tag.id, tag.name],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        tag_ids.insert(&tag.id, id);
+    }
+    for link in note_tags.into_iter().flatten() {
+        let tag_id = tag_ids.get(link.tag_id.as_str()).unwrap_or(&link.tag_id);
+        conn.execute(
+            "INSERT OR IGNORE INTO note_tags (note_id, tag_id) VALUES (?1, ?2)",
+            rusqlite::params![link.note_id, tag_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    for meta in note_meta.into_iter().flatten() {
+        conn.execute(
+            "INSERT OR IGNORE INTO note_meta (note_id, key, value) VALUES (?1, ?2, ?3)",
+            rusqlite::params![meta.note_id, meta.key, meta.value],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// Runs `f` in a transaction with foreign-key checks deferred to commit, so
+// rows can be inserted in any order. SQLite resets the pragma when the
+// transaction ends. On violation nothing is committed and the error names
+// the offending rows.
+fn with_deferred_fk<T>(
+    conn: &mut Connection,
+    f: impl FnOnce(&rusqlite::Transaction) -> Result<T, String>,
+) -> Result<T, String> {
+    fast_import(conn, |tx| {
+        tx.pragma_update(None, "defer_foreign_keys", true)
+            .map_err(|e| e.to_string())?;
+        let value = f(tx)?;
+        let violations = foreign_key_violations(tx)?;
+        if !violations.is_empty() {
+            return Err(format!(
+                "foreign key check failed: {}",
+                violations.join(", ")
+            ));
+        }
+        Ok(value)
+    })
+}
+
+// Runs a bulk import as one transaction with `synchronous = OFF`, restoring
+// the previous level afterwards even if `f` fails. The trade-off: SQLite
+// skips fsyncs while it runs, so an OS crash or power loss mid-import can
+// lose the import or corrupt the file (an app crash can't). Imports can be
+// re-run from their source, so only they get this.
+fn fast_import<T>(
+    conn: &mut Connection,
+    f: impl FnOnce(&rusqlite::Transaction) -> Result<T, String>,
+) -> Result<T, String> {
+    let previous: i64 = conn
+        .pragma_query_value(None, "synchronous", |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "synchronous", "OFF")
+        .map_err(|e| e.to_string())?;
+    let result = conn
+        .transaction()
+        .map_err(|e| e.to_string())
+        .and_then(|tx| {
+            let value = f(&tx)?;
+            tx.commit().map_err(|e| e.to_string())?;
+            Ok(value)
+        });
+    let restored = conn
+        .pragma_update(None, "synchronous", previous)
+        .map_err(|e| e.to_string());
+    let value = result?;
+    restored?;
+    Ok(value)
+}
+
+// Lists rows with dangling references as "table id -> parent table"
+fn foreign_key_violations(conn: &Connection) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare("PRAGMA foreign_key_check")
+        .map_err(|e| e.to_string())?;
+    let rows: Vec<(String, i64, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    for (table, rowid, parent) in rows {
+        let key = match table.as_str() {
+            "note_tags" => "note_id || '/' || tag_id",
+            "note_meta" => "note_id || '/' || key",
+            "folder_counts" => "folder_id",
+            _ => "id",
+        };
+        let id: String = conn
+            .query_row(
+                &format!("SELECT {} FROM \"{}\" WHERE rowid = ?1", key, table),
+                rusqlite::params![rowid],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        let entry = format!("{} {} -> {}", table, id, parent);
+        if !out.contains(&entry) {
+            out.push(entry);
+        }
+    }
+    Ok(out)
+}
+
+// Same insert-or-ignore semantics as import_data, but reports which rows
+// were skipped because the id already existed and whether their content differs.
+#[tauri::command]
+fn import_data_report(
+    db: State<Db>,
+    folders: Vec<Folder>,
+    notes: Vec<Note>,
+) -> Result<ImportReport, String> {
+    let _maintenance = db.maintenance();
+    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut report = ImportReport {
+        inserted_folders: 0,
+        inserted_notes: 0,
+        skipped_conflicts: Vec::new(),
+    };
+    for folder in &folders {
+        let inserted = tx.execute(
+            "INSERT OR IGNORE INTO folders (id, name, created_at, parent_id, sort_order, sort_mode) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![folder.id, folder.name, folder.created_at, folder.parent_id, folder.sort_order, folder.sort_mode],
+        )
+        .map_err(|e| e.to_string())?;
+        if inserted > 0 {
+            report.inserted_folders += 1;
+            continue;
+        }
+        let stored: String = tx
+            .query_row(
+                "SELECT name FROM folders WHERE id = ?1",
+                rusqlite::params![folder.id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        report.skipped_conflicts.push(ImportConflict {
+            id: folder.id.clone(),
+            kind: "folder".to_string(),
+            differs: stored != folder.name,
+        });
+    }
+    for note in &notes {
+        let inserted = tx.execute(
+            "INSERT OR IGNORE INTO notes (id, folder_id, title, body, created_at, updated_at, pinned, sort_order, starred) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            rusqlite::params![note.id, note.folder_id, note.title, note.body, note.created_at, note.updated_at, note.pinned, note.sort_order, note.starred],
+        )
+        .map_err(|e| e.to_string())?;
+        if inserted > 0 {
+            report.inserted_notes += 1;
+            continue;
+        }
+        let stored: String = tx
+            .query_row(
+                "SELECT body FROM notes WHERE id = ?1",
+                rusqlite::params![note.id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        report.skipped_conflicts.push(ImportConflict {
+            id: note.id.clone(),
+            kind: "note".to_string(),
+            differs: stored != note.body,
+        });
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+// Merges another anote database into this one. Rows are matched by id (ids
+// are generated per device, so they don't clash by accident); missing rows are
+// copied over. A note both sides have is kept as is with "skip", or replaced
+// by the other copy when that was edited later with "newer-wins". Tags are
+// matched by name, as names are unique. Attachments stay behind since their
+// files live on the other machine.
+#[tauri::command]
+fn merge_database(
+    db: State<Db>,
+    other_path: String,
+    strategy: String,
+) -> Result<MergeReport, String> {
+    let newer_wins = match strategy.as_str() {
+        "skip" => false,
+        "newer-wins" => true,
+        _ => return Err(format!("unknown merge strategy: {}", strategy)),
+    };
+    // ATTACH would create a missing file
+    let other = std::path::Path::new(&other_path);
+    if !other.is_file() {
+        return Err("database file not found".to_string());
+    }
+    let _maintenance = db.maintenance();
+    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+    let main_path: String = conn
+        .query_row(
+            "SELECT file FROM pragma_database_list WHERE name = 'main'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !main_path.is_empty()
+        && std::fs::canonicalize(&main_path).ok() == std::fs::canonicalize(other).ok()
+    {
+        return Err("cannot merge a database into itself".to_string());
+    }
+    conn.execute(
+        "ATTACH DATABASE ?1 AS merge_src",
+        rusqlite::params![other_path],
+    )
+    .map_err(|e| e.to_string())?;
+    let result = merge_attached(&mut conn, newer_wins);
+    let detached = conn
+        .execute("DETACH DATABASE merge_src", [])
+        .map_err(|e| e.to_string());
+    let report = result?;
+    detached?;
+    Ok(report)
+}
+
+fn merge_attached(conn: &mut Connection, newer_wins: bool) -> Result<MergeReport, String> {
+    let version: i32 = conn
+        .query_row("PRAGMA merge_src.user_version", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if version != REQUIRED_SCHEMA_VERSION {
+        return Err(format!(
+            "other database is at schema version {} (expected {}); open it with this version of anote first",
+            version, REQUIRED_SCHEMA_VERSION
+        ));
+    }
+    with_deferred_fk(conn, |tx| {
+        let folders = tx
+            .execute(
+                "INSERT INTO folders (id, name, created_at, parent_id, sort_order, sort_mode) \
+                 SELECT id, name, created_at, parent_id, sort_order, sort_mode FROM merge_src.folders \
+                 WHERE id NOT IN (SELECT id FROM main.folders)",
+                [],
+            )
+            .map_err(|e| e.to_string())?;
+
+        let mut stmt = tx
+            .prepare(
+                "SELECT o.id, o.updated_at > n.updated_at FROM merge_src.notes o \
+                 JOIN main.notes n ON n.id = o.id \
+                 WHERE o.content_hash IS NOT n.content_hash ORDER BY o.id",
+            )
+            .map_err(|e| e.to_string())?;
+        let both: Vec<(String, bool)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        drop(stmt);
+        let mut replaced = 0;
+        for (id, newer) in &both {
+            if newer_wins && *newer {
+                tx.execute(
+                    "UPDATE main.notes SET (folder_id, title, body, updated_at, pinned, sort_order, starred) = \
+                     (SELECT folder_id, title, body, updated_at, pinned, sort_order, starred FROM merge_src.notes WHERE id = ?1) \
+                     WHERE id = ?1",
+                    rusqlite::params![id],
+                )
+                .map_err(|e| e.to_string())?;
+                replaced += 1;
+            }
+        }
+        let notes = tx
+            .execute(
+                "INSERT INTO notes (id, folder_id, title, body, created_at, updated_at, pinned, sort_order, starred) \
+                 SELECT id, folder_id, title, body, created_at, updated_at, pinned, sort_order, starred FROM merge_src.notes \
+                 WHERE id NOT IN (SELECT id FROM main.notes)",
+                [],
+            )
+            .map_err(|e| e.to_string())?;
+
+        let tags = tx
+            .execute(
+                "INSERT INTO tags (id, name, color, created_at) \
+                 SELECT id, name, color, created_at FROM merge_src.tags o \
+                 WHERE NOT EXISTS (SELECT 1 FROM main.tags t WHERE t.id = o.id OR t.name = o.name)",
+                [],
+            )
+            .map_err(|e| e.to_string())?;
+        tx.execute(
+            "INSERT OR IGNORE INTO note_tags (note_id, tag_id, applied_at) \
+             SELECT nt.note_id, (SELECT t.id FROM main.tags t WHERE t.name = o.name), nt.applied_at \
+             FROM merge_src.note_tags nt JOIN merge_src.tags o ON o.id = nt.tag_id \
+             WHERE nt.note_id IN (SELECT id FROM main.notes)",
+            [],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.execute(
+            "INSERT OR IGNORE INTO note_meta (note_id, key, value) \
+             SELECT note_id, key, value FROM merge_src.note_meta \
+             WHERE note_id IN (SELECT id FROM main.notes)",
+            [],
+        )
+        .map_err(|e| e.to_string())?;
+
+        tx.execute("INSERT INTO notes_fts(notes_fts) VALUES('rebuild')", [])
+            .map_err(|e| e.to_string())?;
+        // Count triggers can't see parents inserted after their children
+        rebuild_folder_counts(tx)?;
+        Ok(MergeReport {
+            folders,
+            notes,
+            tags,
+            conflicts: both.into_iter().map(|(id, _)| id).collect(),
+            replaced,
+        })
+    })
+}
+
+// Imports a Joplin .jex export into `folder_id`: notebooks become subfolders
+// (nested as in Joplin) and notes are appended to their notebook. Resources
+// are skipped. Returns the number of notes imported.
+#[tauri::command]
+fn import_joplin(db: State<Db>, path: String, folder_id: String) -> Result<usize, String> {
+    let data = std::fs::read(&path).map_err(|e| e.to_string())?;
+    let entries = archive::read_tar(&data).map_err(|e| e.to_string())?;
+    let items: Vec<joplin::Item> = entries
+        .iter()
+        .filter(|(name, _)| name.ends_with(".md") && !name.contains('/'))
+        .filter_map(|(_, content)| joplin::parse(&String::from_utf8_lossy(content)))
+        .collect();
+
+    let mut conn = db.write()?;
+    fast_import(&mut conn, |tx| {
+        let exists: bool = tx
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM folders WHERE id = ?1)",
+                rusqlite::params![folder_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if !exists {
+            return Err("folder not found".to_string());
+        }
+        let now = chrono::Utc::now().timestamp_millis();
+
+        // Notebooks are created parents-first; ones whose parent isn't in the
+        // export (or that form a cycle) go directly under the target folder
+        let notebook_ids: std::collections::HashSet<&str> = items
+            .iter()
+            .filter(|i| i.kind == joplin::TYPE_FOLDER)
+            .map(|i| i.id.as_str())
+            .collect();
+        let mut pending: Vec<&joplin::Item> = items
+            .iter()
+            .filter(|i| i.kind == joplin::TYPE_FOLDER)
+            .collect();
+        pending.sort_by(|a, b| a.title.cmp(&b.title));
+        let mut folder_map: std::collections::HashMap<&str, String> =
+            std::collections::HashMap::new();
+        while !pending.is_empty() {
+            let ready = pending.iter().position(|i| {
+                !notebook_ids.contains(i.parent_id.as_str())
+                    || folder_map.contains_key(i.parent_id.as_str())
+            });
+            let item = pending.remove(ready.unwrap_or(0));
+            let parent = folder_map
+                .get(item.parent_id.as_str())
+                .cloned()
+                .unwrap_or_else(|| folder_id.clone());
+            let id = generate_id();
+            let name = if item.title.is_empty() {
+                "Untitled"
+            } else {
+                &item.title
+            };
+            insert_folder(tx, &id, name, item.created.unwrap_or(now), Some(&parent))?;
+            folder_map.insert(&item.id, id);
+        }
+
+        let mut imported = 0;
+        for item in items.iter().filter(|i| i.kind == joplin::TYPE_NOTE) {
+            let target = folder_map
+                .get(item.parent_id.as_str())
+                .unwrap_or(&folder_id);
+            let created = item.created.unwrap_or(now);
+            tx.execute(
+                "INSERT INTO notes (id, folder_id, title, body, created_at, updated_at, pinned, sort_order) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0, (SELECT COALESCE(MAX(sort_order) + 1, 0) FROM notes WHERE folder_id = ?2 AND pinned = 0))",
+                rusqlite::params![generate_id(), target, item.title, item.body, created, item.updated.unwrap_or(created)],
+            )
+            .map_err(|e| e.to_string())?;
+            imported += 1;
+        }
+        Ok(imported)
+    })
+}
+
+// Splits a text file into notes on `delimiter` and appends them to the folder
+// in file order, titled like create_note_from_markdown. A single-line
+// delimiter such as `---` must sit on a line by itself; one containing line
+// breaks (e.g. "\n\n\n" for blank-line runs) matches anywhere. Returns the count.
+#[tauri::command]
+fn import_split_text(
+    db: State<Db>,
+    path: String,
+    folder_id: String,
+    delimiter: String,
+) -> Result<usize, String> {
+    if delimiter.trim_matches(' ').is_empty() {
+        return Err("delimiter cannot be empty".to_string());
+    }
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())?
+        .replace("\r\n", "\n");
+    let chunks: Vec<String> = if delimiter.contains('\n') {
+        text.split(delimiter.as_str()).map(str::to_string).collect()
+    } else {
+        let mut chunks = vec![String::new()];
+        for line in text.lines() {
+            if line.trim() == delimiter.trim() {
+                chunks.push(String::new());
+            } else if let Some(chunk) = chunks.last_mut() {
+                chunk.push_str(line);
+                chunk.push('\n');
+            }
+        }
+        chunks
+    };
+
+    let mut conn = db.write()?;
+    fast_import(&mut conn, |tx| {
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut imported = 0;
+        for chunk in chunks.iter().map(|c| c.trim()).filter(|c| !c.is_empty()) {
+            let (title, body) = title_from_markdown(chunk);
+            tx.execute(
+                "INSERT INTO notes (id, folder_id, title, body, created_at, updated_at, pinned, sort_order) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?5, 0, (SELECT COALESCE(MAX(sort_order) + 1, 0) FROM notes WHERE folder_id = ?2 AND pinned = 0))",
+                rusqlite::params![generate_id(), folder_id, title, body, now],
+            )
+            .map_err(|e| e.to_string())?;
+            imported += 1;
+        }
+        Ok(imported)
+    })
+}
+
+// Loads a `.anote` file written by export_note_anotefile as a new note at the
+// top of the folder, with a fresh id and timestamps. Tags are matched by name
+// and created when missing; embedded attachments get fresh ids too, and the
+// body is pointed at them. Returns the new note's id.
+#[tauri::command]
+fn import_anotefile(db: State<Db>, path: String, folder_id: String) -> Result<String, String> {
+    let mut conn = db.write()?;
+    let store = attachments_dir()?;
+    read_anotefile(&mut conn, &path, &folder_id, &store)
+}
+
+fn read_anotefile(
+    conn: &mut Connection,
+    path: &str,
+    folder_id: &str,
+    store: &std::path::Path,
+) -> Result<String, String> {
+    let data = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let file: serde_json::Value = serde_json::from_str(&data).map_err(|e| e.to_string())?;
+    if file["type"].as_str() != Some(ANOTEFILE_TYPE) {
+        return Err("not an anote single-note file".to_string());
+    }
+    let version = file["version"].as_i64().unwrap_or(0);
+    if version > ANOTEFILE_VERSION {
+        return Err(format!("unsupported .anote version: {}", version));
+    }
+    let note = &file["note"];
+    let title = note["title"].as_str().unwrap_or("");
+    let body = note["body"].as_str().unwrap_or("");
+    let starred = note["starred"].as_i64().unwrap_or(0);
+
+    let id = generate_id();
+    let now = chrono::Utc::now().timestamp_millis();
+    // Old attachment id -> (new row, stored file name, contents)
+    let mut attachments = std::collections::HashMap::new();
+    for a in file["attachments"].as_array().into_iter().flatten() {
+        let (Some(old_id), Some(data)) = (a["id"].as_str(), a["data"].as_str()) else {
+            return Err("invalid attachment in .anote file".to_string());
+        };
+        let bytes = base64::decode(data).ok_or("invalid attachment data in .anote file")?;
+        let new_id = generate_id();
+        let stored = match a["extension"].as_str().filter(|e| is_safe_id(e)) {
+            Some(ext) => format!("{}.{}", new_id, ext),
+            None => new_id.clone(),
+        };
+        let row = Attachment {
+            id: new_id,
+            note_id: id.clone(),
+            file_name: a["file_name"].as_str().unwrap_or(old_id).to_string(),
+            size: bytes.len() as i64,
+            created_at: now,
+        };
+        attachments.insert(old_id.to_string(), (row, stored, bytes));
+    }
+    let body = map_attachment_refs(body, &mut |old_id| {
+        let (row, _, _) = attachments.get(old_id)?;
+        Some(format!("{}{}", ATTACHMENT_SCHEME, row.id))
+    });
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute(
+        "UPDATE notes SET sort_order = sort_order + 1 WHERE folder_id = ?1 AND pinned = 0",
+        rusqlite::params![folder_id],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.execute(
+        "INSERT INTO notes (id, folder_id, title, body, created_at, updated_at, pinned, sort_order, starred) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5, 0, 0, ?6)",
+        rusqlite::params![id, folder_id, title, body, now, starred],
+    )
+    .map_err(|e| e.to_string())?;
+    for tag in file["tags"].as_array().into_iter().flatten() {
+        let Some(name) = tag["name"].as_str().filter(|n| !n.trim().is_empty()) else {
+            continue;
+        };
+        let tag_id = ensure_tag(&tx, name)?;
+        // Only fills in a color; an existing tag keeps its own
+        tx.execute(
+            "UPDATE tags SET color = ?1 WHERE id = ?2 AND color IS NULL",
+            rusqlite::params![tag["color"].as_str(), tag_id],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.execute(
+            "INSERT OR IGNORE INTO note_tags (note_id, tag_id) VALUES (?1, ?2)",
+            rusqlite::params![id, tag_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    let mut files = Vec::with_capacity(attachments.len());
+    for (row, stored, bytes) in attachments.into_values() {
+        tx.execute(
+            "INSERT INTO attachments (id, note_id, file_name, size, created_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![row.id, row.note_id, row.file_name, row.size, row.created_at],
+        )
+        .map_err(|e| e.to_string())?;
+        files.push((stored, bytes));
+    }
+    commit_with_attachment_files(tx, store, &files)?;
+    Ok(id)
+}
+
+// ===== Diagnostics commands =====
+
+#[tauri::command]
+fn get_schema_version(db: State<Db>) -> Result<i32, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.pragma_query_value(None, "user_version", |r| r.get(0))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_db_diagnostics(db: State<Db>) -> Result<DbDiagnostics, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let schema_version: i32 = conn
+        .pragma_query_value(None, "user_version", |r| r.get(0))
+        .map_err(|e| e.to_string())?;
+    let page_count: i64 = conn
+        .pragma_query_value(None, "page_count", |r| r.get(0))
+        .map_err(|e| e.to_string())?;
+    let fts_ok = conn
+        .execute(
+            "INSERT INTO notes_fts(notes_fts) VALUES('integrity-check')",
+            [],
+        )
+        .is_ok();
+    // In-memory databases report an empty path
+    let db_path = conn.path().filter(|p| !p.is_empty()).map(String::from);
+    let file_size = |path: &str| std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let (db_size_bytes, wal_size_bytes) = match &db_path {
+        Some(path) => (file_size(path), file_size(&format!("{}-wal", path))),
+        None => (0, 0),
+    };
+    Ok(DbDiagnostics {
+        schema_version,
+        db_path,
+        db_size_bytes,
+        wal_size_bytes,
+        page_count,
+        fts_ok,
+    })
+}
+
+// Space taken by the search index: the notes_fts shadow tables. dbstat is
+// an optional SQLite build feature, so without it the size is estimated from
+// the blob and docsize rows and rounded up to whole pages.
+#[tauri::command]
+fn get_fts_stats(db: State<Db>) -> Result<FtsStats, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    // The shadow docsize table holds one row per indexed note
+    let doc_count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM notes_fts_docsize", [], |row| {
+            row.get(0)
+        })
+        .map_err(|e| e.to_string())?;
+    let exact = conn
+        .query_row(
+            "SELECT COUNT(*), COALESCE(SUM(pgsize), 0) FROM dbstat \
+             WHERE name LIKE 'notes\\_fts\\_%' ESCAPE '\\'",
+            [],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)),
+        )
+        .ok();
+    let (index_pages, index_bytes) = match exact {
+        Some(stats) => stats,
+        None => {
+            let page_size: i64 = conn
+                .pragma_query_value(None, "page_size", |r| r.get(0))
+                .map_err(|e| e.to_string())?;
+            let bytes: i64 = conn
+                .query_row(
+                    "SELECT (SELECT COALESCE(SUM(LENGTH(block)), 0) FROM notes_fts_data) \
+                          + (SELECT COALESCE(SUM(LENGTH(sz)), 0) FROM notes_fts_docsize)",
+                    [],
+                    |row| row.get(0),
+                )
+                .map_err(|e| e.to_string())?;
+            ((bytes + page_size - 1) / page_size, bytes)
+        }
+    };
+    Ok(FtsStats {
+        index_pages,
+        index_bytes,
+        doc_count,
+        exact: exact.is_some(),
+    })
+}
+
+// Repairs the search index for a note whose entry is stale or missing. FTS5
+// can only drop an external-content entry given the exact text that was
+// indexed, which is unknown here, so a failed integrity check against the
+// notes table triggers a full rebuild. Returns whether a rebuild was needed.
+#[tauri::command]
+fn reindex_note(db: State<Db>, id: String) -> Result<bool, String> {
+    let conn = db.write()?;
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM notes WHERE id = ?1)",
+            rusqlite::params![id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err(format!("note not found: {}", id));
+    }
+    // rank = 1 also compares the index with the content table
+    if conn
+        .execute(
+            "INSERT INTO notes_fts(notes_fts, rank) VALUES('integrity-check', 1)",
+            [],
+        )
+        .is_ok()
+    {
+        return Ok(false);
+    }
+    conn.execute("INSERT INTO notes_fts(notes_fts) VALUES('rebuild')", [])
+        .map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+// Shape of the folder tree. Root folders are depth 1; child counts are per
+// folder, averaged over all folders.
+#[tauri::command]
+fn get_tree_stats(db: State<Db>) -> Result<TreeStats, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.query_row(
+        "WITH RECURSIVE tree(id, depth) AS ( \
+             SELECT id, 1 FROM folders WHERE parent_id IS NULL \
+             UNION ALL \
+             SELECT f.id, t.depth + 1 FROM folders f JOIN tree t ON f.parent_id = t.id \
+         ), \
+         children AS ( \
+             SELECT p.id, COUNT(c.id) AS n FROM folders p \
+             LEFT JOIN folders c ON c.parent_id = p.id GROUP BY p.id \
+         ) \
+         SELECT (SELECT COALESCE(MAX(depth), 0) FROM tree), \
+                (SELECT COUNT(*) FROM folders), \
+                (SELECT COALESCE(MAX(n), 0) FROM children), \
+                (SELECT COALESCE(AVG(n), 0.0) FROM children)",
+        [],
+        |row| {
+            Ok(TreeStats {
+                max_depth: row.get(0)?,
+                folder_count: row.get(1)?,
+                max_children: row.get(2)?,
+                avg_children: row.get(3)?,
+            })
+        },
+    )
+    .map_err(|e| e.to_string())
+}
+
+// Notes with the biggest bodies, in UTF-8 bytes (LENGTH on text counts
+// characters). Deleted notes are removed outright, so there's nothing to skip.
+#[tauri::command]
+fn get_largest_notes(db: State<Db>, limit: i64) -> Result<Vec<NoteSize>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, title, folder_id, LENGTH(CAST(body AS BLOB)) AS size FROM notes \
+             ORDER BY size DESC, id LIMIT ?1",
+        )
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map(rusqlite::params![limit.clamp(1, 500)], |row| {
+            Ok(NoteSize {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                folder_id: row.get(2)?,
+                byte_size: row.get(3)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notes)
+}
+
+// Problems reported by SQLite's integrity check plus any dangling foreign
+// keys. Empty when the database is healthy.
+#[tauri::command]
+fn check_integrity(db: State<Db>) -> Result<Vec<String>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("PRAGMA integrity_check")
+        .map_err(|e| e.to_string())?;
+    let mut problems = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?
+        .filter(|msg| !matches!(msg.as_deref(), Ok("ok")))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    problems.extend(foreign_key_violations(&conn)?);
+    Ok(problems)
+}
+
+// Deletes note_tags rows pointing at missing notes or tags (possible only if
+// rows were written with foreign keys off). Returns the number removed.
+#[tauri::command]
+fn repair_note_tags(db: State<Db>) -> Result<i64, String> {
+    let _maintenance = db.maintenance();
+    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let removed = tx
+        .execute(
+            "DELETE FROM note_tags \
+             WHERE note_id NOT IN (SELECT id FROM notes) \
+             OR tag_id NOT IN (SELECT id FROM tags)",
+            [],
+        )
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(removed as i64)
+}
+
+// Recovery for a schema that doesn't match its user_version (e.g. init_db
+// interrupted mid-migration): compares against a fresh database migrated by
+// init_db and recreates missing tables, columns, indexes and triggers.
+// Returns one line per repair; empty when nothing was missing.
+#[tauri::command]
+fn verify_and_repair_schema(db: State<Db>) -> Result<Vec<String>, String> {
+    let _maintenance = db.maintenance();
+    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+    repair_schema(&mut conn)
+}
+
+// (type, name, sql) of every schema object with SQL, tables first
+fn schema_objects(conn: &Connection) -> Result<Vec<(String, String, String)>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT type, name, sql FROM sqlite_master \
+             WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\' \
+             ORDER BY CASE type WHEN 'table' THEN 0 WHEN 'index' THEN 1 ELSE 2 END, rowid",
+        )
+        .map_err(|e| e.to_string())?;
+    let objects = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(objects)
+}
+
+// Column definitions as `ALTER TABLE ... ADD COLUMN` accepts them, by name
+fn column_defs(conn: &Connection, table: &str) -> Result<Vec<(String, String)>, String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA foreign_key_list(\"{}\")", table))
+        .map_err(|e| e.to_string())?;
+    let references: std::collections::HashMap<String, String> = stmt
+        .query_map([], |row| {
+            let parent: String = row.get(2)?;
+            let from: String = row.get(3)?;
+            let to: String = row.get(4)?;
+            let on_delete: String = row.get(6)?;
+            let mut clause = format!(" REFERENCES {}({})", parent, to);
+            if on_delete != "NO ACTION" {
+                clause.push_str(&format!(" ON DELETE {}", on_delete));
+            }
+            Ok((from, clause))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info(\"{}\")", table))
+        .map_err(|e| e.to_string())?;
+    let columns = stmt
+        .query_map([], |row| {
+            let name: String = row.get(1)?;
+            let kind: String = row.get(2)?;
+            let not_null: bool = row.get(3)?;
+            let default: Option<String> = row.get(4)?;
+            let mut def = format!("{} {}", name, kind);
+            // NOT NULL can only be added along with a default
+            if let Some(default) = default {
+                if not_null {
+                    def.push_str(" NOT NULL");
+                }
+                def.push_str(&format!(" DEFAULT {}", default));
+            }
+            if let Some(clause) = references.get(&name) {
+                def.push_str(clause);
+            }
+            Ok((name, def))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(columns)
+}
+
+fn repair_schema(conn: &mut Connection) -> Result<Vec<String>, String> {
+    let reference = Connection::open_in_memory().map_err(|e| e.to_string())?;
+    init_db(&reference);
+    let expected = schema_objects(&reference)?;
+    let present: std::collections::HashSet<String> = schema_objects(conn)?
+        .into_iter()
+        .map(|(_, name, _)| name)
+        .collect();
+    // FTS shadow tables come and go with their virtual table
+    let virtual_tables: Vec<&str> = expected
+        .iter()
+        .filter(|(_, _, sql)| sql.starts_with("CREATE VIRTUAL TABLE"))
+        .map(|(_, name, _)| name.as_str())
+        .collect();
+    let is_shadow = |name: &str| {
+        virtual_tables
+            .iter()
+            .any(|v| name.starts_with(&format!("{}_", v)))
+    };
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut repairs = Vec::new();
+    let mut created = std::collections::HashSet::new();
+    for (kind, name, sql) in &expected {
+        if present.contains(name) || is_shadow(name) {
+            continue;
+        }
+        // Tables come first, so triggers and indexes find what they refer to
+        tx.execute_batch(sql).map_err(|e| e.to_string())?;
+        repairs.push(format!("created {} {}", kind, name));
+        created.insert(name.as_str());
+    }
+    let mut added = std::collections::HashSet::new();
+    for (_, table, _) in expected.iter().filter(|(kind, _, _)| kind == "table") {
+        let table = table.as_str();
+        if created.contains(table) || is_shadow(table) || virtual_tables.contains(&table) {
+            continue;
+        }
+        let existing: std::collections::HashSet<String> = column_defs(&tx, table)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        for (column, def) in column_defs(&reference, table)? {
+            if existing.contains(&column) {
+                continue;
+            }
+            tx.execute(&format!("ALTER TABLE {} ADD COLUMN {}", table, def), [])
+                .map_err(|e| e.to_string())?;
+            repairs.push(format!("added column {}.{}", table, column));
+            added.insert(format!("{}.{}", table, column));
+        }
+    }
+
+    // Derived data the recreated objects start without. Writes made while an
+    // FTS trigger was missing never reached the index, so that rebuilds it
+    // too. (The content_hash triggers are TEMP ones init_db recreates on every
+    // connection.)
+    let fts_stale = expected.iter().any(|(kind, name, sql)| {
+        created.contains(name.as_str())
+            && (name == "notes_fts" || (kind == "trigger" && sql.contains("notes_fts")))
+    });
+    if fts_stale {
+        tx.execute("INSERT INTO notes_fts(notes_fts) VALUES('rebuild')", [])
+            .map_err(|e| e.to_string())?;
+    }
+    if created.contains("folder_counts") {
+        rebuild_folder_counts(&tx)?;
+    }
+    if added.contains("notes.content_hash") {
+        tx.execute("UPDATE notes SET content_hash = note_hash(title, body)", [])
+            .map_err(|e| e.to_string())?;
+    }
+    if added.contains("note_tags.applied_at") {
+        tx.execute(
+            "UPDATE note_tags SET applied_at = (SELECT updated_at FROM notes WHERE notes.id = note_tags.note_id)",
+            [],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(repairs)
+}
+
+const JOURNAL_MODE_SETTING: &str = "journal_mode";
+
+// Leaving WAL checkpoints and truncates the log first so no -wal contents are
+// stranded. Returns the mode SQLite reports afterwards.
+fn apply_journal_mode(conn: &Connection, mode: &str) -> Result<String, String> {
+    if mode.eq_ignore_ascii_case("DELETE") {
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .map_err(|e| e.to_string())?;
+    }
+    conn.pragma_update_and_check(None, "journal_mode", mode, |row| row.get(0))
+        .map_err(|e| e.to_string())
+}
+
+// Switches between WAL and DELETE journaling (WAL's side files trip up
+// Dropbox/iCloud sync) and remembers the choice for future launches.
+#[tauri::command]
+fn set_journal_mode(db: State<Db>, mode: String) -> Result<String, String> {
+    let mode = mode.to_ascii_uppercase();
+    if mode != "WAL" && mode != "DELETE" {
+        return Err(format!("unsupported journal mode: {}", mode));
+    }
+    let conn = db.write()?;
+    let actual = apply_journal_mode(&conn, &mode)?;
+    if actual.eq_ignore_ascii_case(&mode) {
+        set_setting(&conn, JOURNAL_MODE_SETTING, &mode)?;
+    }
+    Ok(actual)
+}
+
+// ~/.anote/config.json: settings needed before the database is opened
+fn config_path() -> Result<std::path::PathBuf, String> {
+    let home = dirs::home_dir().ok_or("failed to get home directory")?;
+    Ok(home.join(".anote").join("config.json"))
+}
+
+// The database location recorded by relocate_db, if any
+fn configured_db_path(config: &std::path::Path) -> Option<std::path::PathBuf> {
+    let data = std::fs::read_to_string(config).ok()?;
+    let value: serde_json::Value = serde_json::from_str(&data).ok()?;
+    value["db_path"].as_str().map(std::path::PathBuf::from)
+}
+
+// Where to open the database at launch. A path recorded by relocate_db must
+// exist: a missing one (say, an unmounted drive) is an error rather than a
+// fresh empty database. The default ~/.anote/anote.db is seeded from the old
+// Tauri app data location on first run.
+fn startup_db_path(
+    anote_dir: &std::path::Path,
+    config: &std::path::Path,
+    legacy_db: Option<&std::path::Path>,
+) -> Result<std::path::PathBuf, String> {
+    if let Some(path) = configured_db_path(config) {
+        if !path.exists() {
+            return Err(format!(
+                "database not found at {} (set in {})",
+                path.display(),
+                config.display()
+            ));
+        }
+        return Ok(path);
+    }
+    let db_path = anote_dir.join("anote.db");
+    if !db_path.exists() {
+        if let Some(old_db) = legacy_db.filter(|p| p.exists()) {
+            let _ = std::fs::copy(old_db, &db_path);
+        }
+    }
+    Ok(db_path)
+}
+
+// Moves the database file to `new_path` and records it in config.json so
+// later launches open it there. The old file (and any -wal/-shm beside it)
+// is removed only once the copy has been opened and read back.
+#[tauri::command]
+fn relocate_db(db: State<Db>, new_path: String) -> Result<String, String> {
+    let _maintenance = db.maintenance();
+    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+    relocate(&mut conn, std::path::Path::new(&new_path), &config_path()?)
+}
+
+fn relocate(
+    conn: &mut Connection,
+    new_path: &std::path::Path,
+    config: &std::path::Path,
+) -> Result<String, String> {
+    let old_path = conn
+        .path()
+        .filter(|p| !p.is_empty())
+        .map(std::path::PathBuf::from)
+        .ok_or("database is not backed by a file")?;
+    if new_path.exists() {
+        return Err(format!("{} already exists", new_path.display()));
+    }
+    // VACUUM INTO writes a complete copy (WAL included) while the live
+    // connection stays open, so a failure anywhere below leaves it untouched
+    let moved = conn
+        .execute("VACUUM INTO ?1", [new_path.to_string_lossy()])
+        .map_err(|e| e.to_string())
+        .and_then(|_| open_initialized_db(new_path))
+        .and_then(|new_conn| {
+            new_conn
+                .query_row("SELECT COUNT(*) FROM notes", [], |row| row.get::<_, i64>(0))
+                .map_err(|e| e.to_string())?;
+            Ok(new_conn)
+        })
+        .and_then(|new_conn| {
+            let mut value: serde_json::Value = std::fs::read_to_string(config)
+                .ok()
+                .and_then(|data| serde_json::from_str(&data).ok())
+                .filter(serde_json::Value::is_object)
+                .unwrap_or_else(|| serde_json::json!({}));
+            value["db_path"] = serde_json::json!(new_path.to_string_lossy());
+            let json_str = serde_json::to_string_pretty(&value).map_err(|e| e.to_string())?;
+            if let Some(parent) = config.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            std::fs::write(config, json_str).map_err(|e| e.to_string())?;
+            Ok(new_conn)
+        });
+    match moved {
+        Ok(new_conn) => {
+            let old = std::mem::replace(conn, new_conn);
+            if let Err((_, e)) = old.close() {
+                log::warn!("could not close {}: {}", old_path.display(), e);
+            }
+            // The move already happened; a leftover old file is only clutter
+            let mut old_files = vec![old_path.clone()];
+            for suffix in ["-wal", "-shm"] {
+                let mut name = old_path.clone().into_os_string();
+                name.push(suffix);
+                old_files.push(name.into());
+            }
+            for file in old_files.iter().filter(|f| f.exists()) {
+                if let Err(e) = std::fs::remove_file(file) {
+                    log::warn!("could not remove {}: {}", file.display(), e);
+                }
+            }
+            Ok(new_path.to_string_lossy().into_owned())
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(new_path);
+            Err(e)
+        }
+    }
+}
+
+// Puts the app in maintenance mode: write commands fail with "maintenance in
+// progress" until end_maintenance, while maintenance commands (restores,
+// repairs, recounts) keep working.
+#[tauri::command]
+fn begin_maintenance(db: State<Db>) -> Result<(), String> {
+    // Taking the lock waits out a write that's already running
+    let _conn = db.0.lock().map_err(|e| e.to_string())?;
+    if db.1.swap(true, Ordering::SeqCst) {
+        return Err("maintenance in progress".to_string());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn end_maintenance(db: State<Db>) {
+    db.1.store(false, Ordering::SeqCst);
+}
+
+// ===== Export commands =====
+
+// Upper bound on notes written by search-based exports
+const EXPORT_SEARCH_LIMIT: i64 = 10_000;
+
+// Roughly one printed page of body text
+const CHARS_PER_PAGE: i64 = 3000;
+
+// Size of a folder export before running it, so the UI can warn about big
+// ones. Pages are a plain characters-per-page guess.
+#[tauri::command]
+fn estimate_folder_export(
+    db: State<Db>,
+    folder_id: String,
+    include_subfolders: bool,
+) -> Result<ExportEstimate, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM folders WHERE id = ?1)",
+            rusqlite::params![folder_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err("folder not found".to_string());
+    }
+    let folder_ids = if include_subfolders {
+        subtree_folder_ids(&conn, &folder_id)?
+    } else {
+        vec![folder_id]
+    };
+    let placeholders = vec!["?"; folder_ids.len()].join(", ");
+    let (note_count, total_body_bytes, total_chars): (i64, i64, i64) = conn
+        .query_row(
+            &format!(
+                "SELECT COUNT(*), COALESCE(SUM(LENGTH(CAST(body AS BLOB))), 0), \
+                 COALESCE(SUM(LENGTH(body)), 0) FROM notes WHERE folder_id IN ({})",
+                placeholders
+            ),
+            rusqlite::params_from_iter(&folder_ids),
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(ExportEstimate {
+        note_count,
+        total_body_bytes,
+        estimated_pdf_pages: (total_chars + CHARS_PER_PAGE - 1) / CHARS_PER_PAGE,
+    })
+}
+
+// Total body words across a folder's notes, optionally including every
+// subfolder. Counted with word_count, the same rule the tag report uses.
+#[tauri::command]
+fn get_folder_word_count(
+    db: State<Db>,
+    folder_id: String,
+    include_subfolders: bool,
+) -> Result<i64, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM folders WHERE id = ?1)",
+            rusqlite::params![folder_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err("folder not found".to_string());
+    }
+    let folder_ids = if include_subfolders {
+        subtree_folder_ids(&conn, &folder_id)?
+    } else {
+        vec![folder_id]
+    };
+    let placeholders = vec!["?"; folder_ids.len()].join(", ");
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT body FROM notes WHERE folder_id IN ({})",
+            placeholders
+        ))
+        .map_err(|e| e.to_string())?;
+    let mut rows = stmt
+        .query(rusqlite::params_from_iter(&folder_ids))
+        .map_err(|e| e.to_string())?;
+    let mut total = 0;
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let body: String = row.get(0).map_err(|e| e.to_string())?;
+        total += word_count(&body);
+    }
+    Ok(total)
+}
+
+// Writes a browsable HTML site to `out_dir`: index.html, one page per note
+// (`<id>.html`), style.css, and search.json with note metadata for
+// client-side search. Every page carries the folder tree as navigation and
+// wiki-links point at the linked note's page. Returns the pages written.
+#[tauri::command]
+fn export_site(db: State<Db>, out_dir: String) -> Result<usize, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT id, name, parent_id FROM folders ORDER BY sort_order, created_at, id")
+        .map_err(|e| e.to_string())?;
+    let folders: Vec<(String, String, Option<String>)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT n.id, n.folder_id, n.title, n.body, n.updated_at \
+             FROM notes n JOIN folders f ON f.id = n.folder_id ORDER BY {}",
+            FOLDER_NOTES_ORDER
+        ))
+        .map_err(|e| e.to_string())?;
+    let notes: Vec<(String, String, String, String, i64)> = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+
+    // Imported ids aren't guaranteed to be file-name safe
+    let pages: std::collections::HashMap<&str, String> = notes
+        .iter()
+        .enumerate()
+        .map(|(i, (id, ..))| {
+            let file = if is_safe_id(id) {
+                format!("{}.html", id)
+            } else {
+                format!("note-{}.html", i + 1)
+            };
+            (id.as_str(), file)
+        })
+        .collect();
+    let titles = |title: &str| {
+        if title.trim().is_empty() {
+            "Untitled".to_string()
+        } else {
+            title.trim().to_string()
+        }
+    };
+
+    let mut nav = String::new();
+    let mut stack: Vec<(Option<&str>, usize)> = vec![(None, 0)];
+    nav.push_str("<ul>\n<li><a href=\"index.html\">Home</a></li>\n");
+    // Depth-first walk of the tree; each stack entry is a parent and the
+    // index of the next child folder to visit
+    while let Some((parent, next)) = stack.pop() {
+        let children: Vec<&(String, String, Option<String>)> = folders
+            .iter()
+            .filter(|(_, _, p)| p.as_deref() == parent)
+            .collect();
+        if let Some((id, name, _)) = children.get(next) {
+            stack.push((parent, next + 1));
+            nav.push_str(&format!(
+                "<li><span class=\"folder\">{}</span>\n<ul>\n",
+                html::escape(name)
+            ));
+            stack.push((Some(id.as_str()), 0));
+            continue;
+        }
+        if let Some(folder_id) = parent {
+            for (id, _, title, _, _) in notes.iter().filter(|n| n.1 == folder_id) {
+                nav.push_str(&format!(
+                    "<li><a href=\"{}\">{}</a></li>\n",
+                    pages[id.as_str()],
+                    html::escape(&titles(title))
+                ));
+            }
+            nav.push_str("</ul></li>\n");
+        }
+    }
+    nav.push_str("</ul>\n");
+
+    let dir = std::path::Path::new(&out_dir);
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    std::fs::write(dir.join("style.css"), html::STYLE).map_err(|e| e.to_string())?;
+    let resolver = LinkResolver::new(
+        notes
+            .iter()
+            .map(|(id, _, title, _, _)| (id.as_str(), title.as_str())),
+    );
+    let wiki_href = |target: &str| {
+        resolver
+            .resolve(target)
+            .and_then(|id| pages.get(id))
+            .cloned()
+    };
+    let folder_names: std::collections::HashMap<&str, &str> = folders
+        .iter()
+        .map(|(id, name, _)| (id.as_str(), name.as_str()))
+        .collect();
+    let mut search = Vec::new();
+    for (id, folder_id, title, body, updated_at) in &notes {
+        let title = titles(title);
+        let content = format!(
+            "<h1>{}</h1>\n{}",
+            html::escape(&title),
+            html::render(body, &wiki_href)
+        );
+        std::fs::write(
+            dir.join(&pages[id.as_str()]),
+            html::page(&title, &nav, &content),
+        )
+        .map_err(|e| e.to_string())?;
+        search.push(serde_json::json!({
+            "id": id,
+            "title": title,
+            "folder": folder_names.get(folder_id.as_str()),
+            "updated_at": updated_at,
+            "url": pages[id.as_str()],
+        }));
+    }
+    let index = format!(
+        "<h1>Notes</h1>\n<p>{} notes in {} folders.</p>\n",
+        notes.len(),
+        folders.len()
+    );
+    std::fs::write(dir.join("index.html"), html::page("Notes", &nav, &index))
+        .map_err(|e| e.to_string())?;
+    let search_str = serde_json::to_string(&search).map_err(|e| e.to_string())?;
+    std::fs::write(dir.join("search.json"), search_str).map_err(|e| e.to_string())?;
+    Ok(notes.len() + 1)
+}
+
+#[tauri::command]
+fn export_search_markdown(db: State<Db>, query: String, path: String) -> Result<usize, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let ids = search_note_ids(&conn, &query, EXPORT_SEARCH_LIMIT)?;
+    let paths = folder_paths(&conn)?;
+    let mut out = format!("# Search: {}\n", query);
+    for id in &ids {
+        let (folder_id, title, body): (String, String, String) = conn
+            .query_row(
+                "SELECT folder_id, title, body FROM notes WHERE id = ?1",
+                rusqlite::params![id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        let title = if title.trim().is_empty() {
+            "Untitled"
+        } else {
+            &title
+        };
+        let folder = paths.get(&folder_id).map(String::as_str).unwrap_or("");
+        out.push_str(&format!(
+            "\n---\n\n## {}\n\n> {}\n\n{}\n",
+            title,
+            folder,
+            body.trim_end()
+        ));
+    }
+    std::fs::write(&path, out).map_err(|e| e.to_string())?;
+    Ok(ids.len())
+}
+
+// Cold-storage archive: one Markdown file per note under its folder path,
+// plus backup.json (restorable via import_data) and a tags.json index.
+#[tauri::command]
+fn export_archive(db: State<Db>, path: String) -> Result<ArchiveExport, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let dirs = folder_dirs(&conn)?;
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let file = std::fs::File::create(&path).map_err(|e| e.to_string())?;
+    let gz = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+    let mut tar = archive::TarWriter::new(gz);
+
+    let mut stmt = conn
+        .prepare("SELECT id, folder_id, title, body, updated_at FROM notes ORDER BY folder_id, sort_order, id")
+        .map_err(|e| e.to_string())?;
+    let notes = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, i64>(4)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut used = std::collections::HashSet::new();
+    let mut files = std::collections::HashMap::new();
+    for (id, folder_id, title, body, updated_at) in &notes {
+        let dir = match dirs.get(folder_id) {
+            Some(dir) => format!("notes/{}", dir),
+            None => "notes".to_string(),
+        };
+        let entry = unique_file_path(&mut used, &dir, &safe_file_name(title), "md");
+        let content = note_markdown(title, body);
+        tar.append(&entry, content.as_bytes(), updated_at / 1000)
+            .map_err(|e| e.to_string())?;
+        files.insert(id.as_str(), entry);
+    }
+
+    let backup = backup_json(&conn, now)?;
+    let backup_str = serde_json::to_string_pretty(&backup).map_err(|e| e.to_string())?;
+    tar.append("backup.json", backup_str.as_bytes(), now / 1000)
+        .map_err(|e| e.to_string())?;
+
+    // Tag name -> archive paths of its notes, for browsing without the app
+    let mut tag_stmt = conn
+        .prepare(
+            "SELECT t.name, t.color, nt.note_id FROM tags t \
+             LEFT JOIN note_tags nt ON nt.tag_id = t.id ORDER BY t.name, nt.note_id",
+        )
+        .map_err(|e| e.to_string())?;
+    let tag_rows = tag_stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut tags: Vec<serde_json::Value> = Vec::new();
+    for (name, color, note_id) in tag_rows {
+        if tags.last().and_then(|t| t["name"].as_str()) != Some(name.as_str()) {
+            tags.push(serde_json::json!({ "name": name, "color": color, "notes": [] }));
+        }
+        if let Some(entry) = note_id.and_then(|id| files.get(id.as_str())) {
+            if let Some(list) = tags.last_mut().and_then(|t| t["notes"].as_array_mut()) {
+                list.push(serde_json::json!(entry));
+            }
+        }
+    }
+    let tags_str = serde_json::to_string_pretty(&tags).map_err(|e| e.to_string())?;
+    tar.append("tags.json", tags_str.as_bytes(), now / 1000)
+        .map_err(|e| e.to_string())?;
+
+    tar.finish()
+        .and_then(|gz| gz.finish())
+        .map_err(|e| e.to_string())?;
+    Ok(ArchiveExport {
+        path,
+        note_count: notes.len(),
+    })
+}
+
+// A note packaged with every note that links to it: the note at the zip
+// root, linking notes under references/, and a bundle.json of the edges.
+#[tauri::command]
+fn export_note_bundle(db: State<Db>, id: String, path: String) -> Result<usize, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let notes = load_note_texts(&conn)?;
+    let (_, title, body) = notes
+        .iter()
+        .find(|(note_id, _, _)| *note_id == id)
+        .ok_or("note not found")?;
+    let referencing = backlink_ids(&notes, &id);
+    let now = chrono::Utc::now().timestamp();
+
+    let file = std::fs::File::create(&path).map_err(|e| e.to_string())?;
+    let mut zip = archive::ZipWriter::new(std::io::BufWriter::new(file));
+    let mut used = std::collections::HashSet::new();
+    let primary = format!("{}.md", safe_file_name(title));
+    used.insert(primary.to_lowercase());
+    zip.append(&primary, note_markdown(title, body).as_bytes(), now)
+        .map_err(|e| e.to_string())?;
+
+    let mut references = Vec::new();
+    let mut edges = Vec::new();
+    for (ref_id, ref_title, ref_body) in notes.iter().filter(|(n, _, _)| referencing.contains(n)) {
+        let entry = unique_file_path(&mut used, "references", &safe_file_name(ref_title), "md");
+        zip.append(&entry, note_markdown(ref_title, ref_body).as_bytes(), now)
+            .map_err(|e| e.to_string())?;
+        references.push(serde_json::json!({ "id": ref_id, "title": ref_title, "file": entry }));
+        edges.push(serde_json::json!({ "from": ref_id, "to": id }));
+    }
+    let manifest = serde_json::json!({
+        "note": { "id": id, "title": title, "file": primary },
+        "references": references,
+        "edges": edges
+    });
+    let manifest_str = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+    zip.append("bundle.json", manifest_str.as_bytes(), now)
+        .map_err(|e| e.to_string())?;
+    zip.finish()
+        .and_then(|mut out| std::io::Write::flush(&mut out))
+        .map_err(|e| e.to_string())?;
+    Ok(1 + references.len())
+}
 
-    for child_id in children {
-        delete_folder_recursive(conn, &child_id)?;
+// Writes a PNG preview card of the note (bold title, then as much of the
+// body as fits) and returns the path.
+#[tauri::command]
+fn render_note_thumbnail(
+    db: State<Db>,
+    id: String,
+    path: String,
+    width: u32,
+    height: u32,
+) -> Result<String, String> {
+    if !(16..=4096).contains(&width) || !(16..=4096).contains(&height) {
+        return Err("thumbnail size must be between 16 and 4096 pixels".to_string());
     }
-
-    conn.execute(
-        "DELETE FROM notes WHERE folder_id = ?1",
-        rusqlite::params![id],
-    )
-    .map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM folders WHERE id = ?1", rusqlite::params![id])
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let (title, body): (String, String) = conn
+        .query_row(
+            "SELECT title, body FROM notes WHERE id = ?1",
+            rusqlite::params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
         .map_err(|e| e.to_string())?;
-    Ok(())
+    let title = if title.trim().is_empty() {
+        "Untitled"
+    } else {
+        &title
+    };
+    let png = thumbnail::render(title, &body, width, height).map_err(|e| e.to_string())?;
+    std::fs::write(&path, png).map_err(|e| e.to_string())?;
+    Ok(path)
 }
 
-// ===== Note commands =====
-
+// Writes the note as a Word document (headings, lists, tables, code blocks
+// and inline emphasis) and returns the path.
 #[tauri::command]
-fn get_notes_metadata(db: State<Db>) -> Result<Vec<NoteMetadata>, String> {
+fn export_note_docx(db: State<Db>, id: String, path: String) -> Result<String, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
-    let mut stmt = conn
-        .prepare("SELECT id, folder_id, title, substr(body, 1, 200), created_at, updated_at, pinned, sort_order FROM notes")
-        .map_err(|e| e.to_string())?;
-    let notes = stmt
-        .query_map([], |row| {
-            Ok(NoteMetadata {
-                id: row.get(0)?,
-                folder_id: row.get(1)?,
-                title: row.get(2)?,
-                preview: row.get(3)?,
-                created_at: row.get(4)?,
-                updated_at: row.get(5)?,
-                pinned: row.get(6)?,
-                sort_order: row.get(7)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
+    let (title, body): (String, String) = conn
+        .query_row(
+            "SELECT title, body FROM notes WHERE id = ?1",
+            rusqlite::params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
         .map_err(|e| e.to_string())?;
-    Ok(notes)
+    let docx = docx::build(&title, &body).map_err(|e| e.to_string())?;
+    std::fs::write(&path, docx).map_err(|e| e.to_string())?;
+    Ok(path)
 }
 
+// Writes the note as a PDF and returns the path. With `cover`, the title,
+// author and export date get a page of their own and the body starts on
+// page two. Author and subject also go into the document metadata.
 #[tauri::command]
-fn get_note_body(db: State<Db>, id: String) -> Result<String, String> {
+fn export_note_pdf(
+    db: State<Db>,
+    id: String,
+    path: String,
+    cover: Option<bool>,
+    author: Option<String>,
+    subject: Option<String>,
+) -> Result<String, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
-    let body: String = conn
+    let (title, body): (String, String) = conn
         .query_row(
-            "SELECT body FROM notes WHERE id = ?1",
+            "SELECT title, body FROM notes WHERE id = ?1",
             rusqlite::params![id],
-            |row| row.get(0),
+            |row| Ok((row.get(0)?, row.get(1)?)),
         )
         .map_err(|e| e.to_string())?;
-    Ok(body)
+    let date = chrono::Local::now().format("%B %-d, %Y").to_string();
+    let options = pdf::Options {
+        cover: cover.unwrap_or(false),
+        author: author.as_deref(),
+        subject: subject.as_deref(),
+        date: &date,
+    };
+    let pdf = pdf::build(&title, &body, &options).map_err(|e| e.to_string())?;
+    std::fs::write(&path, pdf).map_err(|e| e.to_string())?;
+    Ok(path)
 }
 
+// Writes every tag with the ids of its notes as
+// `{ tags: [{ id, name, color, notes: [...] }] }`. Returns the tag count.
 #[tauri::command]
-fn get_notes_all(db: State<Db>) -> Result<Vec<Note>, String> {
+fn export_tags_json(db: State<Db>, path: String) -> Result<usize, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
     let mut stmt = conn
-        .prepare("SELECT id, folder_id, title, body, created_at, updated_at, pinned, sort_order FROM notes")
+        .prepare(
+            "SELECT t.id, t.name, t.color, nt.note_id FROM tags t \
+             LEFT JOIN note_tags nt ON nt.tag_id = t.id ORDER BY t.name, t.id, nt.note_id",
+        )
         .map_err(|e| e.to_string())?;
-    let notes = stmt
+    let rows = stmt
         .query_map([], |row| {
-            Ok(Note {
-                id: row.get(0)?,
-                folder_id: row.get(1)?,
-                title: row.get(2)?,
-                body: row.get(3)?,
-                created_at: row.get(4)?,
-                updated_at: row.get(5)?,
-                pinned: row.get(6)?,
-                sort_order: row.get(7)?,
-            })
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
         })
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
-    Ok(notes)
+    let mut tags: Vec<serde_json::Value> = Vec::new();
+    for (id, name, color, note_id) in rows {
+        if tags.last().and_then(|t| t["id"].as_str()) != Some(id.as_str()) {
+            tags.push(serde_json::json!({ "id": id, "name": name, "color": color, "notes": [] }));
+        }
+        if let Some(note_id) = note_id {
+            if let Some(list) = tags.last_mut().and_then(|t| t["notes"].as_array_mut()) {
+                list.push(serde_json::json!(note_id));
+            }
+        }
+    }
+    let count = tags.len();
+    let json_str = serde_json::to_string_pretty(&serde_json::json!({ "tags": tags }))
+        .map_err(|e| e.to_string())?;
+    std::fs::write(&path, json_str).map_err(|e| e.to_string())?;
+    Ok(count)
 }
 
+const ANOTEFILE_TYPE: &str = "single-note";
+const ANOTEFILE_VERSION: i64 = 1;
+
+// Writes one note, its tags and its attachments (base64-encoded) as a
+// self-contained `.anote` JSON document that import_anotefile can load into
+// any install. Attachments whose stored file is gone are left out. Returns
+// the path.
 #[tauri::command]
-fn search_notes(db: State<Db>, query: String) -> Result<Vec<NoteMetadata>, String> {
+fn export_note_anotefile(db: State<Db>, id: String, path: String) -> Result<String, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
-    // FTS5 MATCH query, joined back to notes for full metadata
-    let mut stmt = conn
-        .prepare(
-            "SELECT n.id, n.folder_id, n.title, substr(n.body, 1, 200), \
-             n.created_at, n.updated_at, n.pinned, n.sort_order \
-             FROM notes_fts f \
-             JOIN notes n ON n.rowid = f.rowid \
-             WHERE notes_fts MATCH ?1 \
-             ORDER BY rank \
-             LIMIT 80",
+    let store = attachments_dir()?;
+    write_anotefile(&conn, &id, &path, &store)?;
+    Ok(path)
+}
+
+fn write_anotefile(
+    conn: &Connection,
+    id: &str,
+    path: &str,
+    store: &std::path::Path,
+) -> Result<(), String> {
+    let note = conn
+        .query_row(
+            "SELECT title, body, created_at, updated_at, starred FROM notes WHERE id = ?1",
+            rusqlite::params![id],
+            |row| {
+                Ok(serde_json::json!({
+                    "title": row.get::<_, String>(0)?,
+                    "body": row.get::<_, String>(1)?,
+                    "created_at": row.get::<_, i64>(2)?,
+                    "updated_at": row.get::<_, i64>(3)?,
+                    "starred": row.get::<_, i32>(4)?,
+                }))
+            },
         )
         .map_err(|e| e.to_string())?;
-    let notes = stmt
-        .query_map(rusqlite::params![query], |row| {
-            Ok(NoteMetadata {
-                id: row.get(0)?,
-                folder_id: row.get(1)?,
-                title: row.get(2)?,
-                preview: row.get(3)?,
-                created_at: row.get(4)?,
-                updated_at: row.get(5)?,
-                pinned: row.get(6)?,
-                sort_order: row.get(7)?,
-            })
+    let tags: Vec<serde_json::Value> = note_tags(conn, id)?
+        .into_iter()
+        .map(|t| serde_json::json!({ "name": t.name, "color": t.color }))
+        .collect();
+    let stored = attachment_files(store);
+    let mut stmt = conn
+        .prepare("SELECT id, file_name FROM attachments WHERE note_id = ?1 ORDER BY created_at, id")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
         })
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
-    Ok(notes)
+    let mut attachments = Vec::new();
+    for (attachment_id, file_name) in rows {
+        let Some(file) = stored.get(&attachment_id) else {
+            continue;
+        };
+        let bytes = std::fs::read(file).map_err(|e| e.to_string())?;
+        attachments.push(serde_json::json!({
+            "id": attachment_id,
+            "file_name": file_name,
+            "extension": file.extension().map(|e| e.to_string_lossy()),
+            "data": base64::encode(&bytes),
+        }));
+    }
+    let file = serde_json::json!({
+        "type": ANOTEFILE_TYPE,
+        "version": ANOTEFILE_VERSION,
+        "exported_at": chrono::Utc::now().to_rfc3339(),
+        "note": note,
+        "tags": tags,
+        "attachments": attachments,
+    });
+    let json_str = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+    std::fs::write(path, json_str).map_err(|e| e.to_string())?;
+    Ok(())
 }
 
+// Zips a folder and its subfolders as Markdown, one directory per folder.
+// Attachments the notes reference are copied once into `attachments/` and
+// the references rewritten to relative paths; ids without a stored file are
+// reported in `missing_attachments` and left as they are.
 #[tauri::command]
-fn create_note(
+fn export_folder_markdown(
     db: State<Db>,
-    id: String,
     folder_id: String,
-    title: String,
-    body: String,
-    created_at: i64,
-    updated_at: i64,
-    pinned: i32,
-    sort_order: i32,
-) -> Result<(), String> {
+    path: String,
+) -> Result<FolderMarkdownExport, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
-    conn.execute(
-        "INSERT INTO notes (id, folder_id, title, body, created_at, updated_at, pinned, sort_order) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-        rusqlite::params![id, folder_id, title, body, created_at, updated_at, pinned, sort_order],
-    )
-    .map_err(|e| e.to_string())?;
-    Ok(())
+    let store = attachments_dir()?;
+    write_folder_markdown(&conn, &folder_id, path, &store)
 }
 
-#[tauri::command]
-fn update_note(
-    db: State<Db>,
-    id: String,
-    title: String,
-    body: String,
-    updated_at: i64,
-) -> Result<(), String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
-    conn.execute(
-        "UPDATE notes SET title = ?1, body = ?2, updated_at = ?3 WHERE id = ?4",
-        rusqlite::params![title, body, updated_at, id],
-    )
-    .map_err(|e| e.to_string())?;
-    Ok(())
-}
+fn write_folder_markdown(
+    conn: &Connection,
+    folder_id: &str,
+    path: String,
+    store: &std::path::Path,
+) -> Result<FolderMarkdownExport, String> {
+    let subtree = subtree_folder_ids(conn, folder_id)?;
+    if subtree.is_empty() {
+        return Err("folder not found".to_string());
+    }
+    let dirs = folder_dirs(conn)?;
+    // Paths inside the zip start at the exported folder's own directory
+    let root = dirs.get(folder_id).map(String::as_str).unwrap_or_default();
+    let prefix_len = root.rfind('/').map_or(0, |i| i + 1);
+    let stored = attachment_files(store);
+    let now = chrono::Utc::now().timestamp();
 
-#[tauri::command]
-fn delete_note(db: State<Db>, id: String) -> Result<(), String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM notes WHERE id = ?1", rusqlite::params![id])
+    let file = std::fs::File::create(&path).map_err(|e| e.to_string())?;
+    let mut zip = archive::ZipWriter::new(std::io::BufWriter::new(file));
+    let mut used = std::collections::HashSet::new();
+    // Attachment id -> zip path, or None when its file is missing
+    let mut bundled: std::collections::HashMap<String, Option<String>> =
+        std::collections::HashMap::new();
+    let mut missing = Vec::new();
+    let mut note_count = 0;
+    for id in &subtree {
+        let mut stmt = conn
+            .prepare("SELECT title, body FROM notes WHERE folder_id = ?1 ORDER BY pinned DESC, sort_order, id")
+            .map_err(|e| e.to_string())?;
+        let notes = stmt
+            .query_map(rusqlite::params![id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        let dir = dirs.get(id).map_or("", |d| &d[prefix_len.min(d.len())..]);
+        for (title, body) in notes {
+            let entry = unique_file_path(&mut used, dir, &safe_file_name(&title), "md");
+            let up = "../".repeat(entry.matches('/').count());
+            let mut failure = None;
+            let body = map_attachment_refs(&body, &mut |attachment| {
+                if !bundled.contains_key(attachment) {
+                    let target = match stored.get(attachment) {
+                        Some(file) => {
+                            let name = file.file_name().unwrap_or_default().to_string_lossy();
+                            let target = format!("attachments/{}", name);
+                            let appended = std::fs::read(file)
+                                .and_then(|bytes| zip.append(&target, &bytes, now));
+                            if let Err(e) = appended {
+                                failure = Some(e.to_string());
+                            }
+                            Some(target)
+                        }
+                        None => {
+                            missing.push(attachment.to_string());
+                            None
+                        }
+                    };
+                    bundled.insert(attachment.to_string(), target);
+                }
+                bundled[attachment]
+                    .as_ref()
+                    .map(|target| format!("{}{}", up, target))
+            });
+            if let Some(e) = failure {
+                return Err(e);
+            }
+            zip.append(&entry, note_markdown(&title, &body).as_bytes(), now)
+                .map_err(|e| e.to_string())?;
+            note_count += 1;
+        }
+    }
+    zip.finish()
+        .and_then(|mut out| std::io::Write::flush(&mut out))
         .map_err(|e| e.to_string())?;
-    Ok(())
+    Ok(FolderMarkdownExport {
+        path,
+        note_count,
+        attachment_count: bundled.values().filter(|t| t.is_some()).count(),
+        missing_attachments: missing,
+    })
 }
 
-// ===== Pin & reorder commands =====
-
+// Writes each note to `out_dir` as `<title>.md`. A name already used in
+// this batch or present on disk gets the note id appended instead. Every id
+// is checked before anything is written. Returns the written paths in order.
 #[tauri::command]
-fn toggle_note_pinned(db: State<Db>, id: String, pinned: i32) -> Result<(), String> {
+fn export_notes_markdown(
+    db: State<Db>,
+    ids: Vec<String>,
+    out_dir: String,
+) -> Result<Vec<String>, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
-    conn.execute(
-        "UPDATE notes SET pinned = ?1 WHERE id = ?2",
-        rusqlite::params![pinned, id],
-    )
-    .map_err(|e| e.to_string())?;
-    Ok(())
-}
-
-#[tauri::command]
-fn reorder_notes(db: State<Db>, updates: Vec<(String, i32)>) -> Result<(), String> {
-    if updates.is_empty() {
-        return Ok(());
+    let mut notes = Vec::new();
+    for id in &ids {
+        if !is_safe_id(id) {
+            return Err(format!("invalid note id: {}", id));
+        }
+        let note: (String, String) = conn
+            .query_row(
+                "SELECT title, body FROM notes WHERE id = ?1",
+                rusqlite::params![id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("note not found: {}", id))?;
+        notes.push((id, note));
     }
-    // IDs are app-generated alphanumeric (base36), validate to be safe
-    for (id, _) in &updates {
-        if !id.chars().all(|c| c.is_alphanumeric()) {
-            return Err("invalid note id".to_string());
+    let dir = std::path::Path::new(&out_dir);
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let mut used = std::collections::HashSet::new();
+    let mut paths = Vec::new();
+    for (id, (title, body)) in notes {
+        let stem = safe_file_name(&title);
+        let mut name = format!("{}.md", stem);
+        if dir.join(&name).exists() || !used.insert(name.to_lowercase()) {
+            name = format!("{} ({}).md", stem, id);
+            used.insert(name.to_lowercase());
         }
+        let path = dir.join(name);
+        std::fs::write(&path, note_markdown(&title, &body)).map_err(|e| e.to_string())?;
+        paths.push(path.to_string_lossy().into_owned());
     }
-    let case_clauses: Vec<String> = updates
-        .iter()
-        .map(|(id, order)| format!("WHEN '{}' THEN {}", id, order))
-        .collect();
-    let ids: Vec<String> = updates.iter().map(|(id, _)| format!("'{}'", id)).collect();
-    let sql = format!(
-        "UPDATE notes SET sort_order = CASE id {} END WHERE id IN ({})",
-        case_clauses.join(" "),
-        ids.join(",")
-    );
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
-    conn.execute(&sql, []).map_err(|e| e.to_string())?;
-    Ok(())
+    Ok(paths)
 }
 
-// ===== Data migration command =====
+// Markdown file content for a note: the title as an H1, then the body
+fn note_markdown(title: &str, body: &str) -> String {
+    if title.trim().is_empty() {
+        body.to_string()
+    } else {
+        format!("# {}\n\n{}", title.trim(), body)
+    }
+}
 
-#[tauri::command]
-fn import_data(db: State<Db>, folders: Vec<Folder>, notes: Vec<Note>) -> Result<(), String> {
-    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
-    let tx = conn.transaction().map_err(|e| e.to_string())?;
-    for folder in &folders {
-        tx.execute(
-            "INSERT OR IGNORE INTO folders (id, name, created_at, parent_id) VALUES (?1, ?2, ?3, ?4)",
-            rusqlite::params![folder.id, folder.name, folder.created_at, folder.parent_id],
-        )
+// Folder id -> relative directory path built from sanitized folder names
+fn folder_dirs(conn: &Connection) -> Result<std::collections::HashMap<String, String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, name, parent_id FROM folders")
         .map_err(|e| e.to_string())?;
-    }
-    for note in &notes {
-        tx.execute(
-            "INSERT OR IGNORE INTO notes (id, folder_id, title, body, created_at, updated_at, pinned, sort_order) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-            rusqlite::params![note.id, note.folder_id, note.title, note.body, note.created_at, note.updated_at, note.pinned, note.sort_order],
-        )
+    let rows: std::collections::HashMap<String, (String, Option<String>)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
         .map_err(|e| e.to_string())?;
+    let mut dirs = std::collections::HashMap::new();
+    for id in rows.keys() {
+        let mut parts = Vec::new();
+        let mut current = Some(id);
+        // Bounded walk so a corrupt parent cycle can't loop forever
+        while let Some((name, parent)) = current.and_then(|c| rows.get(c)) {
+            if parts.len() > rows.len() {
+                break;
+            }
+            parts.push(safe_file_name(name));
+            current = parent.as_ref();
+        }
+        parts.reverse();
+        dirs.insert(id.clone(), parts.join("/"));
     }
-    tx.commit().map_err(|e| e.to_string())?;
-    Ok(())
+    Ok(dirs)
+}
+
+// A title reduced to something every filesystem accepts as a file name
+fn safe_file_name(title: &str) -> String {
+    let cleaned: String = title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(80)
+        .collect();
+    let cleaned = cleaned.trim().trim_matches('.').trim();
+    if cleaned.is_empty() {
+        "Untitled".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+// `dir/stem.ext`, suffixed with " (n)" when the path is already taken
+// (compared case-insensitively for case-folding filesystems)
+fn unique_file_path(
+    used: &mut std::collections::HashSet<String>,
+    dir: &str,
+    stem: &str,
+    ext: &str,
+) -> String {
+    let mut candidate = format!("{}/{}.{}", dir, stem, ext);
+    let mut n = 2;
+    while !used.insert(candidate.to_lowercase()) {
+        candidate = format!("{}/{} ({}).{}", dir, stem, n, ext);
+        n += 1;
+    }
+    candidate
 }
 
 // ===== Backup command =====
@@ -444,10 +7227,96 @@ fn import_data(db: State<Db>, folders: Vec<Folder>, notes: Vec<Note>) -> Result<
 #[tauri::command]
 fn export_backup(db: State<Db>) -> Result<String, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let now = chrono::Local::now();
+    let backup = backup_json(&conn, now.timestamp_millis())?;
+
+    let json_str = serde_json::to_string_pretty(&backup).map_err(|e| e.to_string())?;
+
+    // Write to ~/.anote/backups/
+    let home = dirs::home_dir().ok_or("failed to get home directory")?;
+    let backups_dir = home.join(".anote").join("backups");
+    std::fs::create_dir_all(&backups_dir).map_err(|e| e.to_string())?;
+
+    let filename = format!("anote-backup-{}.json", now.format("%Y%m%d-%H%M%S"));
+    let file_path = backups_dir.join(&filename);
+    std::fs::write(&file_path, json_str).map_err(|e| e.to_string())?;
+
+    Ok(file_path.to_string_lossy().to_string())
+}
+
+const EXPORT_BOOKMARK_PREFIX: &str = "export_bookmark:";
+
+// Backup-format delta of notes updated since the last export under `label`.
+// Folders, tags and note placements are always written whole, so moves,
+// renames and deletions carry over too (see the `since` branch below).
+#[tauri::command]
+fn export_incremental(
+    db: State<Db>,
+    label: String,
+    path: String,
+) -> Result<IncrementalResult, String> {
+    let label = label.trim();
+    if label.is_empty() {
+        return Err("label cannot be empty".to_string());
+    }
+    let conn = db.write()?;
+    let key = format!("{}{}", EXPORT_BOOKMARK_PREFIX, label);
+    let since = get_setting(&conn, &key)?.and_then(|v| v.parse::<i64>().ok());
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut backup = backup_json(&conn, now)?;
+
+    if let Some(since) = since {
+        // Only unchanged note records are left out. Moves, pins, tagging,
+        // folder renames and deletions don't bump updated_at, so everything
+        // else is written in full and note_positions places every note that
+        // still exists. A note or folder absent from the delta was deleted.
+        let notes = match backup["notes"].take() {
+            serde_json::Value::Array(items) => items,
+            _ => Vec::new(),
+        };
+        let positions: Vec<serde_json::Value> = notes
+            .iter()
+            .map(|n| {
+                serde_json::json!({
+                    "id": n["id"],
+                    "folder_id": n["folder_id"],
+                    "pinned": n["pinned"],
+                    "sort_order": n["sort_order"],
+                    "starred": n["starred"]
+                })
+            })
+            .collect();
+        let changed: Vec<serde_json::Value> = notes
+            .into_iter()
+            .filter(|n| n["updated_at"].as_i64().unwrap_or(0) > since)
+            .collect();
+        backup["notes"] = serde_json::json!(changed);
+        backup["note_positions"] = serde_json::json!(positions);
+        backup["since"] = serde_json::json!(since);
+    }
+
+    let note_count = backup["notes"].as_array().map_or(0, |a| a.len());
+    let folder_count = backup["folders"].as_array().map_or(0, |a| a.len());
+    let json_str = serde_json::to_string_pretty(&backup).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json_str).map_err(|e| e.to_string())?;
+    // Only advance the bookmark once the file is on disk
+    set_setting(&conn, &key, &now.to_string())?;
+    Ok(IncrementalResult {
+        path,
+        note_count,
+        folder_count,
+        since,
+        exported_at: now,
+    })
+}
 
+// Full structured dump in the backup format read back by import_data
+fn backup_json(conn: &Connection, exported_at: i64) -> Result<serde_json::Value, String> {
     // Query all folders
     let mut folder_stmt = conn
-        .prepare("SELECT id, name, created_at, parent_id FROM folders ORDER BY created_at")
+        .prepare(
+            "SELECT id, name, created_at, parent_id, sort_order, sort_mode FROM folders ORDER BY created_at",
+        )
         .map_err(|e| e.to_string())?;
     let folders: Vec<serde_json::Value> = folder_stmt
         .query_map([], |row| {
@@ -455,7 +7324,9 @@ fn export_backup(db: State<Db>) -> Result<String, String> {
                 "id": row.get::<_, String>(0)?,
                 "name": row.get::<_, String>(1)?,
                 "created_at": row.get::<_, i64>(2)?,
-                "parent_id": row.get::<_, Option<String>>(3)?
+                "parent_id": row.get::<_, Option<String>>(3)?,
+                "sort_order": row.get::<_, i32>(4)?,
+                "sort_mode": row.get::<_, String>(5)?
             }))
         })
         .map_err(|e| e.to_string())?
@@ -464,7 +7335,7 @@ fn export_backup(db: State<Db>) -> Result<String, String> {
 
     // Query all notes (full body)
     let mut note_stmt = conn
-        .prepare("SELECT id, folder_id, title, body, created_at, updated_at, pinned, sort_order FROM notes")
+        .prepare("SELECT id, folder_id, title, body, created_at, updated_at, pinned, sort_order, starred FROM notes")
         .map_err(|e| e.to_string())?;
     let notes: Vec<serde_json::Value> = note_stmt
         .query_map([], |row| {
@@ -476,33 +7347,61 @@ fn export_backup(db: State<Db>) -> Result<String, String> {
                 "created_at": row.get::<_, i64>(4)?,
                 "updated_at": row.get::<_, i64>(5)?,
                 "pinned": row.get::<_, i32>(6)?,
-                "sort_order": row.get::<_, i32>(7)?
+                "sort_order": row.get::<_, i32>(7)?,
+                "starred": row.get::<_, i32>(8)?
             }))
         })
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
 
-    let now = chrono::Local::now();
-    let backup = serde_json::json!({
-        "version": "1.0",
-        "exportedAt": now.timestamp_millis(),
-        "folders": folders,
-        "notes": notes
-    });
-
-    let json_str = serde_json::to_string_pretty(&backup).map_err(|e| e.to_string())?;
+    let mut tag_stmt = conn
+        .prepare("SELECT id, name, color, created_at FROM tags ORDER BY name")
+        .map_err(|e| e.to_string())?;
+    let tags = tag_stmt
+        .query_map([], tag_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
 
-    // Write to ~/.anote/backups/
-    let home = dirs::home_dir().ok_or("failed to get home directory")?;
-    let backups_dir = home.join(".anote").join("backups");
-    std::fs::create_dir_all(&backups_dir).map_err(|e| e.to_string())?;
+    let mut note_tag_stmt = conn
+        .prepare("SELECT note_id, tag_id FROM note_tags")
+        .map_err(|e| e.to_string())?;
+    let note_tags = note_tag_stmt
+        .query_map([], |row| {
+            Ok(NoteTag {
+                note_id: row.get(0)?,
+                tag_id: row.get(1)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
 
-    let filename = format!("anote-backup-{}.json", now.format("%Y%m%d-%H%M%S"));
-    let file_path = backups_dir.join(&filename);
-    std::fs::write(&file_path, json_str).map_err(|e| e.to_string())?;
+    let mut meta_stmt = conn
+        .prepare("SELECT note_id, key, value FROM note_meta ORDER BY note_id, key")
+        .map_err(|e| e.to_string())?;
+    let note_meta = meta_stmt
+        .query_map([], |row| {
+            Ok(NoteMeta {
+                note_id: row.get(0)?,
+                key: row.get(1)?,
+                value: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
 
-    Ok(file_path.to_string_lossy().to_string())
+    Ok(serde_json::json!({
+        "version": "1.0",
+        "exportedAt": exported_at,
+        "folders": folders,
+        "notes": notes,
+        "tags": tags,
+        "note_tags": note_tags,
+        "note_meta": note_meta
+    }))
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -513,22 +7412,17 @@ pub fn run() {
             let home = dirs::home_dir().expect("failed to get home directory");
             let anote_dir = home.join(".anote");
             std::fs::create_dir_all(&anote_dir).expect("failed to create ~/.anote/");
-            let db_path = anote_dir.join("anote.db");
-
-            // Migrate from old Tauri app data path if needed
-            if !db_path.exists() {
-                if let Ok(app_data_dir) = app.path().app_data_dir() {
-                    let old_db = app_data_dir.join("anote.db");
-                    if old_db.exists() {
-                        let _ = std::fs::copy(&old_db, &db_path);
-                    }
-                }
-            }
+            let legacy_db = app
+                .path()
+                .app_data_dir()
+                .ok()
+                .map(|dir| dir.join("anote.db"));
+            let db_path = startup_db_path(&anote_dir, &config_path()?, legacy_db.as_deref())?;
 
-            let conn = Connection::open(&db_path).expect("failed to open database");
-            init_db(&conn);
+            let conn = open_initialized_db(&db_path)?;
 
-            app.manage(Db(Mutex::new(conn)));
+            app.manage(Db(Mutex::new(conn), AtomicBool::new(false)));
+            app.manage(MoveUndo::default());
 
             if cfg!(debug_assertions) {
                 app.handle().plugin(
@@ -542,19 +7436,139 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             get_folders,
             create_folder,
+            ensure_folder_path,
             rename_folder,
             delete_folder,
+            get_subtree_folder_ids,
+            clone_folder,
+            normalize_folder_order,
+            move_folder_to_position,
+            dissolve_folder,
+            set_folder_sort_mode,
+            recompute_folder_counts,
+            get_folder_counts,
             get_notes_metadata,
+            get_stale_notes,
+            get_notes_grouped,
+            get_notes_feed,
+            get_note_hashes,
+            rehash_notes,
+            get_notes_in_folders,
+            get_notes_recursive,
+            get_notes_in_local_range,
+            get_notes_histogram,
+            get_notes_index,
             get_note_body,
+            get_note_excerpt,
             get_notes_all,
             search_notes,
+            #[cfg(debug_assertions)]
+            explain_search,
+            search_notes_streamed,
+            search_notes_grouped,
+            search_titles,
+            fuzzy_find_notes,
+            search_notes_advanced,
+            get_related_notes,
+            refine_search,
             create_note,
+            create_note_from_markdown,
+            get_default_note_template,
+            set_default_note_template,
+            get_or_create_daily_note,
             update_note,
+            set_note_timestamps,
+            rename_note,
             delete_note,
+            split_note_by_heading,
+            generate_toc_note,
+            clean_note_bodies,
+            lint_note_markdown,
+            find_empty_items,
+            delete_empty_notes,
+            find_broken_wiki_links,
+            get_note_graph,
+            find_link_cycles,
+            find_title_collisions,
+            snapshot_note,
+            get_note_versions,
+            restore_note_version,
+            export_note_history,
+            import_note_history,
+            prune_version_history,
             toggle_note_pinned,
+            set_notes_pinned,
+            set_note_starred,
+            get_starred_notes,
             reorder_notes,
+            move_note_up,
+            move_note_down,
+            move_note_to_top,
+            move_note_to_bottom,
+            move_note_after,
+            move_notes_with_undo,
+            undo_move,
+            move_search_results,
+            get_note_tags,
+            get_recent_tags,
+            add_note_tag,
+            remove_note_tag,
+            set_note_tags,
+            bulk_rename_tags,
+            get_tag_report,
+            extract_hashtags_to_tags,
+            folderize_to_tags,
+            get_untagged_notes,
+            get_inbox_folder,
+            set_inbox_folder,
+            set_note_meta,
+            get_note_meta,
+            delete_note_meta,
+            find_notes_by_meta,
+            ingest_note_attachments,
+            get_notes_with_attachments,
+            find_broken_attachments,
+            rename_inbox,
+            get_inbox_notes,
+            add_shortcut,
+            remove_shortcut,
+            reorder_shortcuts,
+            get_shortcuts,
             import_data,
+            import_data_unordered,
+            import_data_report,
+            merge_database,
+            import_joplin,
+            import_anotefile,
+            import_split_text,
             export_backup,
+            export_incremental,
+            estimate_folder_export,
+            get_folder_word_count,
+            export_search_markdown,
+            export_site,
+            export_archive,
+            export_note_bundle,
+            export_folder_markdown,
+            render_note_thumbnail,
+            export_note_docx,
+            export_note_pdf,
+            export_notes_markdown,
+            export_tags_json,
+            export_note_anotefile,
+            get_schema_version,
+            get_db_diagnostics,
+            get_fts_stats,
+            reindex_note,
+            get_tree_stats,
+            get_largest_notes,
+            check_integrity,
+            verify_and_repair_schema,
+            repair_note_tags,
+            set_journal_mode,
+            relocate_db,
+            begin_maintenance,
+            end_maintenance,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
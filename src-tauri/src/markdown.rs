@@ -0,0 +1,906 @@
+// Line-level Markdown scanning. Not a full CommonMark parser — just enough
+// structure (headings, fenced code) for note-splitting and similar commands.
+
+// Tracks fenced code blocks (``` or ~~~) across consecutive lines.
+#[derive(Default)]
+pub struct Fences {
+    open: Option<(char, usize)>,
+}
+
+impl Fences {
+    pub fn is_open(&self) -> bool {
+        self.open.is_some()
+    }
+
+    // Feed lines in order; returns true when the line is part of a fenced block,
+    // including the opening and closing delimiter lines.
+    pub fn in_code(&mut self, line: &str) -> bool {
+        let trimmed = strip_indent(line);
+        match self.open {
+            Some((ch, len)) => {
+                let run = trimmed.chars().take_while(|&c| c == ch).count();
+                if run >= len && trimmed[run * ch.len_utf8()..].trim().is_empty() {
+                    self.open = None;
+                }
+                true
+            }
+            None => {
+                let ch = match trimmed.chars().next() {
+                    Some(c @ ('`' | '~')) => c,
+                    _ => return false,
+                };
+                let run = trimmed.chars().take_while(|&c| c == ch).count();
+                // Backtick fences can't have backticks in the info string
+                if run < 3 || (ch == '`' && trimmed[run..].contains('`')) {
+                    return false;
+                }
+                self.open = Some((ch, run));
+                true
+            }
+        }
+    }
+}
+
+// Up to three leading spaces are allowed before block markers.
+fn strip_indent(line: &str) -> &str {
+    let spaces = line.chars().take(4).take_while(|&c| c == ' ').count();
+    if spaces > 3 {
+        line
+    } else {
+        &line[spaces..]
+    }
+}
+
+// Parses an ATX heading (`## Title ##`) into (level, text).
+pub fn atx_heading(line: &str) -> Option<(u8, &str)> {
+    if line.starts_with("    ") {
+        return None;
+    }
+    let trimmed = strip_indent(line);
+    let hashes = trimmed.chars().take_while(|&c| c == '#').count();
+    if hashes == 0 || hashes > 6 {
+        return None;
+    }
+    let rest = &trimmed[hashes..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let mut text = rest.trim();
+    // Optional closing sequence must be preceded by a space
+    let without_closing = text.trim_end_matches('#');
+    if without_closing.is_empty() {
+        text = "";
+    } else if without_closing.ends_with([' ', '\t']) {
+        text = without_closing.trim_end();
+    }
+    Some((hashes as u8, text))
+}
+
+// A heading found outside fenced code, with its 0-based line index.
+pub struct Heading<'a> {
+    pub line: usize,
+    pub level: u8,
+    pub text: &'a str,
+}
+
+pub fn headings(body: &str) -> Vec<Heading<'_>> {
+    let mut fences = Fences::default();
+    let mut out = Vec::new();
+    for (i, line) in body.lines().enumerate() {
+        if fences.in_code(line) {
+            continue;
+        }
+        if let Some((level, text)) = atx_heading(line) {
+            out.push(Heading {
+                line: i,
+                level,
+                text,
+            });
+        }
+    }
+    out
+}
+
+// GitHub-style heading anchor: lowercased, punctuation dropped, spaces as
+// hyphens. Slugger adds -1, -2... to repeats.
+pub fn slug(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+// Hands out the anchors of one document's headings, in order: the slug of
+// the heading's inline text, with -1, -2... added to repeats.
+#[derive(Default)]
+pub struct Slugger {
+    seen: std::collections::HashMap<String, usize>,
+}
+
+impl Slugger {
+    pub fn anchor(&mut self, heading: &str) -> String {
+        let text: String = spans(heading).into_iter().map(|span| span.text).collect();
+        let base = slug(&text);
+        let repeats = self.seen.entry(base.clone()).or_insert(0);
+        let anchor = match *repeats {
+            0 => base,
+            n => format!("{}-{}", base, n),
+        };
+        *repeats += 1;
+        anchor
+    }
+}
+
+// Splits a body into (heading text, section content) at every heading of
+// exactly `level`. Content before the first such heading is returned as a
+// section with no title when it isn't blank.
+pub fn split_sections(body: &str, level: u8) -> Vec<(Option<String>, String)> {
+    let lines: Vec<&str> = body.lines().collect();
+    let starts: Vec<(usize, &str)> = headings(body)
+        .into_iter()
+        .filter(|h| h.level == level)
+        .map(|h| (h.line, h.text))
+        .collect();
+    let mut sections = Vec::new();
+    let first = starts.first().map_or(lines.len(), |(line, _)| *line);
+    let preamble = trim_blank_lines(&lines[..first]);
+    if !preamble.is_empty() {
+        sections.push((None, preamble));
+    }
+    for (i, (line, text)) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).map_or(lines.len(), |(next, _)| *next);
+        let content = trim_blank_lines(&lines[line + 1..end]);
+        sections.push((Some(text.to_string()), content));
+    }
+    sections
+}
+
+fn trim_blank_lines(lines: &[&str]) -> String {
+    let start = lines.iter().position(|l| !l.trim().is_empty());
+    let end = lines.iter().rposition(|l| !l.trim().is_empty());
+    match (start, end) {
+        (Some(s), Some(e)) => lines[s..=e].join("\n"),
+        _ => String::new(),
+    }
+}
+
+// Removes inline code spans so their contents aren't scanned for syntax.
+fn strip_code_spans(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find('`') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start..];
+        let run = after.chars().take_while(|&c| c == '`').count();
+        let fence = &after[..run];
+        match after[run..].find(fence) {
+            Some(end) => rest = &after[run + end + run..],
+            None => {
+                // Unmatched backticks are literal text
+                out.push_str(fence);
+                rest = &after[run..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// Collects `[[target]]` / `[[target|label]]` link targets outside code.
+pub fn wiki_links(body: &str) -> Vec<String> {
+    let mut fences = Fences::default();
+    let mut out = Vec::new();
+    for line in body.lines() {
+        if fences.in_code(line) {
+            continue;
+        }
+        let line = strip_code_spans(line);
+        let mut rest = line.as_str();
+        while let Some(start) = rest.find("[[") {
+            let after = &rest[start + 2..];
+            let Some(end) = after.find("]]") else { break };
+            let inner = &after[..end];
+            let target = inner.split('|').next().unwrap_or("").trim();
+            if !target.is_empty() && !target.contains('[') {
+                out.push(target.to_string());
+            }
+            rest = &after[end + 2..];
+        }
+    }
+    out
+}
+
+// Finds inline `#hashtags` outside code and returns them (deduplicated,
+// case-insensitively, in first-seen order) with the body they were removed from.
+// A tag starts after whitespace or `(` and must contain a letter, so headings,
+// `C#`, `#123` and URL fragments are left alone.
+pub fn extract_hashtags(body: &str) -> (Vec<String>, String) {
+    let mut fences = Fences::default();
+    let mut tags: Vec<String> = Vec::new();
+    let mut lines = Vec::new();
+    for line in body.split('\n') {
+        if fences.in_code(line) {
+            lines.push(line.to_string());
+            continue;
+        }
+        let mut out = String::with_capacity(line.len());
+        let mut removed = false;
+        // The source character before `rest`, so removals don't create tags
+        let mut prev: Option<char> = None;
+        let mut rest = line;
+        while let Some(c) = rest.chars().next() {
+            if c == '`' {
+                // Code spans are copied through untouched
+                let run = rest.chars().take_while(|&c| c == '`').count();
+                let end = rest[run..]
+                    .find(&rest[..run])
+                    .map_or(run, |e| run + e + run);
+                out.push_str(&rest[..end]);
+                prev = rest[..end].chars().last();
+                rest = &rest[end..];
+                continue;
+            }
+            let boundary = match prev {
+                Some(p) => p.is_whitespace() || p == '(',
+                None => true,
+            };
+            if c == '#' && boundary {
+                let len = hashtag_len(&rest[1..]);
+                if len > 0 {
+                    let tag = &rest[1..1 + len];
+                    if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                        tags.push(tag.to_string());
+                    }
+                    rest = &rest[1 + len..];
+                    prev = tag.chars().last();
+                    // Drop one adjoining space, unless the text that follows
+                    // would then run into the word before
+                    if !runs_on(rest) && out.ends_with(' ') {
+                        out.pop();
+                    }
+                    // A `(#tag)` left empty goes with its parens
+                    if out.ends_with('(') && rest.starts_with(')') {
+                        out.pop();
+                        rest = &rest[1..];
+                        prev = Some(')');
+                        if !runs_on(rest) && out.ends_with(' ') {
+                            out.pop();
+                        }
+                    }
+                    if out.is_empty() {
+                        rest = rest.strip_prefix(' ').unwrap_or(rest);
+                    }
+                    removed = true;
+                    continue;
+                }
+            }
+            out.push(c);
+            prev = Some(c);
+            rest = &rest[c.len_utf8()..];
+        }
+        lines.push(if removed {
+            out.trim_end().to_string()
+        } else {
+            out
+        });
+    }
+    (tags, lines.join("\n"))
+}
+
+// Whether `rest` starts with text that would join the word before it if the
+// space between them were dropped
+fn runs_on(rest: &str) -> bool {
+    rest.starts_with(|c: char| {
+        !c.is_whitespace() && !matches!(c, '.' | ',' | ';' | ':' | '!' | '?' | ')')
+    })
+}
+
+// Byte length of a tag name at the start of `s`: letters, digits, `_`, `-`
+// and `/`, with at least one letter and no trailing `-` or `/`.
+fn hashtag_len(s: &str) -> usize {
+    let end = s
+        .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '/')))
+        .unwrap_or(s.len());
+    let name = s[..end].trim_end_matches(['-', '/']);
+    if name.chars().any(char::is_alphabetic) {
+        name.len()
+    } else {
+        0
+    }
+}
+
+// Trims trailing whitespace from each line and collapses runs of 3+ blank
+// lines down to 2. Fenced code blocks are left byte-for-byte untouched.
+pub fn clean_whitespace(body: &str) -> String {
+    let mut fences = Fences::default();
+    let mut out: Vec<&str> = Vec::new();
+    let mut blank_run = 0;
+    for line in body.split('\n') {
+        if fences.in_code(line) {
+            blank_run = 0;
+            out.push(line);
+            continue;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank_run += 1;
+            if blank_run > 2 {
+                continue;
+            }
+        } else {
+            blank_run = 0;
+        }
+        out.push(line);
+    }
+    out.join("\n")
+}
+
+// Block structure of a note, as needed by the document exporters.
+pub enum Block {
+    Heading {
+        level: u8,
+        text: String,
+    },
+    Paragraph(String),
+    ListItem {
+        depth: usize,
+        ordered: bool,
+        text: String,
+    },
+    Code(Vec<String>),
+    // First row is the header
+    Table(Vec<Vec<String>>),
+    Quote(String),
+    Rule,
+}
+
+// Splits a body into blocks. Consecutive text lines join into one paragraph;
+// list nesting depth follows indentation relative to enclosing items.
+pub fn blocks(body: &str) -> Vec<Block> {
+    let lines: Vec<&str> = body.lines().collect();
+    let mut out = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut quote: Vec<&str> = Vec::new();
+    let mut indents: Vec<usize> = Vec::new();
+    let mut fences = Fences::default();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let is_quote = strip_indent(line).starts_with('>');
+        if !paragraph.is_empty() && (line.trim().is_empty() || is_quote) {
+            out.push(Block::Paragraph(paragraph.join(" ")));
+            paragraph.clear();
+        }
+        if !quote.is_empty() && !is_quote {
+            out.push(Block::Quote(quote.join(" ")));
+            quote.clear();
+        }
+        if fences.in_code(line) {
+            flush_text(&mut out, &mut paragraph);
+            indents.clear();
+            let mut code = Vec::new();
+            i += 1;
+            while i < lines.len() && fences.is_open() {
+                fences.in_code(lines[i]);
+                if fences.is_open() {
+                    code.push(lines[i].to_string());
+                }
+                i += 1;
+            }
+            out.push(Block::Code(code));
+            continue;
+        }
+        if line.trim().is_empty() {
+            i += 1;
+            continue;
+        }
+        if is_quote {
+            let text = strip_indent(line)[1..].trim();
+            if !text.is_empty() {
+                quote.push(text);
+            }
+            i += 1;
+            continue;
+        }
+        if let Some((level, text)) = atx_heading(line) {
+            flush_text(&mut out, &mut paragraph);
+            indents.clear();
+            out.push(Block::Heading {
+                level,
+                text: text.to_string(),
+            });
+            i += 1;
+            continue;
+        }
+        if is_rule(line) {
+            flush_text(&mut out, &mut paragraph);
+            indents.clear();
+            out.push(Block::Rule);
+            i += 1;
+            continue;
+        }
+        if let Some((indent, ordered, text)) = list_item(line) {
+            flush_text(&mut out, &mut paragraph);
+            while indents.last().is_some_and(|&top| top > indent) {
+                indents.pop();
+            }
+            if indents.last() != Some(&indent) {
+                indents.push(indent);
+            }
+            out.push(Block::ListItem {
+                depth: indents.len() - 1,
+                ordered,
+                text: text.to_string(),
+            });
+            i += 1;
+            continue;
+        }
+        if paragraph.is_empty()
+            && line.contains('|')
+            && lines.get(i + 1).is_some_and(|l| is_table_separator(l))
+        {
+            indents.clear();
+            let mut rows = vec![table_cells(line)];
+            i += 2;
+            while i < lines.len() && lines[i].contains('|') && !lines[i].trim().is_empty() {
+                rows.push(table_cells(lines[i]));
+                i += 1;
+            }
+            out.push(Block::Table(rows));
+            continue;
+        }
+        // A plain line right after a list item continues that item
+        if paragraph.is_empty() && line.starts_with([' ', '\t']) {
+            if let Some(Block::ListItem { text, .. }) = out.last_mut() {
+                text.push(' ');
+                text.push_str(line.trim());
+                i += 1;
+                continue;
+            }
+        }
+        indents.clear();
+        paragraph.push(line.trim());
+        i += 1;
+    }
+    flush_text(&mut out, &mut paragraph);
+    if !quote.is_empty() {
+        out.push(Block::Quote(quote.join(" ")));
+    }
+    out
+}
+
+fn flush_text(out: &mut Vec<Block>, paragraph: &mut Vec<&str>) {
+    if !paragraph.is_empty() {
+        out.push(Block::Paragraph(paragraph.join(" ")));
+        paragraph.clear();
+    }
+}
+
+// `- item`, `* item`, `+ item`, `1. item` or `1) item` -> (indent, ordered, text)
+pub fn list_item(line: &str) -> Option<(usize, bool, &str)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    let rest = &line[indent..];
+    let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+    let (ordered, marker_len) = if rest.starts_with(['-', '*', '+']) {
+        (false, 1)
+    } else if (1..=9).contains(&digits) && rest[digits..].starts_with(['.', ')']) {
+        (true, digits + 1)
+    } else {
+        return None;
+    };
+    let text = &rest[marker_len..];
+    if text.is_empty() {
+        return Some((indent, ordered, ""));
+    }
+    if !text.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some((indent, ordered, text.trim()))
+}
+
+// `---`, `***` or `___` (three or more, spaces allowed)
+fn is_rule(line: &str) -> bool {
+    let trimmed = strip_indent(line).trim_end();
+    let Some(ch) = trimmed.chars().next() else {
+        return false;
+    };
+    matches!(ch, '-' | '*' | '_')
+        && trimmed.chars().filter(|&c| c == ch).count() >= 3
+        && trimmed.chars().all(|c| c == ch || c == ' ')
+}
+
+fn is_table_separator(line: &str) -> bool {
+    let cells = table_cells(line);
+    line.contains('-')
+        && cells.iter().all(|cell| {
+            let cell = cell.trim_start_matches(':').trim_end_matches(':');
+            !cell.is_empty() && cell.chars().all(|c| c == '-')
+        })
+}
+
+fn table_cells(line: &str) -> Vec<String> {
+    let trimmed = line.trim();
+    let trimmed = trimmed.strip_prefix('|').unwrap_or(trimmed);
+    let trimmed = trimmed.strip_suffix('|').unwrap_or(trimmed);
+    trimmed.split('|').map(|c| c.trim().to_string()).collect()
+}
+
+// A run of inline text with uniform formatting
+pub struct Span {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub code: bool,
+    pub link: Option<String>,
+}
+
+// Parses emphasis (`**`, `__`, `*`, `_`), code spans, links, images (as their
+// alt text) and wiki-links (as their label) into formatted spans.
+pub fn spans(text: &str) -> Vec<Span> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = Vec::new();
+    let mut buf = String::new();
+    let (mut bold, mut italic) = (false, false);
+    let mut link: Option<String> = None;
+    let mut link_end: Option<usize> = None;
+    let mut i = 0;
+    macro_rules! flush {
+        () => {
+            if !buf.is_empty() {
+                out.push(Span {
+                    text: std::mem::take(&mut buf),
+                    bold,
+                    italic,
+                    code: false,
+                    link: link.clone(),
+                });
+            }
+        };
+    }
+    while i < chars.len() {
+        if link_end == Some(i) {
+            flush!();
+            link = None;
+            link_end = None;
+            i = skip_destination(&chars, i);
+            continue;
+        }
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            '\\' if next.is_some_and(|n| n.is_ascii_punctuation()) => {
+                buf.push(next.unwrap_or_default());
+                i += 2;
+            }
+            '`' => {
+                let run = chars[i..].iter().take_while(|&&c| c == '`').count();
+                match find_run(&chars, i + run, '`', run) {
+                    Some(end) => {
+                        flush!();
+                        let code: String = chars[i + run..end].iter().collect();
+                        out.push(Span {
+                            text: code.trim().to_string(),
+                            bold,
+                            italic,
+                            code: true,
+                            link: link.clone(),
+                        });
+                        i = end + run;
+                    }
+                    None => {
+                        buf.extend(&chars[i..i + run]);
+                        i += run;
+                    }
+                }
+            }
+            '*' | '_' => {
+                let double = next == Some(c);
+                let len = if double { 2 } else { 1 };
+                let prev = i.checked_sub(1).map(|j| chars[j]);
+                let after = chars.get(i + len).copied();
+                let active = if double { bold } else { italic };
+                // Underscores inside words (snake_case) are literal
+                let intraword = c == '_'
+                    && prev.is_some_and(|p| p.is_alphanumeric())
+                    && after.is_some_and(|a| a.is_alphanumeric());
+                let closing = active && prev.is_some_and(|p| !p.is_whitespace());
+                let opening = !active && after.is_some_and(|a| !a.is_whitespace());
+                if !intraword && (closing || opening) {
+                    flush!();
+                    if double {
+                        bold = !bold;
+                    } else {
+                        italic = !italic;
+                    }
+                } else {
+                    buf.extend(&chars[i..i + len]);
+                }
+                i += len;
+            }
+            '[' if next == Some('[') => {
+                let inner_end = (i + 2..chars.len().saturating_sub(1))
+                    .find(|&j| chars[j] == ']' && chars[j + 1] == ']');
+                match inner_end {
+                    Some(end) => {
+                        let inner: String = chars[i + 2..end].iter().collect();
+                        let (target, label) = match inner.split_once('|') {
+                            Some((t, l)) => (t.trim(), l.trim()),
+                            None => (inner.trim(), inner.trim()),
+                        };
+                        flush!();
+                        out.push(Span {
+                            text: label.to_string(),
+                            bold,
+                            italic,
+                            code: false,
+                            link: Some(format!("[[{}]]", target)),
+                        });
+                        i = end + 2;
+                    }
+                    None => {
+                        buf.push_str("[[");
+                        i += 2;
+                    }
+                }
+            }
+            '!' | '[' if link.is_none() => {
+                let start = if c == '!' { i + 1 } else { i };
+                match link_parts(&chars, start) {
+                    Some((close, _)) if c == '!' => {
+                        // Images keep only their alt text
+                        buf.extend(&chars[start + 1..close]);
+                        i = skip_destination(&chars, close);
+                    }
+                    Some((close, dest)) => {
+                        flush!();
+                        link = Some(dest);
+                        link_end = Some(close);
+                        i = start + 1;
+                    }
+                    None => {
+                        buf.push(c);
+                        i += 1;
+                    }
+                }
+            }
+            _ => {
+                buf.push(c);
+                i += 1;
+            }
+        }
+    }
+    flush!();
+    out
+}
+
+// Index of the next run of exactly `len` `ch` characters at or after `from`
+fn find_run(chars: &[char], from: usize, ch: char, len: usize) -> Option<usize> {
+    let mut j = from;
+    while j < chars.len() {
+        if chars[j] == ch {
+            let run = chars[j..].iter().take_while(|&&c| c == ch).count();
+            if run == len {
+                return Some(j);
+            }
+            j += run;
+        } else {
+            j += 1;
+        }
+    }
+    None
+}
+
+// For `[text](dest)` starting at `open`, returns the index of `]` and the destination
+fn link_parts(chars: &[char], open: usize) -> Option<(usize, String)> {
+    if chars.get(open) != Some(&'[') {
+        return None;
+    }
+    let close = (open + 1..chars.len()).find(|&j| chars[j] == ']')?;
+    if chars.get(close + 1) != Some(&'(') {
+        return None;
+    }
+    let end = (close + 2..chars.len()).find(|&j| chars[j] == ')')?;
+    let dest: String = chars[close + 2..end].iter().collect();
+    // Drop an optional "title" after the URL
+    let dest = dest.split_whitespace().next().unwrap_or("").to_string();
+    Some((close, dest))
+}
+
+// Given the index of a link's `]`, returns the index just past its `(...)`
+fn skip_destination(chars: &[char], close: usize) -> usize {
+    (close + 2..chars.len())
+        .find(|&j| chars[j] == ')')
+        .map_or(chars.len(), |j| j + 1)
+}
+
+// Readable text of a body for previews: block and inline syntax removed,
+// code blocks and link destinations dropped, whitespace collapsed.
+pub fn plain_text(body: &str) -> String {
+    let mut words: Vec<String> = Vec::new();
+    let mut push = |text: &str| {
+        let plain: String = spans(text).into_iter().map(|span| span.text).collect();
+        words.extend(plain.split_whitespace().map(str::to_string));
+    };
+    for block in blocks(body) {
+        match block {
+            Block::Heading { text, .. }
+            | Block::Paragraph(text)
+            | Block::Quote(text)
+            | Block::ListItem { text, .. } => push(&text),
+            Block::Table(rows) => rows.iter().flatten().for_each(|cell| push(cell)),
+            Block::Code(_) | Block::Rule => {}
+        }
+    }
+    words.join(" ")
+}
+
+// Rewrites image destinations outside code: `f` gets each `![..](dest)`
+// destination and returns its replacement, or None to leave it as is.
+pub fn map_image_destinations(body: &str, f: &mut dyn FnMut(&str) -> Option<String>) -> String {
+    let mut fences = Fences::default();
+    let mut out = String::with_capacity(body.len());
+    for line in body.split_inclusive('\n') {
+        if fences.in_code(line) {
+            out.push_str(line);
+            continue;
+        }
+        let chars: Vec<char> = line.chars().collect();
+        let mut j = 0;
+        while j < chars.len() {
+            if chars[j] == '`' {
+                // Leave code spans untouched; unmatched backticks are text
+                let run = chars[j..].iter().take_while(|&&c| c == '`').count();
+                let end = (j + run..chars.len()).find(|&k| {
+                    chars[k - 1] != '`'
+                        && chars[k..].iter().take_while(|&&c| c == '`').count() == run
+                });
+                let stop = end.map_or(j + run, |k| k + run);
+                out.extend(&chars[j..stop]);
+                j = stop;
+                continue;
+            }
+            let image = chars[j] == '!' && chars.get(j + 1) == Some(&'[');
+            let Some((close, dest)) = link_parts(&chars, j + 1).filter(|_| image) else {
+                out.push(chars[j]);
+                j += 1;
+                continue;
+            };
+            let end = skip_destination(&chars, close);
+            match f(&dest).filter(|_| !dest.is_empty()) {
+                Some(new_dest) => {
+                    let inner: String = chars[close + 2..end - 1].iter().collect();
+                    out.extend(&chars[j..close + 2]);
+                    out.push_str(&inner.replacen(&dest, &new_dest, 1));
+                    out.push(')');
+                }
+                None => out.extend(&chars[j..end]),
+            }
+            j = end;
+        }
+    }
+    out
+}
+
+// A problem found by `lint`, on a 1-based line
+pub struct Issue {
+    pub line: usize,
+    pub severity: &'static str,
+    pub message: String,
+}
+
+// Flags Markdown that won't render as intended: unclosed or empty code
+// fences, table rows whose cell count differs from the header, headings
+// deeper than six levels, and links missing their text or destination.
+pub fn lint(body: &str) -> Vec<Issue> {
+    let lines: Vec<&str> = body.lines().collect();
+    let mut issues = Vec::new();
+    let mut warn = |line: usize, message: String| {
+        issues.push(Issue {
+            line: line + 1,
+            severity: "warning",
+            message,
+        })
+    };
+    let mut fences = Fences::default();
+    // Opening line of the current fence and whether it has content yet
+    let mut fence: Option<(usize, bool)> = None;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let was_open = fences.is_open();
+        if fences.in_code(line) {
+            match (was_open, fences.is_open()) {
+                (false, _) => fence = Some((i, false)),
+                (true, true) if !line.trim().is_empty() => {
+                    fence = fence.map(|(start, _)| (start, true));
+                }
+                (true, false) => {
+                    if let Some((start, false)) = fence {
+                        warn(start, "empty code block".to_string());
+                    }
+                    fence = None;
+                }
+                _ => {}
+            }
+            i += 1;
+            continue;
+        }
+        let trimmed = strip_indent(line);
+        let hashes = trimmed.chars().take_while(|&c| c == '#').count();
+        if hashes > 6 && trimmed[hashes..].starts_with([' ', '\t']) && !line.starts_with("    ") {
+            warn(
+                i,
+                format!(
+                    "heading level {} is deeper than 6 and renders as text",
+                    hashes
+                ),
+            );
+        }
+        if line.contains('|') && lines.get(i + 1).is_some_and(|l| is_table_separator(l)) {
+            let columns = table_cells(line).len();
+            let separator = table_cells(lines[i + 1]).len();
+            if separator != columns {
+                warn(
+                    i + 1,
+                    format!(
+                        "table separator has {} cells, header has {}",
+                        separator, columns
+                    ),
+                );
+            }
+            let mut row = i + 2;
+            while row < lines.len() && lines[row].contains('|') && !lines[row].trim().is_empty() {
+                let cells = table_cells(lines[row]).len();
+                if cells != columns {
+                    warn(
+                        row,
+                        format!("table row has {} cells, header has {}", cells, columns),
+                    );
+                }
+                row += 1;
+            }
+            i = row;
+            continue;
+        }
+        let chars: Vec<char> = strip_code_spans(line).chars().collect();
+        let mut j = 0;
+        while j < chars.len() {
+            if chars[j] == '[' && chars.get(j + 1) == Some(&'[') {
+                j += 2;
+                continue;
+            }
+            let Some((close, dest)) = link_parts(&chars, j) else {
+                j += 1;
+                continue;
+            };
+            let image = j > 0 && chars[j - 1] == '!';
+            let text: String = chars[j + 1..close].iter().collect();
+            if dest.is_empty() {
+                let kind = if image { "image" } else { "link" };
+                warn(
+                    i,
+                    format!("{} \"{}\" has no destination", kind, text.trim()),
+                );
+            } else if !image && text.trim().is_empty() {
+                warn(i, format!("link to {} has no text", dest));
+            }
+            j = skip_destination(&chars, close);
+        }
+        i += 1;
+    }
+    if let Some((start, _)) = fence.filter(|_| fences.is_open()) {
+        issues.push(Issue {
+            line: start + 1,
+            severity: "error",
+            message: "code fence is never closed".to_string(),
+        });
+    }
+    issues.sort_by_key(|issue| issue.line);
+    issues
+}
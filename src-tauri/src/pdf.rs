@@ -0,0 +1,462 @@
+// PDF export: the note's markdown blocks laid out on Letter pages with the
+// standard Helvetica and Courier fonts (no embedding), WinAnsi-encoded, with
+// content streams deflated by flate2.
+
+use std::fmt::Write as _;
+use std::io::{self, Write as _};
+
+use crate::markdown::{self, Block, Span};
+
+const PAGE_WIDTH: f32 = 612.0;
+const PAGE_HEIGHT: f32 = 792.0;
+const MARGIN: f32 = 72.0;
+const TEXT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+const BODY_SIZE: f32 = 11.0;
+const CODE_SIZE: f32 = 9.5;
+
+// Advance widths per 1000 em for ASCII 32..=126, from the Adobe AFM files
+const HELVETICA: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+const HELVETICA_BOLD: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, 975, 722, 722, 722, 722, 667,
+    611, 778, 722, 278, 556, 722, 611, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 333, 278, 333, 584, 556, 333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556,
+    278, 889, 611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+// Resource names /F1../F5, in this order
+const FONTS: [&str; 5] = [
+    "Helvetica",
+    "Helvetica-Bold",
+    "Helvetica-Oblique",
+    "Helvetica-BoldOblique",
+    "Courier",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Font {
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+    Mono,
+}
+
+impl Font {
+    fn resource(self) -> usize {
+        self as usize + 1
+    }
+
+    fn for_span(span: &Span, force_bold: bool) -> Font {
+        match (span.code, span.bold || force_bold, span.italic) {
+            (true, _, _) => Font::Mono,
+            (_, true, true) => Font::BoldItalic,
+            (_, true, false) => Font::Bold,
+            (_, false, true) => Font::Italic,
+            _ => Font::Regular,
+        }
+    }
+
+    fn width(self, text: &[u8], size: f32) -> f32 {
+        let units: u32 = text
+            .iter()
+            .map(|&b| match self {
+                Font::Mono => 600,
+                Font::Bold | Font::BoldItalic => glyph_width(&HELVETICA_BOLD, b),
+                Font::Regular | Font::Italic => glyph_width(&HELVETICA, b),
+            })
+            .sum();
+        units as f32 * size / 1000.0
+    }
+}
+
+fn glyph_width(table: &[u16; 95], b: u8) -> u32 {
+    match b {
+        32..=126 => table[(b - 32) as usize] as u32,
+        _ => 556,
+    }
+}
+
+pub struct Options<'a> {
+    // Puts the title, author and date on a page of their own
+    pub cover: bool,
+    pub author: Option<&'a str>,
+    pub subject: Option<&'a str>,
+    pub date: &'a str,
+}
+
+// Lays out pages top to bottom; `y` is the baseline of the next line
+struct Layout {
+    pages: Vec<Vec<u8>>,
+    y: f32,
+}
+
+impl Layout {
+    fn new() -> Self {
+        Layout {
+            pages: vec![Vec::new()],
+            y: PAGE_HEIGHT - MARGIN,
+        }
+    }
+
+    fn page(&mut self) -> &mut Vec<u8> {
+        self.pages.last_mut().unwrap()
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Vec::new());
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    // Moves down one line of `leading`, breaking the page if it won't fit
+    fn advance(&mut self, leading: f32) {
+        if self.y - leading < MARGIN {
+            self.new_page();
+        }
+        self.y -= leading;
+    }
+
+    fn gap(&mut self, space: f32) {
+        self.y -= space;
+    }
+
+    fn line(&mut self, x: f32, runs: &[(Font, Vec<u8>)], size: f32) {
+        let y = self.y;
+        let out = self.page();
+        let _ = write!(out, "BT {:.2} {:.2} Td ", x, y);
+        for (font, text) in runs {
+            let _ = write!(out, "/F{} {} Tf (", font.resource(), size);
+            out.extend(escape(text));
+            out.extend_from_slice(b") Tj ");
+        }
+        out.extend_from_slice(b"ET\n");
+    }
+
+    // Word-wraps runs into lines of at most `width` starting at `x`
+    fn text(&mut self, x: f32, width: f32, runs: Vec<(Font, Vec<u8>)>, size: f32, leading: f32) {
+        for line in wrap(runs, width, size) {
+            self.advance(leading);
+            self.line(x, &line, size);
+        }
+    }
+
+    // Single-line text centered on the page, wrapped if too wide
+    fn centered(&mut self, text: &str, font: Font, size: f32, leading: f32) {
+        for line in wrap(vec![(font, encode(text))], TEXT_WIDTH, size) {
+            let width: f32 = line.iter().map(|(f, t)| f.width(t, size)).sum();
+            self.advance(leading);
+            self.line((PAGE_WIDTH - width) / 2.0, &line, size);
+        }
+    }
+
+    fn rule(&mut self) {
+        self.advance(12.0);
+        let y = self.y + 4.0;
+        let _ = writeln!(
+            self.page(),
+            "0.5 w {:.2} {:.2} m {:.2} {:.2} l S",
+            MARGIN,
+            y,
+            PAGE_WIDTH - MARGIN,
+            y
+        );
+    }
+}
+
+// Splits runs into words (a word keeps its trailing space) and packs them
+// greedily; a word wider than the line is broken between characters.
+fn wrap(runs: Vec<(Font, Vec<u8>)>, width: f32, size: f32) -> Vec<Vec<(Font, Vec<u8>)>> {
+    let mut words: Vec<(Font, Vec<u8>)> = Vec::new();
+    for (font, text) in runs {
+        let mut word = Vec::new();
+        for b in text {
+            word.push(b);
+            if b == b' ' {
+                words.push((font, std::mem::take(&mut word)));
+            }
+        }
+        if !word.is_empty() {
+            words.push((font, word));
+        }
+    }
+    let mut lines = Vec::new();
+    let mut line: Vec<(Font, Vec<u8>)> = Vec::new();
+    let mut used = 0.0;
+    for (font, mut word) in words {
+        let trimmed = word.strip_suffix(b" ").unwrap_or(&word);
+        if used > 0.0 && used + font.width(trimmed, size) > width {
+            lines.push(std::mem::take(&mut line));
+            used = 0.0;
+        }
+        while font.width(word.strip_suffix(b" ").unwrap_or(&word), size) > width && word.len() > 1 {
+            let mut fit = 1;
+            while fit < word.len() && font.width(&word[..fit + 1], size) <= width - used {
+                fit += 1;
+            }
+            let rest = word.split_off(fit);
+            line.push((font, word));
+            lines.push(std::mem::take(&mut line));
+            used = 0.0;
+            word = rest;
+        }
+        used += font.width(&word, size);
+        match line.last_mut() {
+            Some((last, text)) if *last == font => text.extend(word),
+            _ => line.push((font, word)),
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn span_runs(spans: &[Span], force_bold: bool) -> Vec<(Font, Vec<u8>)> {
+    spans
+        .iter()
+        .map(|span| (Font::for_span(span, force_bold), encode(&span.text)))
+        .collect()
+}
+
+fn text_runs(text: &str, force_bold: bool) -> Vec<(Font, Vec<u8>)> {
+    span_runs(&markdown::spans(text), force_bold)
+}
+
+pub fn build(title: &str, body: &str, options: &Options) -> io::Result<Vec<u8>> {
+    let mut layout = Layout::new();
+    let title = title.trim();
+    if options.cover {
+        layout.gap(PAGE_HEIGHT / 4.0);
+        layout.centered(
+            if title.is_empty() { "Untitled" } else { title },
+            Font::Bold,
+            26.0,
+            32.0,
+        );
+        if let Some(author) = options.author.filter(|a| !a.trim().is_empty()) {
+            layout.gap(18.0);
+            layout.centered(author.trim(), Font::Regular, 14.0, 18.0);
+        }
+        layout.gap(12.0);
+        layout.centered(options.date, Font::Regular, 12.0, 16.0);
+        layout.new_page();
+    } else if !title.is_empty() {
+        layout.text(
+            MARGIN,
+            TEXT_WIDTH,
+            vec![(Font::Bold, encode(title))],
+            22.0,
+            28.0,
+        );
+        layout.gap(8.0);
+    }
+
+    // Counters of the ordered lists currently open, by depth
+    let mut numbers: Vec<usize> = Vec::new();
+    for block in markdown::blocks(body) {
+        if !matches!(block, Block::ListItem { .. }) {
+            numbers.clear();
+        }
+        match block {
+            Block::Heading { level, text } => {
+                let size = [20.0, 16.0, 14.0, 12.0, 11.0, 11.0][(level.clamp(1, 6) - 1) as usize];
+                layout.gap(size * 0.5);
+                layout.text(MARGIN, TEXT_WIDTH, text_runs(&text, true), size, size * 1.3);
+                layout.gap(4.0);
+            }
+            Block::Paragraph(text) => {
+                layout.text(MARGIN, TEXT_WIDTH, text_runs(&text, false), BODY_SIZE, 15.0);
+                layout.gap(6.0);
+            }
+            Block::Quote(text) => {
+                let spans: Vec<Span> = markdown::spans(&text)
+                    .into_iter()
+                    .map(|span| Span {
+                        italic: true,
+                        ..span
+                    })
+                    .collect();
+                let runs = span_runs(&spans, false);
+                layout.text(MARGIN + 18.0, TEXT_WIDTH - 18.0, runs, BODY_SIZE, 15.0);
+                layout.gap(6.0);
+            }
+            Block::Code(lines) => {
+                for line in lines {
+                    let runs = vec![(Font::Mono, encode(&line))];
+                    layout.text(MARGIN + 12.0, TEXT_WIDTH - 12.0, runs, CODE_SIZE, 12.0);
+                }
+                layout.gap(6.0);
+            }
+            Block::ListItem {
+                depth,
+                ordered,
+                text,
+            } => {
+                numbers.truncate(depth + 1);
+                numbers.resize(depth + 1, 0);
+                let marker = if ordered {
+                    numbers[depth] += 1;
+                    format!("{}.", numbers[depth])
+                } else {
+                    "\u{2022}".to_string()
+                };
+                let indent = 18.0 * (depth as f32 + 1.0);
+                let mut runs = vec![(Font::Regular, encode(&format!("{} ", marker)))];
+                runs.extend(text_runs(&text, false));
+                layout.text(MARGIN + indent, TEXT_WIDTH - indent, runs, BODY_SIZE, 15.0);
+            }
+            Block::Table(rows) => {
+                for (i, row) in rows.iter().enumerate() {
+                    let mut runs = Vec::new();
+                    for (c, cell) in row.iter().enumerate() {
+                        if c > 0 {
+                            runs.push((Font::Regular, b" | ".to_vec()));
+                        }
+                        runs.extend(text_runs(cell, i == 0));
+                    }
+                    layout.text(MARGIN, TEXT_WIDTH, runs, BODY_SIZE, 15.0);
+                }
+                layout.gap(6.0);
+            }
+            Block::Rule => layout.rule(),
+        }
+    }
+
+    let mut info = format!("/Title {}", text_string(title));
+    if let Some(author) = options.author.filter(|a| !a.trim().is_empty()) {
+        let _ = write!(info, " /Author {}", text_string(author.trim()));
+    }
+    if let Some(subject) = options.subject.filter(|s| !s.trim().is_empty()) {
+        let _ = write!(info, " /Subject {}", text_string(subject.trim()));
+    }
+    info.push_str(" /Producer (anote)");
+    document(&layout.pages, &info)
+}
+
+// Objects: 1 catalog, 2 page tree, 3 info, 4-8 fonts, then a page and its
+// content stream for each page.
+fn document(pages: &[Vec<u8>], info: &str) -> io::Result<Vec<u8>> {
+    let first_page = 4 + FONTS.len();
+    let mut objects: Vec<Vec<u8>> = Vec::new();
+    objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+    let kids: Vec<String> = (0..pages.len())
+        .map(|i| format!("{} 0 R", first_page + 2 * i))
+        .collect();
+    objects.push(
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        )
+        .into_bytes(),
+    );
+    objects.push(format!("<< {} >>", info).into_bytes());
+    for name in FONTS {
+        objects.push(
+            format!(
+                "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+                name
+            )
+            .into_bytes(),
+        );
+    }
+    let fonts: Vec<String> = (0..FONTS.len())
+        .map(|i| format!("/F{} {} 0 R", i + 1, 4 + i))
+        .collect();
+    for (i, content) in pages.iter().enumerate() {
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /Font << {} >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH,
+                PAGE_HEIGHT,
+                fonts.join(" "),
+                first_page + 2 * i + 1
+            )
+            .into_bytes(),
+        );
+        let mut deflate =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        deflate.write_all(content)?;
+        let data = deflate.finish()?;
+        let mut stream = format!(
+            "<< /Length {} /Filter /FlateDecode >>\nstream\n",
+            data.len()
+        )
+        .into_bytes();
+        stream.extend(data);
+        stream.extend_from_slice(b"\nendstream");
+        objects.push(stream);
+    }
+
+    let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        let _ = writeln!(out, "{} 0 obj", i + 1);
+        out.extend(object);
+        out.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = out.len();
+    let _ = write!(out, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(out, "{:010} 00000 n ", offset);
+    }
+    let _ = write!(
+        out,
+        "trailer\n<< /Size {} /Root 1 0 R /Info 3 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    );
+    Ok(out)
+}
+
+// WinAnsi bytes for the standard fonts; characters outside it become '?'
+fn encode(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+            '\t' => b' ',
+            '\u{20ac}' => 0x80,
+            '\u{2026}' => 0x85,
+            '\u{2018}' => 0x91,
+            '\u{2019}' => 0x92,
+            '\u{201c}' => 0x93,
+            '\u{201d}' => 0x94,
+            '\u{2022}' => 0x95,
+            '\u{2013}' => 0x96,
+            '\u{2014}' => 0x97,
+            _ => b'?',
+        })
+        .collect()
+}
+
+fn escape(text: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len());
+    for &b in text {
+        if matches!(b, b'(' | b')' | b'\\') {
+            out.push(b'\\');
+        }
+        out.push(b);
+    }
+    out
+}
+
+// A document-info string: literal when ASCII, otherwise UTF-16BE hex
+fn text_string(text: &str) -> String {
+    if text.chars().all(|c| (' '..='~').contains(&c)) {
+        return format!("({})", String::from_utf8_lossy(&escape(text.as_bytes())));
+    }
+    let mut hex = "<FEFF".to_string();
+    for unit in text.encode_utf16() {
+        let _ = write!(hex, "{:04X}", unit);
+    }
+    hex.push('>');
+    hex
+}
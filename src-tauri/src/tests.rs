@@ -0,0 +1,4017 @@
+// Command tests against in-memory databases. Commands get their managed
+// state from a mock Tauri app, the same way the real app hands it out.
+
+use super::*;
+use tauri::test::{mock_app, MockRuntime};
+
+struct TestDb {
+    app: tauri::App<MockRuntime>,
+}
+
+impl std::ops::Deref for TestDb {
+    type Target = Db;
+    fn deref(&self) -> &Db {
+        self.app.state::<Db>().inner()
+    }
+}
+
+fn test_db(conn: Connection) -> TestDb {
+    let app = mock_app();
+    app.manage(Db(Mutex::new(conn), AtomicBool::new(false)));
+    app.manage(MoveUndo::default());
+    TestDb { app }
+}
+
+fn mem_db() -> TestDb {
+    let conn = Connection::open_in_memory().unwrap();
+    init_db(&conn);
+    test_db(conn)
+}
+
+fn folder(db: &TestDb, id: &str, parent: Option<&str>) {
+    create_folder(st(db), id.into(), id.into(), 1, parent.map(Into::into)).unwrap();
+}
+
+fn note(db: &TestDb, id: &str, folder_id: &str, title: &str, body: &str, updated: i64, sort: i32) {
+    create_note(
+        st(db),
+        id.into(),
+        folder_id.into(),
+        title.into(),
+        body.into(),
+        updated,
+        updated,
+        0,
+        sort,
+    )
+    .unwrap();
+}
+
+fn st(db: &TestDb) -> State<'_, Db> {
+    db.app.state()
+}
+
+fn mk_note(id: &str, folder_id: &str, body: &str) -> Note {
+    Note {
+        id: id.into(),
+        folder_id: folder_id.into(),
+        title: String::new(),
+        body: body.into(),
+        created_at: 1,
+        updated_at: 1,
+        pinned: 0,
+        sort_order: 0,
+        starred: 0,
+    }
+}
+
+fn mk_folder(id: &str, parent: Option<&str>) -> Folder {
+    Folder {
+        id: id.into(),
+        name: id.into(),
+        created_at: 1,
+        parent_id: parent.map(Into::into),
+        sort_order: 0,
+        sort_mode: "manual".into(),
+    }
+}
+
+// Note ids of a folder in display order
+fn order(db: &TestDb, folder: &str) -> Vec<String> {
+    db.0.lock()
+        .unwrap()
+        .prepare("SELECT id FROM notes WHERE folder_id=?1 ORDER BY pinned DESC, sort_order, rowid")
+        .unwrap()
+        .query_map([folder], |r| r.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+// A scratch directory under the system temp dir, removed when dropped
+struct TempDir(std::path::PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("anote-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// Entries of a zip written by archive::ZipWriter, in order
+fn zip_entries(path: &std::path::Path) -> Vec<(String, Vec<u8>)> {
+    let data = std::fs::read(path).unwrap();
+    let mut entries = Vec::new();
+    let mut pos = 0;
+    while data[pos..].starts_with(&0x04034b50u32.to_le_bytes()) {
+        let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]) as usize;
+        let csize = u32::from_le_bytes(data[pos + 18..pos + 22].try_into().unwrap()) as usize;
+        let (name_len, extra_len) = (u16_at(pos + 26), u16_at(pos + 28));
+        let name = String::from_utf8(data[pos + 30..pos + 30 + name_len].to_vec()).unwrap();
+        let start = pos + 30 + name_len + extra_len;
+        let mut out = Vec::new();
+        std::io::Read::read_to_end(
+            &mut flate2::read::DeflateDecoder::new(&data[start..start + csize]),
+            &mut out,
+        )
+        .unwrap();
+        entries.push((name, out));
+        pos = start + csize;
+    }
+    entries
+}
+
+// Entries of a .tar.gz written by archive::TarWriter, in order
+fn tar_gz_entries(path: &std::path::Path) -> Vec<(String, Vec<u8>)> {
+    let mut tar = Vec::new();
+    std::io::Read::read_to_end(
+        &mut flate2::read::GzDecoder::new(std::fs::File::open(path).unwrap()),
+        &mut tar,
+    )
+    .unwrap();
+    archive::read_tar(&tar).unwrap()
+}
+
+#[test]
+fn fast_import_imports_5000_notes_and_restores_synchronous() {
+    let dir = TempDir::new("fast-import");
+    let db = test_db(open_initialized_db(&dir.0.join("anote.db")).unwrap());
+    let notes: Vec<Note> = (0..5000)
+        .map(|i| mk_note(&format!("n{}", i), "f", &format!("body {}", i)))
+        .collect();
+    import_data(st(&db), vec![mk_folder("f", None)], notes, None, None, None).unwrap();
+    {
+        let conn = db.0.lock().unwrap();
+        let sync: i64 = conn
+            .pragma_query_value(None, "synchronous", |r| r.get(0))
+            .unwrap();
+        assert_eq!(sync, 1);
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM notes", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(count, 5000);
+        let hits: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM notes_fts WHERE notes_fts MATCH '\"body 4999\"'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(hits, 1);
+    }
+
+    // A failed import rolls back and still restores the level
+    let mut conn = db.0.lock().unwrap();
+    let err = fast_import(&mut conn, |tx| {
+        tx.execute("DELETE FROM notes", [])
+            .map_err(|e| e.to_string())?;
+        Err::<(), _>("boom".to_string())
+    });
+    assert_eq!(err.unwrap_err(), "boom");
+    let sync: i64 = conn
+        .pragma_query_value(None, "synchronous", |r| r.get(0))
+        .unwrap();
+    assert_eq!(sync, 1);
+    let count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM notes", [], |r| r.get(0))
+        .unwrap();
+    assert_eq!(count, 5000);
+}
+
+#[test]
+fn import_merges_tags_by_name() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "A", "", 1, 0);
+    add_note_tag(st(&db), "a".into(), "Work".into()).unwrap();
+    let tag = |id: &str, name: &str| Tag {
+        id: id.into(),
+        name: name.into(),
+        color: None,
+        created_at: 1,
+    };
+    let link = |note_id: &str, tag_id: &str| NoteTag {
+        note_id: note_id.into(),
+        tag_id: tag_id.into(),
+    };
+    import_data_unordered(
+        st(&db),
+        vec![],
+        vec![mk_note("b", "f", "")],
+        Some(vec![tag("other", "work"), tag("home", "Home")]),
+        Some(vec![link("b", "other"), link("b", "home")]),
+        None,
+    )
+    .unwrap();
+    let conn = db.0.lock().unwrap();
+    let tags: Vec<(String, String)> = conn
+        .prepare(
+            "SELECT t.name, nt.note_id FROM note_tags nt JOIN tags t ON t.id = nt.tag_id \
+             ORDER BY t.name, nt.note_id",
+        )
+        .unwrap()
+        .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let pairs = |v: &[(&str, &str)]| -> Vec<(String, String)> {
+        v.iter()
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .collect()
+    };
+    assert_eq!(tags, pairs(&[("Home", "b"), ("Work", "a"), ("Work", "b")]));
+}
+
+#[test]
+fn add_note_tag_matches_names_case_insensitively() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "A", "", 1, 0);
+    let t = add_note_tag(st(&db), "a".into(), "Work".into()).unwrap();
+    assert_eq!(t.len(), 1);
+    let t = add_note_tag(st(&db), "a".into(), "work".into()).unwrap();
+    assert_eq!(t.len(), 1);
+    let n: i64 =
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM tags", [], |r| r.get(0))
+            .unwrap();
+    assert_eq!(n, 1);
+    assert!(remove_note_tag(st(&db), "a".into(), "WORK".into())
+        .unwrap()
+        .is_empty());
+    assert!(add_note_tag(st(&db), "a;".into(), "x".into()).is_err());
+}
+
+#[test]
+fn import_unordered_accepts_rows_in_any_order() {
+    let db = mem_db();
+    import_data_unordered(
+        st(&db),
+        vec![mk_folder("c", Some("p")), mk_folder("p", None)],
+        vec![mk_note("n", "c", "x")],
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+    let counts = get_folder_counts(st(&db)).unwrap();
+    let p = counts.iter().find(|c| c.folder_id == "p").unwrap();
+    assert_eq!(p.total_count, 1);
+    let err = import_data_unordered(
+        st(&db),
+        vec![],
+        vec![mk_note("m", "zz", "x")],
+        None,
+        None,
+        None,
+    )
+    .unwrap_err();
+    assert!(err.contains("notes m -> folders"), "{}", err);
+    let conn = db.0.lock().unwrap();
+    let n: i64 = conn
+        .query_row("SELECT count(*) FROM notes", [], |r| r.get(0))
+        .unwrap();
+    assert_eq!(n, 1);
+    let fk: i64 = conn
+        .query_row("PRAGMA defer_foreign_keys", [], |r| r.get(0))
+        .unwrap();
+    assert_eq!(fk, 0);
+}
+
+#[test]
+fn export_search_markdown_collects_matches_and_rejects_blank_queries() {
+    let db = mem_db();
+    folder(&db, "p", None);
+    folder(&db, "c", Some("p"));
+    note(&db, "a", "c", "First", "common word here", 1, 0);
+    note(&db, "b", "p", "Second", "also common", 1, 0);
+    note(&db, "x", "p", "Other", "nothing", 1, 1);
+    let dir = TempDir::new("export-search");
+    let path = dir.0.join("results.md");
+    let n =
+        export_search_markdown(st(&db), "common".into(), path.to_string_lossy().into()).unwrap();
+    assert_eq!(n, 2);
+    let out = std::fs::read_to_string(&path).unwrap();
+    assert!(out.contains("## First") && out.contains("## Second") && !out.contains("Other"));
+    assert!(out.contains("> p / c"));
+    // unbalanced quote -> LIKE fallback
+    let n =
+        export_search_markdown(st(&db), "\"common".into(), path.to_string_lossy().into()).unwrap();
+    assert_eq!(n, 0);
+    let n =
+        export_search_markdown(st(&db), "mon wo\"".into(), path.to_string_lossy().into()).unwrap();
+    assert_eq!(n, 0);
+    let n =
+        export_search_markdown(st(&db), "\"mon wo".into(), path.to_string_lossy().into()).unwrap();
+    assert_eq!(n, 0);
+    // A blank query must not fall back to a match-everything substring
+    for query in ["", "   "] {
+        assert!(
+            export_search_markdown(st(&db), query.into(), path.to_string_lossy().into()).is_err()
+        );
+    }
+}
+
+#[test]
+fn move_search_results_appends_to_the_pin_group() {
+    let db = mem_db();
+    folder(&db, "src", None);
+    folder(&db, "dst", None);
+    note(&db, "x", "dst", "Existing", "", 1, 0);
+    create_note(
+        st(&db),
+        "p".into(),
+        "dst".into(),
+        "Pinned".into(),
+        "".into(),
+        1,
+        1,
+        1,
+        10,
+    )
+    .unwrap();
+    note(&db, "a", "src", "Kiwi one", "", 1, 0);
+    note(&db, "b", "src", "two", "about kiwi", 1, 1);
+    note(&db, "c", "src", "three kiwi", "", 1, 2);
+    note(&db, "d", "src", "other", "", 1, 3);
+    assert!(move_search_results(st(&db), "kiwi".into(), "dst".into(), 0).is_err());
+    let r = move_search_results(st(&db), "kiwi".into(), "dst".into(), 10).unwrap();
+    assert_eq!(r.moved, 3);
+    let mut ids = r.ids.clone();
+    ids.sort();
+    assert_eq!(ids, vec!["a", "b", "c"]);
+    let o = order(&db, "dst");
+    assert_eq!(&o[..2], ["p", "x"]);
+    assert_eq!(o.len(), 5);
+    // Moved notes follow the unpinned group, not the pinned one
+    let sorts: Vec<i64> = db.0.lock().unwrap()
+        .prepare("SELECT sort_order FROM notes WHERE folder_id = 'dst' AND pinned = 0 ORDER BY sort_order")
+        .unwrap().query_map([], |r| r.get(0)).unwrap().collect::<Result<_, _>>().unwrap();
+    assert_eq!(sorts, vec![0, 1, 2, 3]);
+    assert_eq!(order(&db, "src"), vec!["d"]);
+    let r = move_search_results(st(&db), "kiwi".into(), "dst".into(), 10).unwrap();
+    assert_eq!(r.moved, 0);
+    assert!(move_search_results(st(&db), " ".into(), "src".into(), 10).is_err());
+    assert_eq!(order(&db, "src"), vec!["d"]);
+}
+
+#[test]
+fn reindex_note_repairs_missing_and_stale_entries() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "apple", "zebra", 1, 0);
+    note(&db, "b", "f", "banana", "zebra", 1, 1);
+    {
+        let c = db.0.lock().unwrap();
+        c.execute("INSERT INTO notes_fts(notes_fts, rowid, title, body) SELECT 'delete', rowid, title, body FROM notes WHERE id = 'a'", []).unwrap();
+    }
+    let ids = |q: &str| {
+        let mut v: Vec<String> = search_notes(st(&db), q.into(), None)
+            .unwrap()
+            .into_iter()
+            .map(|n| n.id)
+            .collect();
+        v.sort();
+        v
+    };
+    assert_eq!(ids("zebra"), vec!["b"]);
+    assert!(reindex_note(st(&db), "a".into()).unwrap());
+    assert_eq!(ids("zebra"), vec!["a", "b"]);
+    assert_eq!(ids("apple"), vec!["a"]);
+    // Text changed behind the triggers' back: the old tokens must go too
+    {
+        let c = db.0.lock().unwrap();
+        c.execute_batch("DROP TRIGGER notes_au; UPDATE notes SET body = 'yak' WHERE id = 'b';")
+            .unwrap();
+    }
+    assert!(reindex_note(st(&db), "b".into()).unwrap());
+    assert_eq!(ids("zebra"), vec!["a"]);
+    assert_eq!(ids("yak"), vec!["b"]);
+    assert!(!reindex_note(st(&db), "b".into()).unwrap());
+    assert!(db
+        .0
+        .lock()
+        .unwrap()
+        .execute(
+            "INSERT INTO notes_fts(notes_fts, rank) VALUES('integrity-check', 1)",
+            []
+        )
+        .is_ok());
+    assert!(reindex_note(st(&db), "zz".into()).is_err());
+}
+
+#[test]
+fn relocate_moves_the_file_and_records_it() {
+    let tmp = TempDir::new("relocate");
+    let dir = tmp.0.clone();
+    std::fs::create_dir_all(dir.join("ext")).unwrap();
+    let old = dir.join("anote.db");
+    let conn = Connection::open(&old).unwrap();
+    init_db(&conn);
+    let db = test_db(conn);
+    folder(&db, "f", None);
+    note(&db, "n", "f", "Hello", "body", 1, 0);
+    let config = dir.join("config.json");
+    std::fs::write(&config, "{\"other\": 1}").unwrap();
+    let new = dir.join("ext/moved.db");
+    {
+        let mut conn = db.0.lock().unwrap();
+        assert!(relocate(&mut conn, &old, &config).is_err());
+        assert!(old.exists());
+        // A config that can't be written keeps the app on the old database
+        let stuck = dir.join("stuck.json");
+        std::fs::create_dir_all(&stuck).unwrap();
+        assert!(relocate(&mut conn, &new, &stuck).is_err());
+        assert!(!new.exists());
+        assert_eq!(conn.path().map(std::path::PathBuf::from), Some(old.clone()));
+        let n: i64 = conn
+            .query_row("SELECT COUNT(*) FROM notes", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(n, 1);
+        let p = relocate(&mut conn, &new, &config).unwrap();
+        assert_eq!(p, new.to_string_lossy());
+    }
+    for name in ["anote.db", "anote.db-wal", "anote.db-shm"] {
+        assert!(!dir.join(name).exists(), "{}", name);
+    }
+    assert_eq!(configured_db_path(&config).unwrap(), new);
+    let cfg: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&config).unwrap()).unwrap();
+    assert_eq!(cfg["other"], 1);
+    note(&db, "m", "f", "After", "", 2, 1);
+    let fresh = Connection::open(&new).unwrap();
+    let titles: Vec<String> = fresh
+        .prepare("SELECT title FROM notes ORDER BY id")
+        .unwrap()
+        .query_map([], |r| r.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(titles, vec!["After", "Hello"]);
+}
+
+#[test]
+fn startup_refuses_a_missing_configured_path() {
+    let tmp = TempDir::new("startup-path");
+    let dir = &tmp.0;
+    let config = dir.join("config.json");
+    let legacy = dir.join("legacy.db");
+    std::fs::write(&legacy, "old").unwrap();
+    // A configured path that's gone is an error, and nothing gets created there
+    let missing = dir.join("drive/anote.db");
+    std::fs::write(
+        &config,
+        serde_json::json!({ "db_path": missing }).to_string(),
+    )
+    .unwrap();
+    assert!(startup_db_path(dir, &config, Some(&legacy)).is_err());
+    assert!(!missing.exists());
+    let present = dir.join("present.db");
+    std::fs::write(&present, "").unwrap();
+    std::fs::write(
+        &config,
+        serde_json::json!({ "db_path": present }).to_string(),
+    )
+    .unwrap();
+    assert_eq!(
+        startup_db_path(dir, &config, Some(&legacy)).unwrap(),
+        present
+    );
+    assert!(!dir.join("anote.db").exists());
+    // Only the default path is seeded from the legacy database
+    std::fs::remove_file(&config).unwrap();
+    let default = startup_db_path(dir, &config, Some(&legacy)).unwrap();
+    assert_eq!(default, dir.join("anote.db"));
+    assert_eq!(std::fs::read_to_string(&default).unwrap(), "old");
+}
+
+#[test]
+fn note_history_round_trips_and_import_keeps_newer_versions() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "t", "v1", 1, 0);
+    for b in ["v2", "v3"] {
+        snapshot_note(st(&db), "a".into()).unwrap();
+        update_note(st(&db), "a".into(), "t".into(), b.into(), 5).unwrap();
+    }
+    snapshot_note(st(&db), "a".into()).unwrap();
+    let dir = TempDir::new("history");
+    let path = dir.0.join("history.json");
+    let p = path.to_string_lossy().to_string();
+    assert_eq!(
+        export_note_history(st(&db), "a".into(), p.clone()).unwrap(),
+        4
+    );
+    note(&db, "b", "f", "t", "", 1, 1);
+    assert_eq!(
+        import_note_history(st(&db), "b".into(), p.clone()).unwrap(),
+        4
+    );
+    let c = db.0.lock().unwrap();
+    let mut s = c
+        .prepare("SELECT body || ':' || reason FROM note_versions WHERE note_id = 'b' ORDER BY id")
+        .unwrap();
+    let v: Vec<String> = s
+        .query_map([], |r| r.get(0))
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+    assert_eq!(v, vec!["v1:manual", "v2:manual", "v3:manual", "v3:edit"]);
+    drop(s);
+    drop(c);
+    assert!(import_note_history(st(&db), "zz".into(), p.clone()).is_err());
+    // Imported versions are older than the 48 already there, so they're the
+    // ones the 50-version cap drops
+    note(&db, "c", "f", "t", "", 1, 2);
+    {
+        let c = db.0.lock().unwrap();
+        for i in 0..48 {
+            c.execute("INSERT INTO note_versions (note_id, title, body, created_at) VALUES ('c', 't', 'recent', ?1)", [i64::MAX - 100 + i]).unwrap();
+        }
+    }
+    assert_eq!(
+        import_note_history(st(&db), "c".into(), p.clone()).unwrap(),
+        2
+    );
+    let c = db.0.lock().unwrap();
+    let recent: i64 = c
+        .query_row(
+            "SELECT COUNT(*) FROM note_versions WHERE note_id = 'c' AND body = 'recent'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(recent, 48);
+    let imported: Vec<String> = c
+        .prepare(
+            "SELECT body FROM note_versions WHERE note_id = 'c' AND body != 'recent' ORDER BY id",
+        )
+        .unwrap()
+        .query_map([], |r| r.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(imported, vec!["v2", "v3"]);
+}
+
+#[test]
+fn prune_version_history_keeps_newest_by_date() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "n", "f", "t", "b", 1, 0);
+    note(&db, "m", "f", "t", "b", 1, 1);
+    {
+        let conn = db.0.lock().unwrap();
+        for i in 0..10 {
+            conn.execute("INSERT INTO note_versions (note_id, title, body, created_at) VALUES ('n', 't', ?1, ?2)", rusqlite::params![format!("v{}", i), i * 100]).unwrap();
+        }
+        conn.execute("INSERT INTO note_versions (note_id, title, body, created_at) VALUES ('m', 't', 'old', 5)", []).unwrap();
+        // Inserted last but the oldest by date (an imported version)
+        conn.execute("INSERT INTO note_versions (note_id, title, body, created_at) VALUES ('n', 't', 'imported', 50)", []).unwrap();
+    }
+    let bodies = |db: &TestDb, id: &str| -> Vec<String> {
+        let conn = db.0.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT body FROM note_versions WHERE note_id = ?1 ORDER BY id")
+            .unwrap();
+        let v = stmt
+            .query_map([id], |r| r.get(0))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        v
+    };
+    assert_eq!(prune_version_history(st(&db), 3, None).unwrap(), 8);
+    assert_eq!(bodies(&db, "n"), vec!["v7", "v8", "v9"]);
+    assert_eq!(prune_version_history(st(&db), 3, Some(850)).unwrap(), 2);
+    assert_eq!(bodies(&db, "n"), vec!["v9"]);
+    assert_eq!(bodies(&db, "m"), vec!["old"]);
+    assert_eq!(prune_version_history(st(&db), 0, Some(10_000)).unwrap(), 0);
+}
+
+#[test]
+fn manual_snapshots_are_all_recorded_and_restore_keeps_history() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "T", "v1", 1, 0);
+    let v1 = snapshot_note(st(&db), "a".into()).unwrap();
+    // An explicit snapshot is saved even when nothing changed
+    assert!(snapshot_note(st(&db), "a".into()).unwrap() > v1);
+    // A version was just saved, so this edit doesn't record another
+    update_note(st(&db), "a".into(), "T2".into(), "v2".into(), 2).unwrap();
+    let v2 = snapshot_note(st(&db), "a".into()).unwrap();
+    assert!(v2 > v1);
+    let rows = |db: &TestDb| -> Vec<String> {
+        let c = db.0.lock().unwrap();
+        let mut s = c
+            .prepare("SELECT title || ':' || body || ':' || reason FROM note_versions WHERE note_id = 'a' ORDER BY id")
+            .unwrap();
+        let v = s
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        v
+    };
+    assert_eq!(
+        rows(&db),
+        vec!["T:v1:manual", "T:v1:manual", "T2:v2:manual"]
+    );
+    // Once the newest version is old enough, an edit keeps the replaced text
+    db.0.lock()
+        .unwrap()
+        .execute("UPDATE note_versions SET created_at = 0", [])
+        .unwrap();
+    update_note(st(&db), "a".into(), "T2".into(), "v3".into(), 3).unwrap();
+    update_note(st(&db), "a".into(), "T2".into(), "v4".into(), 4).unwrap();
+    assert_eq!(
+        rows(&db),
+        vec!["T:v1:manual", "T:v1:manual", "T2:v2:manual", "T2:v2:edit"]
+    );
+    // Restoring keeps the current text as a 'restore' version
+    let versions = get_note_versions(st(&db), "a".into()).unwrap();
+    assert_eq!(versions[0].body, "v2");
+    assert_eq!(restore_note_version(st(&db), v1).unwrap(), "a");
+    let (title, body): (String, String) =
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT title, body FROM notes WHERE id = 'a'", [], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })
+            .unwrap();
+    assert_eq!((title.as_str(), body.as_str()), ("T", "v1"));
+    assert_eq!(rows(&db).last().unwrap(), "T2:v4:restore");
+    assert!(restore_note_version(st(&db), 999).is_err());
+    assert!(snapshot_note(st(&db), "nope".into()).is_err());
+    for i in 0..60 {
+        update_note(st(&db), "a".into(), "T".into(), format!("n{i}"), 10 + i).unwrap();
+        snapshot_note(st(&db), "a".into()).unwrap();
+    }
+    let n: i64 =
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM note_versions", [], |r| r.get(0))
+            .unwrap();
+    assert_eq!(n, MAX_VERSIONS_PER_NOTE);
+}
+
+#[test]
+fn ingest_attachments_rewrites_local_images_and_cleans_up() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    let dir = TempDir::new("ingest");
+    let base = dir.0.clone();
+    let store = base.join("store");
+    std::fs::create_dir_all(base.join("images")).unwrap();
+    std::fs::write(base.join("images/foo.png"), b"PNGDATA").unwrap();
+    let body = "Intro ![a](./images/foo.png \"t\") and ![b](images/missing.png)\n`![c](./images/foo.png)`\n```\n![d](./images/foo.png)\n```\n![e](https://x/y.png) ![again](./images/foo.png)\n";
+    note(&db, "n", "f", "t", body, 1, 0);
+    let r = {
+        let mut conn = db.0.lock().unwrap();
+        ingest_attachments(&mut conn, "n", &base, &store).unwrap()
+    };
+    assert_eq!(r.missing, vec!["images/missing.png"]);
+    assert_eq!(r.attachments.len(), 1);
+    let a = &r.attachments[0];
+    assert_eq!(a.file_name, "foo.png");
+    assert_eq!(a.size, 7);
+    assert_eq!(
+        std::fs::read(store.join(format!("{}.png", a.id))).unwrap(),
+        b"PNGDATA"
+    );
+    let conn = db.0.lock().unwrap();
+    let new_body: String = conn
+        .query_row("SELECT body FROM notes WHERE id='n'", [], |r| r.get(0))
+        .unwrap();
+    let expected = body
+        .replacen(
+            "![a](./images/foo.png",
+            &format!("![a](anote://attachment/{}", a.id),
+            1,
+        )
+        .replacen(
+            "![again](./images/foo.png",
+            &format!("![again](anote://attachment/{}", a.id),
+            1,
+        );
+    assert_eq!(new_body, expected);
+    let n: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM attachments WHERE note_id='n'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(n, 1);
+    drop(conn);
+    // When the files can't be written nothing is recorded or rewritten
+    note(&db, "m", "f", "t", "![x](images/foo.png)", 1, 1);
+    let blocked = base.join("blocked");
+    std::fs::write(&blocked, b"").unwrap();
+    {
+        let mut conn = db.0.lock().unwrap();
+        assert!(ingest_attachments(&mut conn, "m", &base, &blocked.join("store")).is_err());
+        let (n, body): (i64, String) = conn
+            .query_row("SELECT (SELECT COUNT(*) FROM attachments WHERE note_id = 'm'), body FROM notes WHERE id = 'm'", [], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap();
+        assert_eq!((n, body.as_str()), (0, "![x](images/foo.png)"));
+    }
+    // Deleting the note drops its rows; the files go by id
+    std::fs::write(store.join("other.png"), b"").unwrap();
+    let ids = {
+        let conn = db.0.lock().unwrap();
+        note_attachment_ids(&conn, "n").unwrap()
+    };
+    assert_eq!(ids, vec![a.id.clone()]);
+    delete_note(st(&db), "n".into()).unwrap();
+    let n: i64 =
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM attachments", [], |r| r.get(0))
+            .unwrap();
+    assert_eq!(n, 0);
+    remove_attachment_files_in(&store, &ids);
+    assert!(!store.join(format!("{}.png", a.id)).exists());
+    assert!(store.join("other.png").exists());
+}
+
+#[test]
+fn split_and_folder_delete_keep_track_of_attachments() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    folder(&db, "g", Some("f"));
+    note(
+        &db,
+        "n",
+        "f",
+        "t",
+        "# One\nx\n# Two\n![i](anote://attachment/a2)\n",
+        1,
+        0,
+    );
+    note(&db, "m", "g", "t", "", 1, 0);
+    {
+        let conn = db.0.lock().unwrap();
+        for (id, note_id) in [("a1", "n"), ("a2", "n"), ("a3", "m")] {
+            conn.execute("INSERT INTO attachments (id, note_id, file_name, size, created_at) VALUES (?1, ?2, 'f', 0, 0)", rusqlite::params![id, note_id]).unwrap();
+        }
+    }
+    let ids = split_note_by_heading(st(&db), "n".into(), 1, None).unwrap();
+    let owners: Vec<(String, String)> =
+        db.0.lock()
+            .unwrap()
+            .prepare("SELECT id, note_id FROM attachments WHERE id IN ('a1', 'a2') ORDER BY id")
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+    assert_eq!(
+        owners,
+        vec![
+            ("a1".to_string(), ids[0].clone()),
+            ("a2".to_string(), ids[1].clone())
+        ]
+    );
+    let conn = db.0.lock().unwrap();
+    assert_eq!(delete_folder_recursive(&conn, "f").unwrap().len(), 3);
+}
+
+#[test]
+fn export_folder_markdown_bundles_attachments() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    folder(&db, "g", Some("f"));
+    {
+        let c = db.0.lock().unwrap();
+        c.execute("UPDATE folders SET name = 'Top' WHERE id = 'f'", [])
+            .unwrap();
+        c.execute("UPDATE folders SET name = 'Sub' WHERE id = 'g'", [])
+            .unwrap();
+    }
+    note(
+        &db,
+        "a",
+        "f",
+        "A",
+        "![x](anote://attachment/img1) and ![y](anote://attachment/img1)",
+        1,
+        0,
+    );
+    note(
+        &db,
+        "b",
+        "g",
+        "B",
+        "![z](anote://attachment/img1) ![gone](anote://attachment/lost)",
+        1,
+        0,
+    );
+    let dir = TempDir::new("folder-md");
+    let store = dir.0.join("store");
+    std::fs::create_dir_all(&store).unwrap();
+    std::fs::write(store.join("img1.png"), b"PNG").unwrap();
+    let zip = dir.0.join("out.zip");
+    let report = {
+        let conn = db.0.lock().unwrap();
+        write_folder_markdown(&conn, "f", zip.to_string_lossy().into(), &store).unwrap()
+    };
+    assert_eq!((report.note_count, report.attachment_count), (2, 1));
+    assert_eq!(report.missing_attachments, vec!["lost"]);
+    let entries = zip_entries(&zip);
+    let names: Vec<&str> = entries.iter().map(|(n, _)| n.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["attachments/img1.png", "Top/A.md", "Top/Sub/B.md"]
+    );
+    assert_eq!(entries[0].1, b"PNG");
+    assert_eq!(
+        String::from_utf8(entries[1].1.clone()).unwrap(),
+        "# A\n\n![x](../attachments/img1.png) and ![y](../attachments/img1.png)"
+    );
+    assert_eq!(
+        String::from_utf8(entries[2].1.clone()).unwrap(),
+        "# B\n\n![z](../../attachments/img1.png) ![gone](anote://attachment/lost)"
+    );
+    let conn = db.0.lock().unwrap();
+    assert!(write_folder_markdown(&conn, "nope", zip.to_string_lossy().into(), &store).is_err());
+}
+
+#[test]
+fn find_broken_attachments_flags_missing_rows_and_files() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(
+        &db,
+        "a",
+        "f",
+        "a",
+        "![ok](anote://attachment/good) ![x](anote://attachment/gone)",
+        1,
+        0,
+    );
+    note(
+        &db,
+        "b",
+        "f",
+        "b",
+        "[twice](anote://attachment/ghost) [again](anote://attachment/ghost)",
+        1,
+        1,
+    );
+    note(&db, "c", "f", "c", "no attachments", 1, 2);
+    {
+        let c = db.0.lock().unwrap();
+        for (id, note_id) in [("good", "a"), ("gone", "a")] {
+            c.execute("INSERT INTO attachments (id, note_id, file_name, size, created_at) VALUES (?1, ?2, 'f', 0, 0)", rusqlite::params![id, note_id]).unwrap();
+        }
+    }
+    let dir = TempDir::new("broken-attachments");
+    std::fs::write(dir.0.join("good.png"), b"").unwrap();
+    let conn = db.0.lock().unwrap();
+    let broken: Vec<(String, String, String)> = broken_attachments(&conn, &dir.0)
+        .unwrap()
+        .into_iter()
+        .map(|b| (b.note_id, b.attachment_ref, b.reason))
+        .collect();
+    assert_eq!(
+        broken,
+        vec![
+            (
+                "a".to_string(),
+                "gone".to_string(),
+                "file missing".to_string()
+            ),
+            ("b".to_string(), "ghost".to_string(), "no row".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn anotefile_round_trips_tags_and_attachments() {
+    let dir = TempDir::new("anotefile");
+    let (src_store, dst_store) = (dir.0.join("src"), dir.0.join("dst"));
+    std::fs::create_dir_all(&src_store).unwrap();
+    std::fs::write(src_store.join("pic1.png"), b"\x89PNG bytes").unwrap();
+    let src = mem_db();
+    folder(&src, "f", None);
+    note(
+        &src,
+        "a",
+        "f",
+        "Recipe",
+        "Flour\n- water\n![p](anote://attachment/pic1)",
+        5,
+        0,
+    );
+    set_note_starred(st(&src), "a".into(), true).unwrap();
+    add_note_tag(st(&src), "a".into(), "food".into()).unwrap();
+    {
+        let c = src.0.lock().unwrap();
+        c.execute("UPDATE tags SET color = '#f00'", []).unwrap();
+        c.execute("INSERT INTO attachments (id, note_id, file_name, size, created_at) VALUES ('pic1', 'a', 'dish.png', 10, 1)", []).unwrap();
+    }
+    let path = dir.0.join("note.anote").to_string_lossy().into_owned();
+    write_anotefile(&src.0.lock().unwrap(), "a", &path, &src_store).unwrap();
+
+    let dst = mem_db();
+    folder(&dst, "g", None);
+    note(&dst, "old", "g", "old", "", 1, 0);
+    add_note_tag(st(&dst), "old".into(), "FOOD".into()).unwrap();
+    let id = read_anotefile(&mut dst.0.lock().unwrap(), &path, "g", &dst_store).unwrap();
+    assert_ne!(id, "a");
+    let conn = dst.0.lock().unwrap();
+    let (attachment, file_name): (String, String) = conn
+        .query_row(
+            "SELECT id, file_name FROM attachments WHERE note_id = ?1",
+            [&id],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .unwrap();
+    assert_ne!(attachment, "pic1");
+    assert_eq!(file_name, "dish.png");
+    assert_eq!(
+        std::fs::read(dst_store.join(format!("{}.png", attachment))).unwrap(),
+        b"\x89PNG bytes"
+    );
+    let (title, body, created, starred): (String, String, i64, i32) = conn
+        .query_row(
+            "SELECT title, body, created_at, starred FROM notes WHERE id = ?1",
+            [&id],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)),
+        )
+        .unwrap();
+    let expected = format!("Flour\n- water\n![p](anote://attachment/{})", attachment);
+    assert_eq!(
+        (title.as_str(), body.as_str(), starred),
+        ("Recipe", expected.as_str(), 1)
+    );
+    assert!(created > 5);
+    let tags = note_tags(&conn, &id).unwrap();
+    assert_eq!(
+        (tags.len(), tags[0].name.as_str(), tags[0].color.as_deref()),
+        (1, "FOOD", Some("#f00"))
+    );
+    drop(conn);
+    assert_eq!(order(&dst, "g")[0], id);
+    let bad = dir.0.join("bad.anote");
+    std::fs::write(&bad, "{\"type\":\"other\"}").unwrap();
+    let bad = bad.to_string_lossy();
+    assert!(read_anotefile(&mut dst.0.lock().unwrap(), &bad, "g", &dst_store).is_err());
+    for (raw, encoded) in [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+    ] {
+        assert_eq!(base64::encode(raw.as_bytes()), encoded);
+        assert_eq!(base64::decode(encoded).unwrap(), raw.as_bytes());
+    }
+    assert!(base64::decode("Zg=").is_none());
+    assert!(base64::decode("Zg==Zg==").is_none());
+}
+
+#[test]
+fn export_site_writes_linked_pages() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    folder(&db, "c", Some("f"));
+    note(
+        &db,
+        "a",
+        "f",
+        "Alpha",
+        "Links to [[Beta]] and [[missing]].\n\n- one\n  - two\n1. x\n\n| h |\n|---|\n| <v> |",
+        1,
+        0,
+    );
+    note(
+        &db,
+        "b",
+        "c",
+        "Beta",
+        "**bold** [ext](http://e.com) [bad](javascript:alert(1))",
+        1,
+        0,
+    );
+    let tmp = TempDir::new("site");
+    let dir = tmp.0.join("site");
+    assert_eq!(
+        export_site(st(&db), dir.to_string_lossy().into()).unwrap(),
+        3
+    );
+    let a = std::fs::read_to_string(dir.join("a.html")).unwrap();
+    assert!(a.contains("<a href=\"b.html\">Beta</a>"));
+    assert!(a.contains("<a class=\"broken\">missing</a>"));
+    assert!(a.contains("&lt;v&gt;"));
+    assert!(a.contains("<span class=\"folder\">f</span>"));
+    let b = std::fs::read_to_string(dir.join("b.html")).unwrap();
+    assert!(b.contains("<strong>bold</strong>") && b.contains("href=\"http://e.com\""));
+    assert!(!b.contains("javascript"));
+    let idx = std::fs::read_to_string(dir.join("index.html")).unwrap();
+    assert!(idx.contains("href=\"a.html\"") && idx.contains("href=\"b.html\""));
+    let s: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("search.json")).unwrap()).unwrap();
+    assert_eq!(s.as_array().unwrap().len(), 2);
+    assert_eq!(s[0]["url"], "b.html");
+    assert!(dir.join("style.css").exists());
+}
+
+#[test]
+fn site_links_keep_only_allowed_schemes() {
+    let no_wiki = |_: &str| None;
+    let links = |body: &str| html::render(body, &no_wiki);
+    for ok in [
+        "http://e.com/x",
+        "HTTPS://e.com",
+        "mailto:a@b.c",
+        "#top",
+        "other.html",
+        "dir/page.html?q=a:b",
+        "/abs",
+    ] {
+        let out = links(&format!("[x]({})", ok));
+        assert!(out.contains(&format!("href=\"{}\"", ok)), "{}: {}", ok, out);
+    }
+    for bad in [
+        "javascript:alert(1)",
+        "JavaScript:alert(1)",
+        "java\u{1}script:alert(1)",
+        "java\u{7f}script:x",
+        "\u{1}javascript:x",
+        "data:text/html,hi",
+        "vbscript:msgbox",
+        "file:///etc/passwd",
+    ] {
+        let out = links(&format!("[x]({})", bad));
+        assert!(!out.contains("href"), "{:?}: {}", bad, out);
+    }
+}
+
+#[test]
+fn content_hash_follows_title_and_body() {
+    assert_eq!(
+        crate::sha256::note_hash("", ""),
+        "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d"
+    );
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "t", "body", 1, 0);
+    let h = |db: &TestDb| get_note_hashes(st(db)).unwrap()[0].1.clone();
+    let h1 = h(&db);
+    assert_eq!(h1, crate::sha256::note_hash("t", "body"));
+    update_note(st(&db), "a".into(), "t".into(), "body".into(), 5).unwrap();
+    assert_eq!(h(&db), h1);
+    update_note(st(&db), "a".into(), "t".into(), "body2".into(), 6).unwrap();
+    let h2 = h(&db);
+    assert_ne!(h2, h1);
+    let meta = get_notes_feed(st(&db), None, 10).unwrap().0;
+    assert_eq!(meta[0].content_hash.as_deref(), Some(h2.as_str()));
+}
+
+#[test]
+fn other_clients_can_write_notes_without_note_hash() {
+    let dir = TempDir::new("hash-triggers");
+    let path = dir.0.join("anote.db");
+    drop(open_initialized_db(&path).unwrap());
+    // A plain connection has no note_hash(), and the schema doesn't need it
+    let other = rusqlite::Connection::open(&path).unwrap();
+    let triggers: i64 = other
+        .query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE name LIKE 'notes_hash%'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(triggers, 0);
+    other
+        .execute_batch(
+            "INSERT INTO folders (id, name, created_at) VALUES ('f', 'f', 1);
+             INSERT INTO notes (id, folder_id, title, body, created_at, updated_at) VALUES ('a', 'f', 't', 'b', 1, 1);",
+        )
+        .unwrap();
+    drop(other);
+    // The app fills in the hash the next time it opens the database
+    let db = test_db(open_initialized_db(&path).unwrap());
+    let hash = |db: &TestDb| -> String {
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT content_hash FROM notes", [], |r| r.get(0))
+            .unwrap()
+    };
+    assert_eq!(hash(&db), crate::sha256::note_hash("t", "b"));
+    drop(db);
+    // An edit from another client leaves the hash stale until a rehash
+    let other = rusqlite::Connection::open(&path).unwrap();
+    other.execute("UPDATE notes SET body = 'c'", []).unwrap();
+    drop(other);
+    let db = test_db(open_initialized_db(&path).unwrap());
+    assert_eq!(hash(&db), crate::sha256::note_hash("t", "b"));
+    assert_eq!(rehash_notes(st(&db)).unwrap(), 1);
+    assert_eq!(hash(&db), crate::sha256::note_hash("t", "c"));
+    assert_eq!(rehash_notes(st(&db)).unwrap(), 0);
+}
+
+#[test]
+fn delete_folder_moves_notes_to_the_inbox() {
+    let db = mem_db();
+    folder(&db, "Inbox", None);
+    folder(&db, "p", None);
+    folder(&db, "c", Some("p"));
+    note(&db, "i", "Inbox", "i", "", 1, 0);
+    note(&db, "a", "p", "a", "", 1, 0);
+    note(&db, "b", "c", "b", "", 1, 0);
+    delete_folder(st(&db), "p".into(), None).unwrap();
+    assert_eq!(order(&db, "Inbox"), vec!["i", "a", "b"]);
+    let n: i64 =
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM folders", [], |r| r.get(0))
+            .unwrap();
+    assert_eq!(n, 1);
+    assert!(delete_folder(st(&db), "Inbox".into(), Some("bogus".into())).is_err());
+    // Deleting the inbox hands its notes to a new one
+    delete_folder(st(&db), "Inbox".into(), None).unwrap();
+    let inbox = get_inbox_folder(st(&db)).unwrap().unwrap();
+    assert_ne!(inbox, "Inbox");
+    assert_eq!(order(&db, &inbox), vec!["i", "a", "b"]);
+    // An empty inbox just goes away
+    delete_folder(st(&db), inbox.clone(), Some("delete".into())).unwrap();
+    folder(&db, "Inbox", None);
+    delete_folder(st(&db), "Inbox".into(), None).unwrap();
+    assert_eq!(get_inbox_folder(st(&db)).unwrap(), None);
+    // no inbox: one is created
+    let db = mem_db();
+    folder(&db, "p", None);
+    note(&db, "a", "p", "a", "", 1, 0);
+    delete_folder(st(&db), "p".into(), Some("move_to_inbox".into())).unwrap();
+    let inbox = get_inbox_folder(st(&db)).unwrap().unwrap();
+    assert_eq!(order(&db, &inbox), vec!["a"]);
+    // A missing folder is an error, and no inbox is made for it
+    let db = mem_db();
+    assert!(delete_folder(st(&db), "nope".into(), None).is_err());
+    assert_eq!(get_inbox_folder(st(&db)).unwrap(), None);
+    let n: i64 =
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM folders", [], |r| r.get(0))
+            .unwrap();
+    assert_eq!(n, 0);
+}
+
+#[test]
+fn toc_note_links_to_heading_ids() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    folder(&db, "g", None);
+    note(&db, "src", "f", "Guide", "# Getting Started!\n\ntext\n\n```\n# not a heading\n```\n\n## Install *fast*\n\n## Install fast\n", 1, 0);
+    let id = generate_toc_note(st(&db), "src".into(), "g".into()).unwrap();
+    let body = |db: &TestDb, id: &str| -> (String, String) {
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT title, body FROM notes WHERE id=?1", [id], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })
+            .unwrap()
+    };
+    let (title, toc) = body(&db, &id);
+    assert_eq!(title, "TOC: Guide");
+    assert_eq!(toc, "Contents of [[src|Guide]]\n\n- [Getting Started!](#getting-started)\n  - [Install fast](#install-fast)\n  - [Install fast](#install-fast-1)\n");
+    db.0.lock()
+        .unwrap()
+        .execute("UPDATE notes SET body = '# Only' WHERE id='src'", [])
+        .unwrap();
+    let again = generate_toc_note(st(&db), "src".into(), "g".into()).unwrap();
+    assert_eq!(again, id);
+    assert_eq!(
+        body(&db, &id).1,
+        "Contents of [[src|Guide]]\n\n- [Only](#only)\n"
+    );
+    assert_eq!(order(&db, "g"), vec![id.clone()]);
+    assert!(generate_toc_note(st(&db), "nope".into(), "g".into()).is_err());
+    // The anchors are the ids exported pages give their headings
+    let no_wiki = |_: &str| None;
+    let page = html::render(
+        "# Getting Started!\n\n## Install *fast*\n\n## Install fast\n",
+        &no_wiki,
+    );
+    assert!(page.contains("<h1 id=\"getting-started\">"), "{}", page);
+    assert!(
+        page.contains("<h2 id=\"install-fast\">Install <em>fast</em></h2>"),
+        "{}",
+        page
+    );
+    assert!(page.contains("<h2 id=\"install-fast-1\">"), "{}", page);
+    // A title can't break out of the header's wiki link
+    note(&db, "odd", "f", "a]] b|c [x]", "# H", 1, 1);
+    let odd = generate_toc_note(st(&db), "odd".into(), "g".into()).unwrap();
+    assert!(body(&db, &odd)
+        .1
+        .starts_with("Contents of [[odd|a bc x]]\n"));
+}
+
+#[test]
+fn grouped_search_splits_title_and_body_matches() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "t", "f", "Kiwi plans", "nothing here", 1, 0);
+    note(&db, "b", "f", "Groceries", "buy kiwi and apples", 1, 1);
+    note(&db, "both", "f", "kiwi", "more kiwi", 1, 2);
+    note(&db, "x", "f", "Other", "unrelated", 1, 3);
+    let g = search_notes_grouped(st(&db), "kiwi".into(), None).unwrap();
+    let mut titles: Vec<String> = g.title_matches.iter().map(|n| n.id.clone()).collect();
+    titles.sort();
+    assert_eq!(titles, vec!["both", "t"]);
+    let bodies: Vec<String> = g.body_matches.iter().map(|n| n.id.clone()).collect();
+    assert_eq!(bodies, vec!["b"]);
+    let g = search_notes_grouped(st(&db), "apples OR unrelated".into(), None).unwrap();
+    assert!(g.title_matches.is_empty());
+    assert_eq!(g.body_matches.len(), 2);
+    // Can't step outside the title filter
+    assert!(search_notes_grouped(st(&db), "kiwi) OR (unrelated".into(), None).is_err());
+    // Title matches don't crowd body matches out of the limit
+    for i in 0..100 {
+        note(&db, &format!("k{}", i), "f", "kiwi", "", 1, 10 + i);
+    }
+    let g = search_notes_grouped(st(&db), "kiwi".into(), None).unwrap();
+    assert_eq!(g.title_matches.len(), 80);
+    let bodies: Vec<String> = g.body_matches.iter().map(|n| n.id.clone()).collect();
+    assert_eq!(bodies, vec!["b"]);
+}
+
+#[test]
+fn journal_mode_setting_survives_reopen() {
+    let tmp = TempDir::new("journal");
+    let dir = tmp.0.clone();
+    let path = dir.join("a.db");
+    let open = || {
+        let c = rusqlite::Connection::open(&path).unwrap();
+        init_db(&c);
+        test_db(c)
+    };
+    let db = open();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "t", "b", 1, 0);
+    assert_eq!(
+        set_journal_mode(st(&db), "delete".into()).unwrap(),
+        "delete"
+    );
+    let wal = dir.join("a.db-wal");
+    assert!(!wal.exists() || std::fs::metadata(&wal).unwrap().len() == 0);
+    note(&db, "b", "f", "t", "b", 1, 1);
+    drop(db);
+    assert!(!wal.exists());
+    let db = open();
+    let mode: String =
+        db.0.lock()
+            .unwrap()
+            .pragma_query_value(None, "journal_mode", |r| r.get(0))
+            .unwrap();
+    assert_eq!(mode, "delete");
+    // Reopening in DELETE mode never passes through WAL
+    note(&db, "c", "f", "t", "b", 1, 2);
+    assert!(!wal.exists());
+    assert_eq!(set_journal_mode(st(&db), "WAL".into()).unwrap(), "wal");
+    assert!(set_journal_mode(st(&db), "memory".into()).is_err());
+    drop(db);
+    let db = open();
+    let mode: String =
+        db.0.lock()
+            .unwrap()
+            .pragma_query_value(None, "journal_mode", |r| r.get(0))
+            .unwrap();
+    assert_eq!(mode, "wal");
+}
+
+#[test]
+fn repair_schema_recreates_missing_objects() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "n", "f", "Alpha", "zebra body", 1, 0);
+    {
+        let mut conn = db.0.lock().unwrap();
+        assert!(repair_schema(&mut conn).unwrap().is_empty());
+        conn.execute_batch(
+            "ALTER TABLE folders DROP COLUMN sort_mode;
+             DROP TABLE note_tags; DROP TABLE tags;
+             DROP TRIGGER notes_ai; DROP INDEX idx_note_meta_key;
+             DROP TABLE notes_fts;
+             DROP TABLE folder_counts;",
+        )
+        .unwrap();
+        let mut repairs = repair_schema(&mut conn).unwrap();
+        repairs.sort();
+        assert_eq!(
+            repairs,
+            vec![
+                "added column folders.sort_mode",
+                "created index idx_note_meta_key",
+                "created index idx_note_tags_tag",
+                "created table folder_counts",
+                "created table note_tags",
+                "created table notes_fts",
+                "created table tags",
+                "created trigger note_tags_applied",
+                "created trigger notes_ai",
+            ]
+        );
+        assert!(repair_schema(&mut conn).unwrap().is_empty());
+        let mode: String = conn
+            .query_row("SELECT sort_mode FROM folders WHERE id='f'", [], |r| {
+                r.get(0)
+            })
+            .unwrap();
+        assert_eq!(mode, "manual");
+    }
+    assert_eq!(
+        search_notes(st(&db), "zebra".into(), None).unwrap().len(),
+        1
+    );
+    note(&db, "m", "f", "Beta", "yak", 1, 1);
+    assert_eq!(search_notes(st(&db), "yak".into(), None).unwrap().len(), 1);
+    add_note_tag(st(&db), "n".into(), "work".into()).unwrap();
+    assert_eq!(get_folder_counts(st(&db)).unwrap()[0].direct_count, 2);
+    // Notes written while only an FTS trigger was missing get indexed too
+    {
+        let mut conn = db.0.lock().unwrap();
+        conn.execute("DROP TRIGGER notes_ai", []).unwrap();
+        conn.execute(
+            "INSERT INTO notes (id, folder_id, title, body, created_at, updated_at) VALUES ('q', 'f', 'Gamma', 'quokka', 1, 1)",
+            [],
+        )
+        .unwrap();
+        assert_eq!(
+            repair_schema(&mut conn).unwrap(),
+            vec!["created trigger notes_ai"]
+        );
+    }
+    assert_eq!(
+        search_notes(st(&db), "quokka".into(), None).unwrap().len(),
+        1
+    );
+}
+
+#[test]
+fn incremental_export_carries_moves_and_deletions() {
+    let db = mem_db();
+    folder(&db, "p", None);
+    folder(&db, "f", Some("p"));
+    folder(&db, "g", None);
+    note(&db, "a", "f", "a", "", 1, 0);
+    note(&db, "b", "g", "b", "", 1, 0);
+    note(&db, "c", "g", "c", "", 1, 1);
+    add_note_tag(st(&db), "a".into(), "x".into()).unwrap();
+    add_note_tag(st(&db), "b".into(), "y".into()).unwrap();
+    let dir = TempDir::new("incremental");
+    let p = dir.0.join("delta.json").to_string_lossy().to_string();
+    let r = export_incremental(st(&db), "usb".into(), p.clone()).unwrap();
+    assert_eq!((r.note_count, r.folder_count, r.since), (3, 3, None));
+    {
+        let c = db.0.lock().unwrap();
+        c.execute(
+            "UPDATE notes SET updated_at = ?1 WHERE id = 'a'",
+            [chrono::Utc::now().timestamp_millis() + 1000],
+        )
+        .unwrap();
+        // Neither of these bumps updated_at
+        c.execute(
+            "UPDATE notes SET folder_id = 'p', pinned = 1 WHERE id = 'b'",
+            [],
+        )
+        .unwrap();
+        c.execute("DELETE FROM notes WHERE id = 'c'", []).unwrap();
+    }
+    let r2 = export_incremental(st(&db), "usb".into(), p.clone()).unwrap();
+    assert_eq!(
+        (r2.note_count, r2.folder_count, r2.since),
+        (1, 3, Some(r.exported_at))
+    );
+    let v: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&p).unwrap()).unwrap();
+    assert_eq!(v["notes"].as_array().unwrap().len(), 1);
+    assert_eq!(v["notes"][0]["id"], "a");
+    assert_eq!(v["tags"].as_array().unwrap().len(), 2);
+    assert_eq!(v["note_tags"].as_array().unwrap().len(), 2);
+    let mut positions: Vec<(String, String, i64)> = v["note_positions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|n| {
+            (
+                n["id"].as_str().unwrap().to_string(),
+                n["folder_id"].as_str().unwrap().to_string(),
+                n["pinned"].as_i64().unwrap(),
+            )
+        })
+        .collect();
+    positions.sort();
+    assert_eq!(
+        positions,
+        vec![
+            ("a".to_string(), "f".to_string(), 0),
+            ("b".to_string(), "p".to_string(), 1)
+        ]
+    );
+    // other labels are independent
+    assert_eq!(
+        export_incremental(st(&db), "dropbox".into(), p.clone())
+            .unwrap()
+            .note_count,
+        2
+    );
+}
+
+#[test]
+fn move_undo_restores_positions_and_keeps_recent_tokens() {
+    let db = mem_db();
+    folder(&db, "a", None);
+    folder(&db, "b", None);
+    note(&db, "n1", "a", "", "", 1, 0);
+    note(&db, "n2", "a", "", "", 1, 1);
+    note(&db, "n3", "a", "", "", 1, 2);
+    note(&db, "m", "b", "", "", 1, 0);
+    let t = move_notes_with_undo(
+        st(&db),
+        db.app.state::<MoveUndo>(),
+        vec!["n3".into(), "n1".into(), "gone".into()],
+        "b".into(),
+    )
+    .unwrap();
+    assert_eq!(t.moved, 2);
+    assert_eq!(order(&db, "b"), vec!["m", "n3", "n1"]);
+    assert_eq!(order(&db, "a"), vec!["n2"]);
+    assert_eq!(
+        undo_move(st(&db), db.app.state::<MoveUndo>(), t.token.clone()).unwrap(),
+        2
+    );
+    assert_eq!(order(&db, "a"), vec!["n1", "n2", "n3"]);
+    assert_eq!(order(&db, "b"), vec!["m"]);
+    assert!(undo_move(st(&db), db.app.state::<MoveUndo>(), t.token).is_err());
+    // A repeated id is recorded once, at its original position
+    let t = move_notes_with_undo(
+        st(&db),
+        db.app.state::<MoveUndo>(),
+        vec!["n1".into(), "n1".into()],
+        "b".into(),
+    )
+    .unwrap();
+    assert_eq!(t.moved, 1);
+    undo_move(st(&db), db.app.state::<MoveUndo>(), t.token).unwrap();
+    assert_eq!(order(&db, "a"), vec!["n1", "n2", "n3"]);
+    // Only the newest offers are kept
+    let first = move_notes_with_undo(
+        st(&db),
+        db.app.state::<MoveUndo>(),
+        vec!["n2".into()],
+        "b".into(),
+    )
+    .unwrap();
+    for _ in 0..MAX_MOVE_UNDOS {
+        move_notes_with_undo(
+            st(&db),
+            db.app.state::<MoveUndo>(),
+            vec!["n2".into()],
+            "b".into(),
+        )
+        .unwrap();
+    }
+    assert_eq!(
+        db.app.state::<MoveUndo>().0.lock().unwrap().len(),
+        MAX_MOVE_UNDOS
+    );
+    assert!(undo_move(st(&db), db.app.state::<MoveUndo>(), first.token).is_err());
+}
+
+#[test]
+fn daily_note_is_reused_and_expands_the_template() {
+    let db = mem_db();
+    folder(&db, "j", None);
+    note(&db, "old", "j", "x", "", 1, 0);
+    let a = get_or_create_daily_note(st(&db), "j".into(), Some("# {{date}}\n".into())).unwrap();
+    assert_eq!(
+        get_or_create_daily_note(st(&db), "j".into(), None).unwrap(),
+        a
+    );
+    assert_eq!(order(&db, "j"), vec![a.clone(), "old".to_string()]);
+    let at = chrono::TimeZone::with_ymd_and_hms(&chrono::Local, 2000, 1, 1, 9, 30, 0).unwrap();
+    let b = daily_note(
+        &mut db.0.lock().unwrap(),
+        "j",
+        at,
+        Some("{{date}} {{time}}"),
+    )
+    .unwrap();
+    assert_ne!(a, b);
+    let (title, body): (String, String) =
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT title, body FROM notes WHERE id = ?1", [&b], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })
+            .unwrap();
+    assert_eq!(title, "2000-01-01");
+    assert_eq!(body, "2000-01-01 09:30");
+    assert!(get_or_create_daily_note(st(&db), "zz".into(), None).is_err());
+}
+
+#[test]
+fn starred_is_independent_of_pinned() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "a", "", 1, 0);
+    note(&db, "b", "f", "b", "", 2, 0);
+    set_note_starred(st(&db), "a".into(), true).unwrap();
+    toggle_note_pinned(st(&db), "b".into(), 1).unwrap();
+    let starred: Vec<String> = get_starred_notes(st(&db))
+        .unwrap()
+        .into_iter()
+        .map(|n| n.id)
+        .collect();
+    assert_eq!(starred, vec!["a"]);
+    let meta = get_notes_metadata(st(&db), None, None).unwrap();
+    assert_eq!(meta[0].id, "b");
+    assert_eq!((meta[0].pinned, meta[0].starred), (1, 0));
+    assert_eq!((meta[1].pinned, meta[1].starred), (0, 1));
+    set_note_starred(st(&db), "a".into(), false).unwrap();
+    assert!(get_starred_notes(st(&db)).unwrap().is_empty());
+    let backup = backup_json(&db.0.lock().unwrap(), 0).unwrap();
+    assert_eq!(
+        serde_json::to_value(&backup).unwrap()["notes"][0]["starred"],
+        0
+    );
+}
+
+#[test]
+fn starred_migration_can_rerun() {
+    let conn = Connection::open_in_memory().unwrap();
+    init_db(&conn);
+    conn.execute(
+        "INSERT INTO folders (id, name, created_at) VALUES ('f', 'f', 1)",
+        [],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO notes (id, folder_id, title, body, created_at, updated_at, starred) VALUES ('a', 'f', 'a', '', 1, 1, 1)",
+        [],
+    )
+    .unwrap();
+    // A v7 migration that was interrupted before the version bump runs again
+    conn.pragma_update(None, "user_version", 6).unwrap();
+    init_db(&conn);
+    let starred: i32 = conn
+        .query_row("SELECT starred FROM notes WHERE id = 'a'", [], |r| r.get(0))
+        .unwrap();
+    assert_eq!(starred, 1);
+}
+
+#[test]
+fn hashtags_become_tags_outside_code() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "a", "Working on #project today #Ideas\n\n```\nlet x = \"#project in code\";\n#fenced\n```\nSee `#inline` and C# or #123 and http://x.com/#frag\n#Project again", 1, 0);
+    note(&db, "b", "f", "b", "# Heading\nno tags here", 1, 1);
+    add_note_tag(st(&db), "b".into(), "ideas".into()).unwrap();
+    let r = extract_hashtags_to_tags(st(&db), true).unwrap();
+    assert_eq!((r.tags_created, r.notes_affected), (1, 1));
+    assert_eq!(note_tags(&db.0.lock().unwrap(), "a").unwrap().len(), 0);
+    let r = extract_hashtags_to_tags(st(&db), false).unwrap();
+    assert_eq!((r.tags_created, r.notes_affected), (1, 1));
+    let names: Vec<String> = note_tags(&db.0.lock().unwrap(), "a")
+        .unwrap()
+        .into_iter()
+        .map(|t| t.name)
+        .collect();
+    assert_eq!(names, vec!["ideas", "project"]);
+    let body: String =
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT body FROM notes WHERE id='a'", [], |r| r.get(0))
+            .unwrap();
+    assert_eq!(body, "Working on today\n\n```\nlet x = \"#project in code\";\n#fenced\n```\nSee `#inline` and C# or #123 and http://x.com/#frag\nagain");
+    assert_eq!(
+        extract_hashtags_to_tags(st(&db), false)
+            .unwrap()
+            .notes_affected,
+        0
+    );
+}
+
+#[test]
+fn hashtag_removal_keeps_separators() {
+    let strip = |body: &str| crate::markdown::extract_hashtags(body).1;
+    assert_eq!(strip("see (#tag) now"), "see now");
+    assert_eq!(strip("(#tag) first"), "first");
+    assert_eq!(strip("both (#a #b) here"), "both here");
+    assert_eq!(strip("x #a#b"), "x #b");
+    assert_eq!(strip("x #a, y"), "x, y");
+    assert_eq!(strip("#a x"), "x");
+    let (tags, _) = crate::markdown::extract_hashtags("x #a#b (#c)");
+    assert_eq!(tags, vec!["a", "c"]);
+}
+
+#[test]
+fn untagged_notes_exclude_tagged_ones() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "a", "", 1, 0);
+    note(&db, "b", "f", "b", "", 3, 0);
+    note(&db, "c", "f", "c", "", 2, 0);
+    add_note_tag(st(&db), "a".into(), "work".into()).unwrap();
+    let ids: Vec<String> = get_untagged_notes(st(&db), 10, 0)
+        .unwrap()
+        .into_iter()
+        .map(|n| n.id)
+        .collect();
+    assert_eq!(ids, vec!["b", "c"]);
+    let ids: Vec<String> = get_untagged_notes(st(&db), 1, 1)
+        .unwrap()
+        .into_iter()
+        .map(|n| n.id)
+        .collect();
+    assert_eq!(ids, vec!["c"]);
+    // A negative limit would mean "no limit" to SQLite
+    assert_eq!(get_untagged_notes(st(&db), -1, 0).unwrap().len(), 1);
+}
+
+#[test]
+fn notes_recursive_cover_the_subtree() {
+    let db = mem_db();
+    folder(&db, "a", None);
+    folder(&db, "b", Some("a"));
+    folder(&db, "c", Some("b"));
+    folder(&db, "other", None);
+    note(&db, "n1", "a", "top", "", 1, 0);
+    note(&db, "n2", "b", "mid", "", 3, 0);
+    note(&db, "n3", "c", "deep", "", 2, 0);
+    note(&db, "n4", "other", "x", "", 9, 0);
+    let got: Vec<(String, String)> = get_notes_recursive(st(&db), "a".into(), 10, 0)
+        .unwrap()
+        .into_iter()
+        .map(|n| (n.note.id, n.folder_name))
+        .collect();
+    assert_eq!(
+        got,
+        vec![
+            ("n2".into(), "b".into()),
+            ("n3".into(), "c".into()),
+            ("n1".into(), "a".into())
+        ]
+    );
+    assert_eq!(
+        get_notes_recursive(st(&db), "b".into(), 1, 1).unwrap()[0]
+            .note
+            .id,
+        "n3"
+    );
+    assert_eq!(
+        get_notes_recursive(st(&db), "a".into(), -1, 0)
+            .unwrap()
+            .len(),
+        1
+    );
+    let json =
+        serde_json::to_value(&get_notes_recursive(st(&db), "c".into(), 10, 0).unwrap()[0]).unwrap();
+    assert_eq!(
+        (json["title"].as_str(), json["folder_name"].as_str()),
+        (Some("deep"), Some("c"))
+    );
+}
+
+#[test]
+fn advanced_search_excludes_terms() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "both", "f", "apple", "banana", 1, 0);
+    note(&db, "inc", "f", "apple pie", "", 2, 1);
+    note(&db, "none", "f", "cherry", "", 3, 2);
+    let ids = |i: &str, e: Option<&str>| -> Vec<String> {
+        search_notes_advanced(st(&db), i.into(), e.map(String::from), 10)
+            .unwrap()
+            .into_iter()
+            .map(|n| n.id)
+            .collect()
+    };
+    assert_eq!(ids("apple", Some("banana")), vec!["inc"]);
+    let mut all = ids("apple", None);
+    all.sort();
+    assert_eq!(all, vec!["both", "inc"]);
+    // FTS rejects the unbalanced quote, LIKE fallback
+    assert_eq!(ids("apple", Some("\"banana")), vec!["inc"]);
+    assert_eq!(ids("\"apple", Some("  ")), vec!["inc", "both"]);
+    assert!(ids("  ", None).is_empty());
+    assert_eq!(
+        search_notes_advanced(st(&db), "apple".into(), None, -1)
+            .unwrap()
+            .len(),
+        1
+    );
+    assert_eq!(
+        search_notes_advanced(st(&db), "\"apple".into(), None, 0)
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn stale_notes_are_oldest_first() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    let now = chrono::Utc::now().timestamp_millis();
+    let day = 86_400_000;
+    note(&db, "recent", "f", "r", "", now - day, 0);
+    note(&db, "old", "f", "o", "", now - 40 * day, 1);
+    note(&db, "older", "f", "o", "", now - 400 * day, 2);
+    note(&db, "edge", "f", "e", "", now - 29 * day, 3);
+    let ids: Vec<String> = get_stale_notes(st(&db), 10, 30)
+        .unwrap()
+        .into_iter()
+        .map(|n| n.id)
+        .collect();
+    assert_eq!(ids, vec!["older", "old"]);
+    assert_eq!(get_stale_notes(st(&db), 1, 30).unwrap()[0].id, "older");
+    assert_eq!(get_stale_notes(st(&db), -1, 30).unwrap().len(), 1);
+}
+
+#[test]
+fn fuzzy_find_tolerates_typos() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "m", "f", "Weekly meeting notes", "", 1, 0);
+    note(&db, "g", "f", "Groceries", "", 1, 1);
+    note(&db, "mm", "f", "Meeting", "", 1, 2);
+    let ids = |q: &str| -> Vec<String> {
+        fuzzy_find_notes(st(&db), q.into(), 10)
+            .unwrap()
+            .into_iter()
+            .map(|n| n.metadata.id)
+            .collect()
+    };
+    assert_eq!(ids("meetnig"), vec!["mm", "m"]);
+    assert_eq!(ids("meating"), vec!["mm", "m"]);
+    assert_eq!(ids("grocerys"), vec!["g"]);
+    assert!(ids("xyzzy").is_empty());
+    assert!(ids("  ").is_empty());
+    assert_eq!(
+        fuzzy_find_notes(st(&db), "meeting".into(), 1)
+            .unwrap()
+            .len(),
+        1
+    );
+    assert_eq!(
+        fuzzy_find_notes(st(&db), "meeting".into(), 0)
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn recent_tags_follow_application_order() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "a", "", 1, 0);
+    note(&db, "b", "f", "b", "", 1, 1);
+    for (n, t) in [("a", "one"), ("a", "two"), ("b", "three"), ("b", "one")] {
+        add_note_tag(st(&db), n.into(), t.into()).unwrap();
+    }
+    let names: Vec<String> = get_recent_tags(st(&db), 10)
+        .unwrap()
+        .into_iter()
+        .map(|t| t.name)
+        .collect();
+    assert_eq!(names, vec!["one", "three", "two"]);
+    assert_eq!(get_recent_tags(st(&db), 1).unwrap().len(), 1);
+    assert_eq!(get_recent_tags(st(&db), -1).unwrap().len(), 1);
+    let missing: i64 =
+        db.0.lock()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM note_tags WHERE applied_at IS NULL",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+    assert_eq!(missing, 0);
+}
+
+#[test]
+fn related_notes_share_keywords() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(
+        &db,
+        "a",
+        "f",
+        "Sourdough starter",
+        "Feed the sourdough starter with rye flour and water. The starter doubles.",
+        1,
+        0,
+    );
+    note(
+        &db,
+        "b",
+        "f",
+        "Baking day",
+        "Used my sourdough starter; rye flour gives a tangy loaf.",
+        1,
+        1,
+    );
+    note(
+        &db,
+        "c",
+        "f",
+        "Tax return",
+        "Gather receipts and file the return before the deadline.",
+        1,
+        2,
+    );
+    let ids = |v: Vec<NoteMetadata>| v.into_iter().map(|n| n.id).collect::<Vec<_>>();
+    assert_eq!(
+        ids(get_related_notes(st(&db), "a".into(), 5).unwrap()),
+        vec!["b"]
+    );
+    assert_eq!(
+        ids(get_related_notes(st(&db), "b".into(), 5).unwrap()),
+        vec!["a"]
+    );
+    assert_eq!(get_related_notes(st(&db), "a".into(), 0).unwrap().len(), 1);
+    assert_eq!(top_keywords("The the", "and of", 8), Vec::<String>::new());
+}
+
+#[test]
+fn title_search_prefers_short_titles() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(
+        &db,
+        "body",
+        "f",
+        "Groceries",
+        "project plan inside body",
+        1,
+        0,
+    );
+    note(&db, "long", "f", "Project planning notes for Q3", "", 1, 0);
+    note(&db, "short", "f", "Project plan", "", 1, 0);
+    note(&db, "other", "f", "Projector", "", 1, 0);
+    let ids = |v: Vec<NoteMetadata>| v.into_iter().map(|n| n.id).collect::<Vec<_>>();
+    assert_eq!(
+        ids(search_titles(st(&db), "project plan".into(), 10).unwrap()),
+        vec!["short", "long"]
+    );
+    assert_eq!(
+        ids(search_titles(st(&db), "proj".into(), 10).unwrap()),
+        vec!["other", "short", "long"]
+    );
+    assert_eq!(
+        ids(search_titles(st(&db), "\"plan*)".into(), 10).unwrap()),
+        vec!["short", "long"]
+    );
+    assert_eq!(search_titles(st(&db), "proj".into(), -1).unwrap().len(), 1);
+    assert!(search_titles(st(&db), "  -- ".into(), 10)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn inbox_notes_follow_the_setting() {
+    let db = mem_db();
+    assert!(get_inbox_notes(st(&db), 10).unwrap().is_empty());
+    folder(&db, "inbox", None);
+    db.0.lock()
+        .unwrap()
+        .execute("UPDATE folders SET name = 'inbox' WHERE id = 'inbox'", [])
+        .unwrap();
+    folder(&db, "cap", None);
+    note(&db, "a", "inbox", "a", "", 1, 2);
+    note(&db, "b", "inbox", "b", "", 3, 0);
+    note(&db, "c", "inbox", "c", "", 2, 1);
+    let ids = |v: Vec<NoteMetadata>| v.into_iter().map(|n| n.id).collect::<Vec<_>>();
+    assert_eq!(
+        ids(get_inbox_notes(st(&db), 10).unwrap()),
+        vec!["b", "c", "a"]
+    );
+    assert_eq!(get_inbox_notes(st(&db), -1).unwrap().len(), 1);
+    set_inbox_folder(st(&db), "cap".into()).unwrap();
+    note(&db, "d", "cap", "d", "", 5, 0);
+    assert_eq!(ids(get_inbox_notes(st(&db), 10).unwrap()), vec!["d"]);
+    assert!(set_inbox_folder(st(&db), "nope".into()).is_err());
+    delete_folder(st(&db), "cap".into(), Some("delete".into())).unwrap();
+    assert_eq!(get_inbox_folder(st(&db)).unwrap().as_deref(), Some("inbox"));
+}
+
+#[test]
+fn notes_in_folders_merge_folders() {
+    let db = mem_db();
+    folder(&db, "a", None);
+    folder(&db, "b", None);
+    folder(&db, "c", None);
+    folder(&db, "s", Some("a"));
+    note(&db, "n1", "a", "", "", 1, 0);
+    note(&db, "n2", "b", "", "", 3, 0);
+    note(&db, "n3", "c", "", "", 2, 0);
+    note(&db, "n4", "s", "", "", 4, 0);
+    let ids = |v: Vec<NoteMetadata>| v.into_iter().map(|n| n.id).collect::<Vec<_>>();
+    assert_eq!(
+        ids(get_notes_in_folders(st(&db), vec!["a".into(), "b".into()], 10, 0, None).unwrap()),
+        vec!["n2", "n1"]
+    );
+    assert_eq!(
+        ids(
+            get_notes_in_folders(st(&db), vec!["a".into(), "b".into()], 10, 0, Some(true)).unwrap()
+        ),
+        vec!["n4", "n2", "n1"]
+    );
+    assert_eq!(
+        ids(get_notes_in_folders(st(&db), vec!["a".into(), "s".into()], 1, 1, Some(true)).unwrap()),
+        vec!["n1"]
+    );
+    assert_eq!(
+        get_notes_in_folders(st(&db), vec!["a".into(), "b".into()], -1, 0, None)
+            .unwrap()
+            .len(),
+        1
+    );
+    assert!(get_notes_in_folders(st(&db), vec!["a'".into()], 10, 0, None).is_err());
+}
+
+#[test]
+fn folder_rename_can_bump_descendant_notes() {
+    let db = mem_db();
+    folder(&db, "top", None);
+    folder(&db, "mid", Some("top"));
+    folder(&db, "leaf", Some("mid"));
+    folder(&db, "other", None);
+    note(&db, "a", "leaf", "a", "", 5, 0);
+    note(&db, "b", "other", "b", "", 5, 0);
+    rename_folder(st(&db), "top".into(), "Top2".into(), None).unwrap();
+    let q = |id: &str| -> i64 {
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT updated_at FROM notes WHERE id=?1", [id], |r| {
+                r.get(0)
+            })
+            .unwrap()
+    };
+    assert_eq!(q("a"), 5);
+    rename_folder(st(&db), "top".into(), "Top3".into(), Some(true)).unwrap();
+    assert!(q("a") > 5);
+    assert_eq!(q("b"), 5);
+    // A failed bump leaves the old name in place
+    db.0.lock().unwrap().execute_batch("CREATE TEMP TRIGGER no_bump BEFORE UPDATE OF updated_at ON notes BEGIN SELECT RAISE(ABORT, 'no'); END;").unwrap();
+    assert!(rename_folder(st(&db), "top".into(), "Top4".into(), Some(true)).is_err());
+    let name: String =
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT name FROM folders WHERE id = 'top'", [], |r| {
+                r.get(0)
+            })
+            .unwrap();
+    assert_eq!(name, "Top3");
+}
+
+#[test]
+fn refine_search_narrows_earlier_results() {
+    let db = mem_db();
+    folder(&db, "f1", None);
+    note(&db, "a", "f1", "apple pie", "sweet", 1, 0);
+    note(&db, "b", "f1", "apple tart", "sour", 2, 1);
+    note(&db, "c", "f1", "banana", "sweet", 3, 2);
+    let broad: Vec<String> = search_notes(st(&db), "apple".into(), None)
+        .unwrap()
+        .into_iter()
+        .map(|n| n.id)
+        .collect();
+    assert_eq!(broad.len(), 2);
+    let r = refine_search(st(&db), "sweet".into(), broad, None).unwrap();
+    assert_eq!(r.len(), 1);
+    assert_eq!(r[0].id, "a");
+    assert!(refine_search(st(&db), "x".into(), vec!["a'b".into()], None).is_err());
+}
+
+#[test]
+fn split_note_by_heading_skips_fenced_headings() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(
+        &db,
+        "x",
+        "f",
+        "Orig",
+        "# One\nbody1\n\n# Two\n```\n# not heading\n```\n# Three\nbody3  #\n",
+        1,
+        0,
+    );
+    note(&db, "y", "f", "After", "", 1, 1);
+    let ids = split_note_by_heading(st(&db), "x".into(), 1, None).unwrap();
+    assert_eq!(ids.len(), 3);
+    let conn = db.0.lock().unwrap();
+    let rows: Vec<(String, String, i32)> = conn
+        .prepare("SELECT title, body, sort_order FROM notes ORDER BY sort_order")
+        .unwrap()
+        .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        rows.iter().map(|r| r.0.as_str()).collect::<Vec<_>>(),
+        vec!["One", "Two", "Three", "After"]
+    );
+    assert_eq!(rows[1].1, "```\n# not heading\n```");
+    assert!(generate_id().chars().all(|c| c.is_ascii_alphanumeric()));
+    assert_ne!(generate_id(), generate_id());
+    assert_eq!(markdown::atx_heading("## Hi ##"), Some((2, "Hi")));
+    assert_eq!(markdown::atx_heading("#Hi"), None);
+    assert_eq!(markdown::atx_heading("# C#"), Some((1, "C#")));
+}
+
+#[test]
+fn notes_index_lists_every_note() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "x", "f", "T", "body", 1, 0);
+    let r = get_notes_index(st(&db)).unwrap();
+    assert_eq!(r.len(), 1);
+}
+
+#[test]
+fn folder_order_is_normalized_per_parent() {
+    let db = mem_db();
+    folder(&db, "a", None);
+    folder(&db, "b", None);
+    folder(&db, "c", Some("a"));
+    folder(&db, "d", Some("a"));
+    {
+        let c = db.0.lock().unwrap();
+        let so: Vec<i32> = c
+            .prepare("SELECT sort_order FROM folders ORDER BY id")
+            .unwrap()
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(so, vec![0, 1, 0, 1]);
+        c.execute("UPDATE folders SET sort_order = 5", []).unwrap();
+    }
+    normalize_folder_order(st(&db), None, Some(true)).unwrap();
+    let c = db.0.lock().unwrap();
+    let so: Vec<i32> = c
+        .prepare("SELECT sort_order FROM folders ORDER BY id")
+        .unwrap()
+        .query_map([], |r| r.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(so, vec![0, 1, 0, 1]);
+    let v: i32 = c
+        .pragma_query_value(None, "user_version", |r| r.get(0))
+        .unwrap();
+    assert!(v >= 3);
+}
+
+#[test]
+fn broken_wiki_links_skip_code_and_resolved_links() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(
+        &db,
+        "a",
+        "f",
+        "Alpha",
+        "see [[alpha]] and [[Nope|x]] `[[code]]`\n```\n[[fenced]]\n```\n[[b]]",
+        1,
+        0,
+    );
+    note(&db, "b", "f", "Beta", "", 1, 1);
+    let r = find_broken_wiki_links(st(&db)).unwrap();
+    assert_eq!(r.len(), 1);
+    assert_eq!(r[0].link_text, "Nope");
+}
+
+#[test]
+fn rename_note_checks_the_expected_timestamp() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "Old", "keep me", 10, 0);
+    let ts = rename_note(st(&db), "a".into(), "New".into(), Some(10)).unwrap();
+    assert!(ts > 10);
+    assert!(rename_note(st(&db), "a".into(), "X".into(), Some(10)).is_err());
+    assert!(rename_note(st(&db), "zz".into(), "X".into(), None).is_err());
+    assert_eq!(get_note_body(st(&db), "a".into()).unwrap(), "keep me");
+}
+
+#[test]
+fn db_diagnostics_report_schema_and_fts() {
+    let db = mem_db();
+    assert!(get_schema_version(st(&db)).unwrap() >= 3);
+    let d = get_db_diagnostics(st(&db)).unwrap();
+    assert!(d.fts_ok);
+    assert!(d.db_path.is_none());
+    assert!(d.page_count > 0);
+}
+
+#[test]
+fn note_from_markdown_takes_the_heading_as_title() {
+    assert_eq!(
+        title_from_markdown("\n# Hello\n\nworld"),
+        ("Hello".to_string(), "world".to_string())
+    );
+    assert_eq!(
+        title_from_markdown("just text\nmore"),
+        ("just text".to_string(), "just text\nmore".to_string())
+    );
+    assert_eq!(
+        title_from_markdown(""),
+        ("Untitled".to_string(), "".to_string())
+    );
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "A", "", 1, 0);
+    let id = create_note_from_markdown(st(&db), "f".into(), "# T\nbody".into()).unwrap();
+    let c = db.0.lock().unwrap();
+    let so: i32 = c
+        .query_row("SELECT sort_order FROM notes WHERE id='a'", [], |r| {
+            r.get(0)
+        })
+        .unwrap();
+    assert_eq!(so, 1);
+    let t: String = c
+        .query_row("SELECT title FROM notes WHERE id=?1", [&id], |r| r.get(0))
+        .unwrap();
+    assert_eq!(t, "T");
+}
+
+#[test]
+fn folder_sort_mode_orders_notes() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "b-title", "", 1, 0);
+    note(&db, "b", "f", "A-title", "", 3, 1);
+    note(&db, "c", "f", "c-title", "", 2, 2);
+    let ids = |db: &TestDb| {
+        get_notes_metadata(st(db), None, None)
+            .unwrap()
+            .into_iter()
+            .map(|n| n.id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ids(&db), vec!["a", "b", "c"]);
+    assert!(set_folder_sort_mode(st(&db), "f".into(), "bogus".into()).is_err());
+    set_folder_sort_mode(st(&db), "f".into(), "updated_desc".into()).unwrap();
+    assert_eq!(ids(&db), vec!["b", "c", "a"]);
+    set_folder_sort_mode(st(&db), "f".into(), "title_asc".into()).unwrap();
+    assert_eq!(ids(&db), vec!["b", "a", "c"]);
+    set_folder_sort_mode(st(&db), "f".into(), "created_desc".into()).unwrap();
+    assert_eq!(ids(&db), vec!["b", "c", "a"]);
+    assert!(reorder_notes(st(&db), vec![("a".into(), 5)]).is_err());
+    set_folder_sort_mode(st(&db), "f".into(), "manual".into()).unwrap();
+    reorder_notes(st(&db), vec![("a".into(), 5)]).unwrap();
+    assert_eq!(get_folders(st(&db)).unwrap()[0].sort_mode, "manual");
+}
+
+#[test]
+fn folder_counts_follow_moves_and_deletes() {
+    let db = mem_db();
+    folder(&db, "a", None);
+    folder(&db, "b", Some("a"));
+    folder(&db, "c", Some("b"));
+    folder(&db, "d", None);
+    note(&db, "n1", "c", "", "", 1, 0);
+    note(&db, "n2", "b", "", "", 1, 0);
+    note(&db, "n3", "d", "", "", 1, 0);
+    let snap = |db: &TestDb| {
+        let mut v: Vec<(String, i64, i64)> = get_folder_counts(st(db))
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.folder_id, c.direct_count, c.total_count))
+            .collect();
+        v.sort();
+        v
+    };
+    assert_eq!(
+        snap(&db),
+        vec![
+            ("a".into(), 0, 2),
+            ("b".into(), 1, 2),
+            ("c".into(), 1, 1),
+            ("d".into(), 1, 1)
+        ]
+    );
+    db.0.lock()
+        .unwrap()
+        .execute("UPDATE notes SET folder_id='d' WHERE id='n1'", [])
+        .unwrap();
+    assert_eq!(
+        snap(&db),
+        vec![
+            ("a".into(), 0, 1),
+            ("b".into(), 1, 1),
+            ("c".into(), 0, 0),
+            ("d".into(), 2, 2)
+        ]
+    );
+    db.0.lock()
+        .unwrap()
+        .execute("UPDATE folders SET parent_id='d' WHERE id='b'", [])
+        .unwrap();
+    assert_eq!(
+        snap(&db),
+        vec![
+            ("a".into(), 0, 0),
+            ("b".into(), 1, 1),
+            ("c".into(), 0, 0),
+            ("d".into(), 2, 3)
+        ]
+    );
+    delete_note(st(&db), "n2".into()).unwrap();
+    let inc = snap(&db);
+    recompute_folder_counts(st(&db)).unwrap();
+    assert_eq!(inc, snap(&db));
+    delete_folder(st(&db), "b".into(), Some("delete".into())).unwrap();
+    let inc = snap(&db);
+    recompute_folder_counts(st(&db)).unwrap();
+    assert_eq!(inc, snap(&db));
+}
+
+#[test]
+fn notes_move_up_and_down_within_their_group() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "", "", 1, 0);
+    note(&db, "b", "f", "", "", 1, 1);
+    note(&db, "c", "f", "", "", 1, 2);
+    assert!(move_note_up(st(&db), "b".into()).unwrap());
+    assert_eq!(order(&db, "f"), vec!["b", "a", "c"]);
+    assert!(move_note_down(st(&db), "b".into()).unwrap());
+    assert!(move_note_down(st(&db), "b".into()).unwrap());
+    assert_eq!(order(&db, "f"), vec!["a", "c", "b"]);
+    assert!(!move_note_down(st(&db), "b".into()).unwrap());
+    assert!(!move_note_up(st(&db), "a".into()).unwrap());
+    db.0.lock()
+        .unwrap()
+        .execute("UPDATE notes SET sort_order = 0", [])
+        .unwrap();
+    assert!(move_note_up(st(&db), "c".into()).unwrap());
+    assert_eq!(order(&db, "f"), vec!["a", "c", "b"]);
+}
+
+#[test]
+fn ensure_folder_path_reuses_existing_folders() {
+    let db = mem_db();
+    let a = ensure_folder_path(st(&db), "Projects/Alpha/Specs".into()).unwrap();
+    let b = ensure_folder_path(st(&db), " Projects / Alpha/Specs/".into()).unwrap();
+    assert_eq!(a, b);
+    assert_eq!(get_folders(st(&db)).unwrap().len(), 3);
+    assert!(ensure_folder_path(st(&db), "//".into()).is_err());
+}
+
+#[test]
+fn import_report_lists_skipped_conflicts() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "", "same", 1, 0);
+    note(&db, "b", "f", "", "old", 1, 0);
+    let r = import_data_report(
+        st(&db),
+        vec![],
+        vec![
+            mk_note("a", "f", "same"),
+            mk_note("b", "f", "new"),
+            mk_note("c", "f", "x"),
+        ],
+    )
+    .unwrap();
+    assert_eq!(r.inserted_notes, 1);
+    assert_eq!(r.skipped_conflicts.len(), 2);
+    assert!(!r.skipped_conflicts[0].differs && r.skipped_conflicts[1].differs);
+}
+
+#[test]
+fn set_note_tags_replaces_the_whole_set() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "", "", 1, 0);
+    let t = add_note_tag(st(&db), "a".into(), "one".into()).unwrap();
+    let t2 = add_note_tag(st(&db), "a".into(), "two".into()).unwrap();
+    assert_eq!(t2.len(), 2);
+    let three = {
+        let c = db.0.lock().unwrap();
+        ensure_tag(&c, "three").unwrap()
+    };
+    let r = set_note_tags(st(&db), "a".into(), vec![t[0].id.clone(), three]).unwrap();
+    assert_eq!(
+        r.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(),
+        vec!["one", "three"]
+    );
+    assert!(set_note_tags(st(&db), "a".into(), vec!["nope".into()]).is_err());
+    assert_eq!(get_note_tags(st(&db), "a".into()).unwrap().len(), 2);
+}
+
+#[test]
+fn preview_length_is_clamped() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "t", "0123456789", 1, 0);
+    assert_eq!(
+        get_notes_metadata(st(&db), Some(4), None).unwrap()[0].preview,
+        "0123"
+    );
+    assert_eq!(
+        get_notes_metadata(st(&db), Some(0), None).unwrap()[0].preview,
+        ""
+    );
+    assert_eq!(
+        get_notes_metadata(st(&db), Some(-5), None).unwrap()[0].preview,
+        ""
+    );
+    assert_eq!(
+        search_notes(st(&db), "t".into(), Some(2)).unwrap()[0].preview,
+        "01"
+    );
+}
+
+#[test]
+fn subtree_folder_ids_are_breadth_first() {
+    let db = mem_db();
+    folder(&db, "r", None);
+    folder(&db, "a", Some("r"));
+    folder(&db, "b", Some("a"));
+    folder(&db, "c", Some("r"));
+    folder(&db, "x", None);
+    note(&db, "n", "b", "n", "", 1, 0);
+    assert_eq!(
+        get_subtree_folder_ids(st(&db), "r".into()).unwrap(),
+        vec!["r", "a", "c", "b"]
+    );
+    assert!(get_subtree_folder_ids(st(&db), "zz".into())
+        .unwrap()
+        .is_empty());
+    delete_folder(st(&db), "a".into(), Some("delete".into())).unwrap();
+    assert_eq!(
+        get_subtree_folder_ids(st(&db), "r".into()).unwrap(),
+        vec!["r", "c"]
+    );
+    let conn = db.0.lock().unwrap();
+    let n: i64 = conn
+        .query_row("SELECT count(*) FROM notes", [], |r| r.get(0))
+        .unwrap();
+    assert_eq!(n, 0);
+}
+
+#[test]
+fn notes_move_to_top_and_bottom() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "a", "", 1, 5);
+    note(&db, "b", "f", "b", "", 1, 9);
+    note(&db, "c", "f", "c", "", 1, 12);
+    note(&db, "p", "f", "p", "", 1, 0);
+    toggle_note_pinned(st(&db), "p".into(), 1).unwrap();
+    move_note_to_top(st(&db), "c".into()).unwrap();
+    assert_eq!(order(&db, "f"), vec!["p", "c", "a", "b"]);
+    move_note_to_bottom(st(&db), "c".into()).unwrap();
+    assert_eq!(order(&db, "f"), vec!["p", "a", "b", "c"]);
+    let orders: Vec<i32> =
+        db.0.lock()
+            .unwrap()
+            .prepare("SELECT sort_order FROM notes WHERE pinned = 0 ORDER BY sort_order")
+            .unwrap()
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+    assert_eq!(orders, vec![0, 1, 2]);
+}
+
+#[test]
+fn streamed_search_matches_search_notes() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    for i in 0..5 {
+        note(
+            &db,
+            &format!("n{}", i),
+            "f",
+            "apple",
+            &"apple ".repeat(i + 1),
+            1,
+            0,
+        );
+    }
+    note(&db, "x", "f", "pear", "pear", 1, 0);
+    let sent = std::sync::Arc::new(Mutex::new(Vec::new()));
+    let sink = sent.clone();
+    let ch = tauri::ipc::Channel::new(move |body| {
+        if let tauri::ipc::InvokeResponseBody::Json(json) = body {
+            sink.lock()
+                .unwrap()
+                .push(serde_json::from_str::<serde_json::Value>(&json).unwrap());
+        }
+        Ok(())
+    });
+    search_notes_streamed(st(&db), "apple".into(), None, ch).unwrap();
+    let events = sent.lock().unwrap().clone();
+    let expected: Vec<serde_json::Value> = search_notes(st(&db), "apple".into(), None)
+        .unwrap()
+        .iter()
+        .map(|e| serde_json::to_value(e).unwrap())
+        .collect();
+    assert_eq!(&events[..5], &expected[..]);
+    assert_eq!(events[5], serde_json::json!({"done": true, "total": 5}));
+}
+
+#[test]
+fn clean_note_bodies_normalizes_whitespace() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    let body = "a  \n\n\n\n\nb\t\n```\ncode  \n\n\n\n\nx \n```\nend ";
+    note(&db, "a", "f", "a", body, 1, 0);
+    note(&db, "b", "f", "b", "clean\n\nok\n", 1, 0);
+    let r = clean_note_bodies(st(&db), true).unwrap();
+    assert_eq!((r.scanned, r.changed), (2, 1));
+    assert_eq!(get_note_body(st(&db), "a".into()).unwrap(), body);
+    let r = clean_note_bodies(st(&db), false).unwrap();
+    assert_eq!(r.note_ids, vec!["a"]);
+    assert_eq!(
+        get_note_body(st(&db), "a".into()).unwrap(),
+        "a\n\n\nb\n```\ncode  \n\n\n\n\nx \n```\nend"
+    );
+    assert_eq!(get_note_body(st(&db), "b".into()).unwrap(), "clean\n\nok\n");
+}
+
+#[test]
+fn integrity_check_finds_orphaned_tag_rows() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "a", "", 1, 0);
+    add_note_tag(st(&db), "a".into(), "keep".into()).unwrap();
+    {
+        let conn = db.0.lock().unwrap();
+        conn.execute_batch("PRAGMA foreign_keys = OFF; INSERT INTO note_tags (note_id, tag_id) VALUES ('ghost', (SELECT id FROM tags)); PRAGMA foreign_keys = ON;").unwrap();
+    }
+    let problems = check_integrity(st(&db)).unwrap();
+    assert_eq!(problems.len(), 1, "{:?}", problems);
+    assert!(problems[0].starts_with("note_tags ghost/"));
+    assert_eq!(repair_note_tags(st(&db)).unwrap(), 1);
+    assert!(check_integrity(st(&db)).unwrap().is_empty());
+    assert_eq!(get_note_tags(st(&db), "a".into()).unwrap().len(), 1);
+}
+
+#[test]
+fn tree_stats_count_folders_and_depth() {
+    let db = mem_db();
+    let s = get_tree_stats(st(&db)).unwrap();
+    assert_eq!(
+        (s.max_depth, s.folder_count, s.max_children, s.avg_children),
+        (0, 0, 0, 0.0)
+    );
+    folder(&db, "r", None);
+    folder(&db, "a", Some("r"));
+    folder(&db, "b", Some("r"));
+    folder(&db, "c", Some("r"));
+    folder(&db, "d", Some("a"));
+    folder(&db, "x", None);
+    let s = get_tree_stats(st(&db)).unwrap();
+    assert_eq!((s.max_depth, s.folder_count, s.max_children), (3, 6, 3));
+    assert!((s.avg_children - 4.0 / 6.0).abs() < 1e-9);
+}
+
+#[test]
+fn local_ranges_follow_the_local_day() {
+    use chrono::TimeZone;
+    let tz = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+    // 2026-03-11 (Wednesday) 00:10 local == 2026-03-10 22:10 UTC
+    let now = tz.with_ymd_and_hms(2026, 3, 11, 0, 10, 0).unwrap();
+    let (s, e) = local_range_bounds("today", now).unwrap();
+    let created = now.timestamp_millis() - 5 * 60 * 1000; // 00:05 local, still "yesterday" in UTC
+    assert!(created >= s && created < e);
+    assert_eq!(e - s, 86_400_000);
+    let (ys, ye) = local_range_bounds("yesterday", now).unwrap();
+    assert_eq!((ye, ys), (s, s - 86_400_000));
+    let (ws, we) = local_range_bounds("this_week", now).unwrap();
+    assert_eq!(
+        ws,
+        tz.with_ymd_and_hms(2026, 3, 9, 0, 0, 0)
+            .unwrap()
+            .timestamp_millis()
+    );
+    assert_eq!(we - ws, 7 * 86_400_000);
+    let (ms, me) = local_range_bounds("this_month", now).unwrap();
+    assert_eq!(
+        ms,
+        tz.with_ymd_and_hms(2026, 3, 1, 0, 0, 0)
+            .unwrap()
+            .timestamp_millis()
+    );
+    assert_eq!(
+        me,
+        tz.with_ymd_and_hms(2026, 4, 1, 0, 0, 0)
+            .unwrap()
+            .timestamp_millis()
+    );
+    assert!(local_range_bounds("decade", now).is_err());
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(
+        &db,
+        "a",
+        "f",
+        "",
+        "",
+        chrono::Utc::now().timestamp_millis(),
+        0,
+    );
+    note(&db, "b", "f", "", "", 1, 0);
+    let ids: Vec<String> = get_notes_in_local_range(st(&db), "today".into())
+        .unwrap()
+        .into_iter()
+        .map(|n| n.id)
+        .collect();
+    assert_eq!(ids, vec!["a"]);
+}
+
+#[test]
+fn set_notes_pinned_keeps_group_order() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    folder(&db, "g", None);
+    note(&db, "a", "f", "a", "", 1, 0);
+    note(&db, "b", "f", "b", "", 1, 1);
+    note(&db, "c", "f", "c", "", 1, 2);
+    note(&db, "x", "g", "x", "", 1, 0);
+    note(&db, "y", "g", "y", "", 1, 1);
+    toggle_note_pinned(st(&db), "b".into(), 1).unwrap();
+    assert_eq!(
+        set_notes_pinned(st(&db), vec!["c".into(), "y".into(), "b".into()], 1).unwrap(),
+        2
+    );
+    assert_eq!(order(&db, "f"), vec!["b", "c", "a"]);
+    assert_eq!(order(&db, "g"), vec!["y", "x"]);
+    let conn = db.0.lock().unwrap();
+    let rows: Vec<(String, i32, i32)> = conn
+        .prepare("SELECT id, pinned, sort_order FROM notes ORDER BY id")
+        .unwrap()
+        .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+    drop(conn);
+    assert_eq!(
+        rows,
+        vec![
+            ("a".into(), 0, 0),
+            ("b".into(), 1, 0),
+            ("c".into(), 1, 1),
+            ("x".into(), 0, 0),
+            ("y".into(), 1, 0)
+        ]
+    );
+    assert_eq!(set_notes_pinned(st(&db), vec!["c".into()], 0).unwrap(), 1);
+    assert_eq!(order(&db, "f"), vec!["b", "a", "c"]);
+    assert!(set_notes_pinned(st(&db), vec!["a".into(), "nope".into()], 1).is_err());
+    assert_eq!(order(&db, "f"), vec!["b", "a", "c"]);
+}
+
+#[test]
+fn explain_search_reports_the_query_plan() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "hello", "world", 1, 0);
+    let plan = explain_search(st(&db), "hello".into()).unwrap();
+    assert!(plan.iter().any(|l| l.contains("notes_fts")));
+}
+
+#[test]
+fn maintenance_mode_blocks_writes() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "a", "", 1, 0);
+    begin_maintenance(st(&db)).unwrap();
+    assert_eq!(
+        begin_maintenance(st(&db)).unwrap_err(),
+        "maintenance in progress"
+    );
+    assert_eq!(
+        toggle_note_pinned(st(&db), "a".into(), 1).unwrap_err(),
+        "maintenance in progress"
+    );
+    assert_eq!(
+        create_note_from_markdown(st(&db), "f".into(), "x".into()).unwrap_err(),
+        "maintenance in progress"
+    );
+    // Maintenance ops still run, and leave the window open
+    repair_note_tags(st(&db)).unwrap();
+    assert!(db.1.load(Ordering::SeqCst));
+    assert_eq!(get_notes_metadata(st(&db), None, None).unwrap().len(), 1);
+    end_maintenance(st(&db));
+    toggle_note_pinned(st(&db), "a".into(), 1).unwrap();
+    // A standalone maintenance op clears the flag even when it fails
+    {
+        let _g = db.maintenance();
+        assert!(db.write().is_err());
+    }
+    assert!(db.write().is_ok());
+    assert!(import_data(
+        st(&db),
+        vec![],
+        vec![mk_note("n", "missing", "")],
+        None,
+        None,
+        None
+    )
+    .is_err());
+    assert!(!db.1.load(Ordering::SeqCst));
+}
+
+#[test]
+fn largest_notes_are_ordered_by_size() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "small", "f", "s", "abc", 1, 0);
+    note(&db, "multi", "f", "m", "ééééé", 1, 1);
+    note(&db, "big", "f", "b", &"x".repeat(50), 1, 2);
+    note(&db, "empty", "f", "e", "", 1, 3);
+    let sizes: Vec<(String, i64)> = get_largest_notes(st(&db), 3)
+        .unwrap()
+        .into_iter()
+        .map(|n| (n.id, n.byte_size))
+        .collect();
+    assert_eq!(
+        sizes,
+        vec![
+            ("big".into(), 50),
+            ("multi".into(), 10),
+            ("small".into(), 3)
+        ]
+    );
+    assert_eq!(get_largest_notes(st(&db), 0).unwrap().len(), 1);
+}
+
+#[test]
+fn clone_folder_copies_the_subtree() {
+    let db = mem_db();
+    folder(&db, "p", None);
+    folder(&db, "c", Some("p"));
+    folder(&db, "dest", None);
+    note(&db, "n1", "p", "one", "body1", 1, 0);
+    note(&db, "n2", "p", "two", "body2", 1, 1);
+    note(&db, "n3", "c", "three", "body3", 1, 0);
+    add_note_tag(st(&db), "n3".into(), "tpl".into()).unwrap();
+    let root = clone_folder(st(&db), "p".into(), Some("dest".into()), true).unwrap();
+    let conn = db.0.lock().unwrap();
+    let (name, parent): (String, Option<String>) = conn
+        .query_row(
+            "SELECT name, parent_id FROM folders WHERE id = ?1",
+            [&root],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!((name.as_str(), parent.as_deref()), ("p", Some("dest")));
+    let child: String = conn
+        .query_row(
+            "SELECT id FROM folders WHERE parent_id = ?1",
+            [&root],
+            |r| r.get(0),
+        )
+        .unwrap();
+    let count = |f: &str| -> i64 {
+        conn.query_row(
+            "SELECT COUNT(*) FROM notes WHERE folder_id = ?1",
+            [f],
+            |r| r.get(0),
+        )
+        .unwrap()
+    };
+    assert_eq!((count(&root), count(&child)), (2, 1));
+    let (title, body): (String, String) = conn
+        .query_row(
+            "SELECT title, body FROM notes WHERE folder_id = ?1",
+            [&child],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!((title.as_str(), body.as_str()), ("three", "body3"));
+    let tagged: i64 = conn
+        .query_row("SELECT COUNT(*) FROM note_tags", [], |r| r.get(0))
+        .unwrap();
+    assert_eq!(tagged, 2);
+    assert_eq!(
+        conn.query_row("SELECT COUNT(*) FROM notes", [], |r| r.get::<_, i64>(0))
+            .unwrap(),
+        6
+    );
+    drop(conn);
+    let titles: Vec<String> = order(&db, &root)
+        .iter()
+        .map(|id| {
+            db.0.lock()
+                .unwrap()
+                .query_row("SELECT title FROM notes WHERE id = ?1", [id], |r| r.get(0))
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(titles, vec!["one", "two"]);
+    let shallow = clone_folder(st(&db), "p".into(), None, false).unwrap();
+    assert_eq!(
+        subtree_folder_ids(&db.0.lock().unwrap(), &shallow)
+            .unwrap()
+            .len(),
+        1
+    );
+    assert!(clone_folder(st(&db), "nope".into(), None, true).is_err());
+}
+
+#[test]
+fn notes_feed_pages_by_keyset() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    {
+        let conn = db.0.lock().unwrap();
+        for i in 0..1000 {
+            conn.execute("INSERT INTO notes (id, folder_id, title, body, created_at, updated_at) VALUES (?1, 'f', '', '', 0, ?2)",
+                rusqlite::params![format!("n{:04}", i), (i % 37) as i64]).unwrap();
+        }
+    }
+    let mut seen = Vec::new();
+    let mut cursor = None;
+    let mut pages = 0;
+    loop {
+        let (page, next) = get_notes_feed(st(&db), cursor, 64).unwrap();
+        pages += 1;
+        seen.extend(page.into_iter().map(|n| (n.updated_at, n.id)));
+        match next {
+            Some(c) => cursor = Some(c),
+            None => break,
+        }
+    }
+    assert_eq!(pages, 16);
+    assert_eq!(seen.len(), 1000);
+    let mut sorted = seen.clone();
+    sorted.sort_by(|a, b| b.cmp(a));
+    sorted.dedup();
+    assert_eq!(sorted, seen);
+    let plan: String = db.0.lock().unwrap().query_row("EXPLAIN QUERY PLAN SELECT id FROM notes n WHERE (n.updated_at, n.id) < (5, 'x') ORDER BY n.updated_at DESC, n.id DESC LIMIT 3", [], |r| r.get(3)).unwrap();
+    assert!(plan.contains("idx_notes_updated"), "{}", plan);
+}
+
+#[test]
+fn set_note_timestamps_validates_order() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "a", "", 500, 0);
+    set_note_timestamps(st(&db), "a".into(), Some(100), Some(200)).unwrap();
+    let get = || -> (i64, i64) {
+        db.0.lock()
+            .unwrap()
+            .query_row(
+                "SELECT created_at, updated_at FROM notes WHERE id = 'a'",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap()
+    };
+    assert_eq!(get(), (100, 200));
+    assert!(set_note_timestamps(st(&db), "a".into(), Some(300), Some(200)).is_err());
+    assert!(set_note_timestamps(st(&db), "a".into(), Some(300), None).is_err());
+    assert!(set_note_timestamps(st(&db), "a".into(), Some(-1), None).is_err());
+    set_note_timestamps(st(&db), "a".into(), None, Some(900)).unwrap();
+    assert_eq!(get(), (100, 900));
+    assert!(set_note_timestamps(st(&db), "zz".into(), Some(1), None).is_err());
+}
+
+#[test]
+fn bulk_rename_tags_merges_collisions() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "a", "", 1, 0);
+    note(&db, "b", "f", "b", "", 1, 1);
+    add_note_tag(st(&db), "a".into(), "wrk".into()).unwrap();
+    add_note_tag(st(&db), "b".into(), "wrk".into()).unwrap();
+    add_note_tag(st(&db), "a".into(), "work".into()).unwrap();
+    add_note_tag(st(&db), "b".into(), "todo".into()).unwrap();
+    let r = bulk_rename_tags(
+        st(&db),
+        vec![
+            ("wrk".into(), "Work".into()),
+            ("todo".into(), "tasks".into()),
+            ("nope".into(), "x".into()),
+        ],
+    )
+    .unwrap();
+    let flags: Vec<(bool, bool, bool)> = r
+        .iter()
+        .map(|e| (e.renamed, e.merged, e.not_found))
+        .collect();
+    assert_eq!(
+        flags,
+        vec![
+            (false, true, false),
+            (true, false, false),
+            (false, false, true)
+        ]
+    );
+    let names = |id: &str| -> Vec<String> {
+        note_tags(&db.0.lock().unwrap(), id)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect()
+    };
+    assert_eq!(names("a"), vec!["work"]);
+    assert_eq!(names("b"), vec!["tasks", "work"]);
+    let count: i64 =
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM tags", [], |r| r.get(0))
+            .unwrap();
+    assert_eq!(count, 2);
+    let r = bulk_rename_tags(st(&db), vec![("work".into(), "Work".into())]).unwrap();
+    assert!(r[0].renamed);
+    assert_eq!(names("a"), vec!["Work"]);
+}
+
+#[test]
+fn link_cycles_include_self_links() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "A", "to [[B]]", 1, 0);
+    note(&db, "b", "f", "B", "back to [[a]] and [[B]]", 2, 1);
+    note(&db, "x", "f", "X", "[[Y]]", 3, 2);
+    note(&db, "y", "f", "Y", "[[Z]] and `[[X]]`", 4, 3);
+    note(&db, "z", "f", "Z", "end [[missing]]", 5, 4);
+    let cycles = find_link_cycles(st(&db)).unwrap();
+    assert_eq!(
+        cycles,
+        vec![
+            vec!["a".to_string(), "b".to_string()],
+            vec!["b".to_string()]
+        ]
+    );
+}
+
+#[test]
+fn tag_report_counts_notes_per_tag() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "a", "one two three", 1, 0);
+    note(&db, "b", "f", "b", "four  five\nsix seven", 1, 1);
+    note(&db, "c", "f", "c", "x", 1, 2);
+    for (n, t) in [("a", "essay"), ("b", "essay"), ("c", "poem")] {
+        add_note_tag(st(&db), n.into(), t.into()).unwrap();
+    }
+    db.0.lock()
+        .unwrap()
+        .execute(
+            "INSERT INTO tags (id, name, created_at) VALUES ('t9', 'unused', 0)",
+            [],
+        )
+        .unwrap();
+    let r: Vec<(String, i64, i64)> = get_tag_report(st(&db))
+        .unwrap()
+        .into_iter()
+        .map(|t| (t.tag, t.note_count, t.total_words))
+        .collect();
+    assert_eq!(
+        r,
+        vec![
+            ("essay".into(), 2, 7),
+            ("poem".into(), 1, 1),
+            ("unused".into(), 0, 0)
+        ]
+    );
+}
+
+#[test]
+fn notes_grouped_split_pinned_and_unpinned() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    folder(&db, "empty", None);
+    note(&db, "a", "f", "a", "", 1, 2);
+    note(&db, "b", "f", "b", "", 1, 0);
+    note(&db, "c", "f", "c", "", 1, 1);
+    toggle_note_pinned(st(&db), "a".into(), 1).unwrap();
+    let g = get_notes_grouped(st(&db)).unwrap();
+    let summary: Vec<(String, Vec<String>)> = g
+        .into_iter()
+        .map(|g| (g.folder_id, g.notes.into_iter().map(|n| n.id).collect()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("f".into(), vec!["a".into(), "b".into(), "c".into()]),
+            ("empty".into(), vec![])
+        ]
+    );
+}
+
+#[test]
+fn rename_inbox_keeps_the_setting() {
+    let db = mem_db();
+    folder(&db, "Inbox", None);
+    folder(&db, "x", None);
+    assert_eq!(rename_inbox(st(&db), "Captures".into()).unwrap(), "Inbox");
+    assert_eq!(get_inbox_folder(st(&db)).unwrap(), Some("Inbox".into()));
+    let name: String =
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT name FROM folders WHERE id = 'Inbox'", [], |r| {
+                r.get(0)
+            })
+            .unwrap();
+    assert_eq!(name, "Captures");
+    note(&db, "n", "Inbox", "n", "", 5, 0);
+    assert_eq!(get_inbox_notes(st(&db), 10).unwrap().len(), 1);
+    let count: i64 =
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM folders", [], |r| r.get(0))
+            .unwrap();
+    assert_eq!(count, 2);
+    assert!(rename_inbox(st(&db), "  ".into()).is_err());
+}
+
+#[test]
+fn folderize_to_tags_tags_notes_by_path() {
+    let db = mem_db();
+    folder(&db, "Projects", None);
+    folder(&db, "Alpha", Some("Projects"));
+    folder(&db, "flat", None);
+    note(&db, "a", "Alpha", "a", "", 1, 0);
+    note(&db, "p", "Projects", "p", "", 1, 0);
+    add_note_tag(st(&db), "a".into(), "Alpha".into()).unwrap();
+    let r = folderize_to_tags(st(&db), "Projects".into(), true, None).unwrap();
+    assert_eq!((r.tags_created, r.notes_affected), (1, 2));
+    let mut names: Vec<String> = get_note_tags(st(&db), "a".into())
+        .unwrap()
+        .into_iter()
+        .map(|t| t.name)
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["Alpha", "Projects"]);
+    let r = folderize_to_tags(st(&db), "Alpha".into(), false, Some("flat".into())).unwrap();
+    assert_eq!((r.tags_created, r.notes_affected), (0, 1));
+    assert_eq!(order(&db, "flat"), vec!["a"]);
+    assert!(folderize_to_tags(st(&db), "nope".into(), false, None).is_err());
+}
+
+#[test]
+fn fts_stats_report_index_size() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    for i in 0..5 {
+        note(
+            &db,
+            &format!("n{}", i),
+            "f",
+            "title",
+            "some body words here",
+            1,
+            i,
+        );
+    }
+    let s = get_fts_stats(st(&db)).unwrap();
+    assert_eq!(s.doc_count, 5);
+    assert!(s.index_bytes > 0 && s.index_pages > 0);
+}
+
+#[test]
+fn fts_stats_match_the_shadow_tables() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "title", "body", 1, 0);
+    let b: i64 = db.0.lock().unwrap().query_row("SELECT (SELECT COALESCE(SUM(LENGTH(block)), 0) FROM notes_fts_data) + (SELECT COALESCE(SUM(LENGTH(sz)), 0) FROM notes_fts_docsize)", [], |r| r.get(0)).unwrap();
+    assert!(b > 0);
+}
+
+#[test]
+fn move_note_after_reorders_the_group() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    folder(&db, "g", None);
+    for (i, id) in ["a", "b", "c", "d"].iter().enumerate() {
+        note(&db, id, "f", id, "", 1, i as i32);
+    }
+    note(&db, "x", "g", "x", "", 1, 0);
+    note(&db, "y", "g", "y", "", 1, 1);
+    move_note_after(st(&db), "x".into(), "b".into(), 99).unwrap();
+    assert_eq!(order(&db, "f"), vec!["a", "b", "x", "c", "d"]);
+    assert_eq!(order(&db, "g"), vec!["y"]);
+    let so: Vec<i32> = ["a", "b", "x", "c", "d"]
+        .iter()
+        .map(|id| {
+            db.0.lock()
+                .unwrap()
+                .query_row("SELECT sort_order FROM notes WHERE id = ?1", [id], |r| {
+                    r.get(0)
+                })
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(so, vec![0, 1, 2, 3, 4]);
+    // within the same folder
+    move_note_after(st(&db), "a".into(), "d".into(), 99).unwrap();
+    assert_eq!(order(&db, "f"), vec!["b", "x", "c", "d", "a"]);
+    assert!(move_note_after(st(&db), "a".into(), "zz".into(), 1).is_err());
+    assert!(move_note_after(st(&db), "a".into(), "a".into(), 1).is_err());
+}
+
+#[test]
+fn move_folder_to_position_rejects_cycles() {
+    let db = mem_db();
+    folder(&db, "a", None);
+    folder(&db, "b", None);
+    folder(&db, "c", None);
+    folder(&db, "p", None);
+    folder(&db, "p1", Some("p"));
+    folder(&db, "p2", Some("p"));
+    let kids = |db: &TestDb, parent: Option<&str>| -> Vec<String> {
+        let c = db.0.lock().unwrap();
+        let mut s = c.prepare("SELECT id || ':' || sort_order FROM folders WHERE parent_id IS ?1 ORDER BY sort_order").unwrap();
+        let v = s
+            .query_map([parent], |r| r.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        v
+    };
+    move_folder_to_position(st(&db), "b".into(), Some("p".into()), 0).unwrap();
+    assert_eq!(kids(&db, Some("p")), vec!["b:0", "p1:1", "p2:2"]);
+    assert_eq!(kids(&db, None), vec!["a:0", "c:1", "p:2"]);
+    move_folder_to_position(st(&db), "a".into(), Some("p".into()), 99).unwrap();
+    assert_eq!(kids(&db, Some("p")), vec!["b:0", "p1:1", "p2:2", "a:3"]);
+    move_folder_to_position(st(&db), "p2".into(), Some("p".into()), 0).unwrap();
+    assert_eq!(kids(&db, Some("p")), vec!["p2:0", "b:1", "p1:2", "a:3"]);
+    assert!(move_folder_to_position(st(&db), "p".into(), Some("p1".into()), 0).is_err());
+    assert!(move_folder_to_position(st(&db), "p".into(), Some("p".into()), 0).is_err());
+    move_folder_to_position(st(&db), "p1".into(), None, 0).unwrap();
+    assert_eq!(kids(&db, None), vec!["p1:0", "c:1", "p:2"]);
+}
+
+#[test]
+fn empty_items_are_found_and_deleted() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    folder(&db, "e", None);
+    folder(&db, "outer", None);
+    folder(&db, "inner", Some("outer"));
+    folder(&db, "deep", None);
+    folder(&db, "deep2", Some("deep"));
+    note(&db, "blank", "f", "", "", 1, 0);
+    note(&db, "ws", "f", "  ", " \n\t\n ", 1, 1);
+    note(&db, "real", "f", "", "text", 1, 2);
+    note(&db, "d", "deep2", "x", "", 1, 0);
+    let r = find_empty_items(st(&db), None).unwrap();
+    assert_eq!(r.empty_notes, vec!["blank", "ws"]);
+    assert_eq!(r.empty_folders, vec!["e", "inner"]);
+    let r = find_empty_items(st(&db), Some(true)).unwrap();
+    assert_eq!(r.empty_folders, vec!["e", "inner", "outer"]);
+    assert_eq!(delete_empty_notes(st(&db)).unwrap(), 2);
+    assert_eq!(order(&db, "f"), vec!["real"]);
+}
+
+#[test]
+fn dissolve_folder_moves_contents_up() {
+    let db = mem_db();
+    folder(&db, "top", None);
+    folder(&db, "sib", Some("top"));
+    folder(&db, "mid", Some("top"));
+    folder(&db, "c1", Some("mid"));
+    folder(&db, "c2", Some("mid"));
+    note(&db, "t1", "top", "t1", "", 1, 0);
+    note(&db, "m1", "mid", "m1", "", 1, 0);
+    note(&db, "m2", "mid", "m2", "", 1, 1);
+    dissolve_folder(st(&db), "mid".into()).unwrap();
+    assert_eq!(order(&db, "top"), vec!["t1", "m1", "m2"]);
+    let c = db.0.lock().unwrap();
+    let mut s = c.prepare("SELECT id || ':' || sort_order FROM folders WHERE parent_id = 'top' ORDER BY sort_order").unwrap();
+    let v: Vec<String> = s
+        .query_map([], |r| r.get(0))
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+    assert_eq!(v, vec!["sib:0", "c1:1", "c2:2"]);
+    drop(s);
+    drop(c);
+    assert!(dissolve_folder(st(&db), "top".into()).is_err());
+    folder(&db, "x", Some("top"));
+    create_folder(st(&db), "dup".into(), "sib".into(), 1, Some("x".into())).unwrap();
+    assert!(dissolve_folder(st(&db), "x".into()).is_err());
+}
+
+#[test]
+fn lint_flags_markdown_problems() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    let body = "# Title\n\nSee [here]() and [](http://x) and `[a]()` and ![pic]().\n\n| a | b |\n|---|---|\n| 1 | 2 |\n| 1 | 2 | 3 |\n\n####### deep\n\n```\n```\n\n[[wiki]](x)\n\n~~~\nopen";
+    note(&db, "a", "f", "t", body, 1, 0);
+    let r: Vec<(usize, String, String)> = lint_note_markdown(st(&db), "a".into())
+        .unwrap()
+        .into_iter()
+        .map(|i| (i.line, i.severity, i.message))
+        .collect();
+    let lines: Vec<usize> = r.iter().map(|x| x.0).collect();
+    assert_eq!(lines, vec![3, 3, 3, 8, 10, 12, 17]);
+    assert_eq!(r[6].1, "error");
+    assert!(r
+        .iter()
+        .any(|x| x.2 == "table row has 3 cells, header has 2"));
+    assert!(r.iter().any(|x| x.2 == "link \"here\" has no destination"));
+    assert!(lint_note_markdown(st(&db), "zz".into()).is_err());
+}
+
+#[test]
+fn export_estimate_counts_the_subtree() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    folder(&db, "c", Some("f"));
+    note(&db, "a", "f", "a", &"x".repeat(3500), 1, 0);
+    note(&db, "b", "f", "b", "héllo", 1, 1);
+    note(&db, "d", "c", "d", &"y".repeat(4000), 1, 0);
+    let e = estimate_folder_export(st(&db), "f".into(), false).unwrap();
+    assert_eq!(
+        (e.note_count, e.total_body_bytes, e.estimated_pdf_pages),
+        (2, 3506, 2)
+    );
+    let e = estimate_folder_export(st(&db), "f".into(), true).unwrap();
+    assert_eq!(
+        (e.note_count, e.total_body_bytes, e.estimated_pdf_pages),
+        (3, 7506, 3)
+    );
+    assert!(estimate_folder_export(st(&db), "zz".into(), true).is_err());
+}
+
+#[test]
+fn title_collisions_are_grouped() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "Meeting", "", 1, 0);
+    note(&db, "b", "f", "Ideas", "", 1, 1);
+    note(&db, "c", "f", " meeting ", "", 1, 2);
+    note(&db, "d", "f", "", "", 1, 3);
+    note(&db, "e", "f", "  ", "", 1, 4);
+    let r: Vec<(String, Vec<String>)> = find_title_collisions(st(&db))
+        .unwrap()
+        .into_iter()
+        .map(|c| (c.title, c.note_ids))
+        .collect();
+    assert_eq!(
+        r,
+        vec![(
+            "Meeting".to_string(),
+            vec!["a".to_string(), "c".to_string()]
+        )]
+    );
+}
+
+#[test]
+fn folder_word_count_covers_the_subtree() {
+    let db = mem_db();
+    folder(&db, "novel", None);
+    folder(&db, "ch1", Some("novel"));
+    folder(&db, "scene", Some("ch1"));
+    note(&db, "a", "novel", "t", "one two three", 1, 0);
+    note(&db, "b", "ch1", "t", "four five", 1, 0);
+    note(&db, "c", "scene", "t", "six\nseven  eight", 1, 0);
+    assert_eq!(
+        get_folder_word_count(st(&db), "novel".into(), false).unwrap(),
+        3
+    );
+    assert_eq!(
+        get_folder_word_count(st(&db), "novel".into(), true).unwrap(),
+        8
+    );
+    assert!(get_folder_word_count(st(&db), "nope".into(), true).is_err());
+}
+
+#[test]
+fn note_excerpt_strips_markdown() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "n", "f", "t", "# Heading One\n\nSome **bold** and _soft_ text with a [link](https://example.com/x).\n\n```\nlet code = 1;\n```\n\n- item `x`\n", 1, 0);
+    let full = get_note_excerpt(st(&db), "n".into(), 500).unwrap();
+    assert_eq!(
+        full,
+        "Heading One Some bold and soft text with a link. item x"
+    );
+    for c in ['#', '*', '[', ']', '(', '`', '_'] {
+        assert!(!full.contains(c), "{}", c);
+    }
+    assert_eq!(
+        get_note_excerpt(st(&db), "n".into(), 14).unwrap(),
+        "Heading One…"
+    );
+    assert_eq!(
+        get_note_excerpt(st(&db), "n".into(), 11).unwrap(),
+        "Heading One…"
+    );
+    let meta = get_notes_metadata(st(&db), Some(20), Some(true)).unwrap();
+    assert_eq!(meta[0].preview, "Heading One Some…");
+}
+
+#[test]
+fn note_graph_links_resolved_targets() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    folder(&db, "g", None);
+    note(
+        &db,
+        "a",
+        "f",
+        "Alpha",
+        "see [[Beta]] and [[beta]] and [[Nowhere]]",
+        1,
+        0,
+    );
+    note(&db, "b", "g", "Beta", "back to [[c]]", 1, 1);
+    note(&db, "c", "f", "Gamma", "`[[Alpha]]`", 1, 2);
+    let g = get_note_graph(st(&db)).unwrap();
+    assert_eq!(g.nodes.len(), 3);
+    let b = g.nodes.iter().find(|n| n.id == "b").unwrap();
+    assert_eq!((b.title.as_str(), b.folder_id.as_str()), ("Beta", "g"));
+    let edges: Vec<(String, String)> = g.edges.into_iter().map(|e| (e.source, e.target)).collect();
+    assert_eq!(
+        edges,
+        vec![("a".into(), "b".into()), ("b".into(), "c".into())]
+    );
+}
+
+#[test]
+fn note_meta_round_trips_and_cascades() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "A", "", 1, 0);
+    note(&db, "b", "f", "B", "", 2, 1);
+    set_note_meta(st(&db), "a".into(), "status".into(), "draft".into()).unwrap();
+    set_note_meta(st(&db), "a".into(), " status ".into(), "done".into()).unwrap();
+    set_note_meta(st(&db), "a".into(), "priority".into(), "high".into()).unwrap();
+    set_note_meta(st(&db), "b".into(), "status".into(), "done".into()).unwrap();
+    assert!(set_note_meta(st(&db), "zz".into(), "k".into(), "v".into()).is_err());
+    assert!(set_note_meta(st(&db), "a".into(), " ".into(), "v".into()).is_err());
+    let m = get_note_meta(st(&db), "a".into()).unwrap();
+    assert_eq!(m.len(), 2);
+    assert_eq!(m["status"], "done");
+    let ids: Vec<String> = find_notes_by_meta(st(&db), "status".into(), "done".into())
+        .unwrap()
+        .into_iter()
+        .map(|n| n.id)
+        .collect();
+    assert_eq!(ids, vec!["b", "a"]);
+    assert!(delete_note_meta(st(&db), "a".into(), "priority".into()).unwrap());
+    assert!(!delete_note_meta(st(&db), "a".into(), "priority".into()).unwrap());
+    assert_eq!(get_note_meta(st(&db), "a".into()).unwrap().len(), 1);
+    let backup = {
+        let conn = db.0.lock().unwrap();
+        backup_json(&conn, 0).unwrap()
+    };
+    assert_eq!(backup["note_meta"].as_array().unwrap().len(), 2);
+    let restored = mem_db();
+    let meta: Vec<NoteMeta> = serde_json::from_value(backup["note_meta"].clone()).unwrap();
+    let notes: Vec<Note> = serde_json::from_value(backup["notes"].clone()).unwrap();
+    let folders: Vec<Folder> = serde_json::from_value(backup["folders"].clone()).unwrap();
+    import_data(st(&restored), folders, notes, None, None, Some(meta)).unwrap();
+    assert_eq!(
+        get_note_meta(st(&restored), "b".into()).unwrap()["status"],
+        "done"
+    );
+    delete_note(st(&db), "a".into()).unwrap();
+    let n: i64 =
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM note_meta", [], |r| r.get(0))
+            .unwrap();
+    assert_eq!(n, 1);
+}
+
+#[test]
+fn notes_with_attachments_are_listed() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "A", "", 1, 0);
+    note(&db, "b", "f", "B", "", 1, 1);
+    note(&db, "c", "f", "C", "", 1, 2);
+    db.0.lock().unwrap().execute_batch("INSERT INTO attachments (id,note_id,file_name,size,created_at) VALUES ('x1','a','p.png',1,1),('x2','c','p.png',1,1),('x3','c','q.png',1,1)").unwrap();
+    let got: Vec<(String, i64)> = get_notes_with_attachments(st(&db))
+        .unwrap()
+        .into_iter()
+        .map(|n| (n.metadata.id, n.attachment_count))
+        .collect();
+    assert_eq!(got, vec![("c".to_string(), 2), ("a".to_string(), 1)]);
+}
+
+#[test]
+fn default_note_template_applies_to_new_notes() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    assert_eq!(get_default_note_template(st(&db)).unwrap(), None);
+    set_default_note_template(st(&db), "## {{date}} {{time}}\n\n- ".into()).unwrap();
+    assert_eq!(
+        get_default_note_template(st(&db)).unwrap().as_deref(),
+        Some("## {{date}} {{time}}\n\n- ")
+    );
+    let body = |id: &str| -> String {
+        db.0.lock()
+            .unwrap()
+            .query_row("SELECT body FROM notes WHERE id=?1", [id], |r| r.get(0))
+            .unwrap()
+    };
+    let empty = create_note_from_markdown(st(&db), "f".into(), "".into()).unwrap();
+    let b = body(&empty);
+    assert!(b.starts_with("## ") && !b.contains("{{"), "{}", b);
+    assert_eq!(b.len(), "## 2026-01-01 12:00\n\n- ".len());
+    let full = create_note_from_markdown(st(&db), "f".into(), "# T\n\nhello".into()).unwrap();
+    assert_eq!(body(&full), "hello");
+    // addNote() creates notes through create_note with an empty body
+    let stored = create_note(
+        st(&db),
+        "n".into(),
+        "f".into(),
+        "".into(),
+        "".into(),
+        1,
+        1,
+        0,
+        0,
+    )
+    .unwrap();
+    assert!(
+        stored.starts_with("## ") && !stored.contains("{{"),
+        "{}",
+        stored
+    );
+    assert_eq!(body("n"), stored);
+    let kept = create_note(
+        st(&db),
+        "m".into(),
+        "f".into(),
+        "".into(),
+        "text".into(),
+        1,
+        1,
+        0,
+        0,
+    )
+    .unwrap();
+    assert_eq!(kept, "text");
+    assert_eq!(body("m"), "text");
+    set_default_note_template(st(&db), "  ".into()).unwrap();
+    assert_eq!(get_default_note_template(st(&db)).unwrap(), None);
+    let plain = create_note_from_markdown(st(&db), "f".into(), "".into()).unwrap();
+    assert_eq!(body(&plain), "");
+}
+
+#[test]
+fn notes_histogram_buckets_by_day() {
+    use chrono::TimeZone;
+    let db = mem_db();
+    folder(&db, "f", None);
+    let tz = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+    let at = |y, m, d, h| {
+        tz.with_ymd_and_hms(y, m, d, h, 0, 0)
+            .unwrap()
+            .timestamp_millis()
+    };
+    // 1 Mar 01:00 local is still 28 Feb in UTC
+    for (i, ms) in [
+        at(2026, 2, 3, 9),
+        at(2026, 2, 20, 23),
+        at(2026, 3, 1, 1),
+        at(2026, 3, 15, 12),
+        at(2026, 3, 31, 22),
+    ]
+    .iter()
+    .enumerate()
+    {
+        note(&db, &format!("n{}", i), "f", "", "", *ms, i as i32);
+    }
+    let c = db.0.lock().unwrap();
+    let h: Vec<(i64, i64)> = notes_histogram(&c, "month", &tz)
+        .unwrap()
+        .into_iter()
+        .map(|b| (b.bucket_start_ms, b.count))
+        .collect();
+    assert_eq!(h, vec![(at(2026, 2, 1, 0), 2), (at(2026, 3, 1, 0), 3)]);
+    let w = notes_histogram(&c, "week", &tz).unwrap();
+    assert_eq!(w[0].bucket_start_ms, at(2026, 2, 2, 0));
+    assert_eq!(notes_histogram(&c, "day", &tz).unwrap().len(), 5);
+    assert!(notes_histogram(&c, "year", &tz).is_err());
+    drop(c);
+    assert_eq!(
+        get_notes_histogram(st(&db), "month".into())
+            .unwrap()
+            .iter()
+            .map(|b| b.count)
+            .sum::<i64>(),
+        5
+    );
+}
+
+#[test]
+fn shortcuts_keep_their_order() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    for id in ["a", "b", "c"] {
+        note(&db, id, "f", id, "", 1, 0);
+    }
+    assert!(get_shortcuts(st(&db)).unwrap().is_empty());
+    for id in ["c", "a", "b", "a"] {
+        add_shortcut(st(&db), id.into()).unwrap();
+    }
+    assert!(add_shortcut(st(&db), "zz".into()).is_err());
+    let ids = |db: &TestDb| -> Vec<String> {
+        get_shortcuts(st(db))
+            .unwrap()
+            .into_iter()
+            .map(|n| n.id)
+            .collect()
+    };
+    assert_eq!(ids(&db), vec!["c", "a", "b"]);
+    assert!(reorder_shortcuts(st(&db), vec!["a".into(), "b".into()]).is_err());
+    assert!(reorder_shortcuts(st(&db), vec!["a".into(), "a".into(), "b".into()]).is_err());
+    reorder_shortcuts(st(&db), vec!["b".into(), "c".into(), "a".into()]).unwrap();
+    db.0.lock()
+        .unwrap()
+        .execute("DELETE FROM notes WHERE id='c'", [])
+        .unwrap();
+    assert_eq!(ids(&db), vec!["b", "a"]);
+    assert!(remove_shortcut(st(&db), "c".into()).unwrap());
+    assert!(!remove_shortcut(st(&db), "c".into()).unwrap());
+    assert_eq!(ids(&db), vec!["b", "a"]);
+}
+
+#[test]
+fn archive_export_writes_markdown_tree() {
+    let db = mem_db();
+    folder(&db, "r", None);
+    folder(&db, "a", Some("r"));
+    note(&db, "n1", "a", "Hello/World", "body one", 1000, 0);
+    note(&db, "n2", "a", "hello_world", "dup", 1000, 1);
+    note(&db, "n3", "r", "", "untitled", 1000, 0);
+    note(&db, "n4", "r", &"x".repeat(200), "long", 1000, 1);
+    folder(&db, "deep", Some("a"));
+    db.0.lock()
+        .unwrap()
+        .execute(
+            "UPDATE folders SET name = ?1 WHERE id = 'deep'",
+            ["y".repeat(80)],
+        )
+        .unwrap();
+    note(&db, "n5", "deep", "leaf", "x", 1000, 0);
+    add_note_tag(st(&db), "n1".into(), "work".into()).unwrap();
+    let dir = TempDir::new("archive");
+    let path = dir.0.join("notes.tar.gz");
+    let res = export_archive(st(&db), path.to_string_lossy().into()).unwrap();
+    assert_eq!(res.note_count, 5);
+    let entries = tar_gz_entries(&path);
+    let file = |name: &str| -> String {
+        let (_, data) = entries
+            .iter()
+            .find(|(n, _)| n == name)
+            .unwrap_or_else(|| panic!("{}", name));
+        String::from_utf8(data.clone()).unwrap()
+    };
+    assert_eq!(
+        file("notes/r/a/Hello_World.md"),
+        "# Hello/World\n\nbody one"
+    );
+    file("notes/r/a/hello_world (2).md");
+    file("notes/r/Untitled.md");
+    file("backup.json");
+    // Paths past the 100-byte ustar name field go through a pax header
+    file(&format!("notes/r/a/{}/leaf.md", "y".repeat(80)));
+    assert!(file("tags.json").contains("notes/r/a/Hello_World.md"));
+}
+
+#[test]
+fn note_bundle_collects_backlinks() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "t", "f", "Target", "the target", 1, 0);
+    note(&db, "a", "f", "A", "see [[target]]", 1, 0);
+    note(&db, "b", "f", "A", "see [[t|here]]", 1, 0);
+    note(&db, "c", "f", "C", "`[[Target]]` and [[Nope]]", 1, 0);
+    let dir = TempDir::new("bundle");
+    let path = dir.0.join("bundle.zip");
+    let n = export_note_bundle(st(&db), "t".into(), path.to_string_lossy().into()).unwrap();
+    assert_eq!(n, 3);
+    let entries = zip_entries(&path);
+    let mut names: Vec<&str> = entries.iter().map(|(n, _)| n.as_str()).collect();
+    names.sort();
+    assert_eq!(
+        names,
+        vec![
+            "Target.md",
+            "bundle.json",
+            "references/A (2).md",
+            "references/A.md"
+        ]
+    );
+    let file = |name: &str| entries.iter().find(|(n, _)| n == name).unwrap().1.clone();
+    assert_eq!(
+        String::from_utf8(file("Target.md")).unwrap(),
+        "# Target\n\nthe target"
+    );
+    let manifest: serde_json::Value = serde_json::from_slice(&file("bundle.json")).unwrap();
+    assert_eq!(
+        manifest["edges"],
+        serde_json::json!([{ "from": "a", "to": "t" }, { "from": "b", "to": "t" }])
+    );
+}
+
+#[test]
+fn thumbnail_is_a_valid_png() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(
+        &db,
+        "a",
+        "f",
+        "Hello thumbnail",
+        &"The quick brown fox jumps over the lazy dog. ".repeat(40),
+        1,
+        0,
+    );
+    let dir = TempDir::new("thumbnail");
+    let path = dir.0.join("thumb.png");
+    let p = render_note_thumbnail(st(&db), "a".into(), path.to_string_lossy().into(), 400, 300)
+        .unwrap();
+    let png = std::fs::read(&p).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    let u32_at = |i: usize| u32::from_be_bytes(png[i..i + 4].try_into().unwrap());
+    assert_eq!((u32_at(16), u32_at(20)), (400, 300));
+    let idat = png.windows(4).position(|w| w == b"IDAT").unwrap();
+    let len = u32_at(idat - 4) as usize;
+    let mut crc = flate2::Crc::new();
+    crc.update(&png[idat..idat + 4 + len]);
+    assert_eq!(crc.sum(), u32_at(idat + 4 + len));
+    let mut raw = Vec::new();
+    std::io::Read::read_to_end(
+        &mut flate2::read::ZlibDecoder::new(&png[idat + 4..idat + 4 + len]),
+        &mut raw,
+    )
+    .unwrap();
+    // A filter byte per row, then one grey byte per pixel; text is 0x22
+    assert_eq!(raw.len(), 401 * 300);
+    assert!(raw.iter().filter(|&&b| b == 0x22).count() > 500);
+    assert!(render_note_thumbnail(st(&db), "a".into(), p.clone(), 0, 300).is_err());
+}
+
+#[test]
+fn docx_export_maps_markdown_blocks() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    let body = "# Intro\n\nSome **bold** and *it* text with `code` and [a link](http://x) & <tag>.\n\n- one\n  - nested\n- two\n\n1. first\n2. second\n\n| A | B |\n|---|---|\n| 1 | 2 |\n\n```\nfn main() {}\n```\n\n> quoted\n\n---\nsnake_case_word\n";
+    note(&db, "a", "f", "My Doc", body, 1, 0);
+    let dir = TempDir::new("docx");
+    let path = dir.0.join("note.docx");
+    let p = export_note_docx(st(&db), "a".into(), path.to_string_lossy().into()).unwrap();
+    let entries = zip_entries(std::path::Path::new(&p));
+    assert_eq!(entries[0].0, "[Content_Types].xml");
+    let (_, doc) = entries
+        .iter()
+        .find(|(n, _)| n == "word/document.xml")
+        .unwrap();
+    let d = String::from_utf8(doc.clone()).unwrap();
+    let count = |needle: &str| d.matches(needle).count();
+    assert_eq!(count("<w:numPr>"), 5);
+    assert_eq!(count("Heading1"), 1);
+    assert_eq!(count("<w:tbl>"), 1);
+    assert_eq!(count("w:val=\"Code\""), 1);
+    assert_eq!(count("<w:b/>"), 3);
+    assert!(d.contains("nested") && d.contains("snake_case_word"));
+    assert!(d.contains("&amp; &lt;tag&gt;"));
+    // The ordered list gets its own numbering after the shared bullet one
+    assert!(d.contains("numId w:val=\"2\""));
+    let b = markdown::blocks("- a\n    - b\n  - c\n- d");
+    let depths: Vec<usize> = b
+        .iter()
+        .map(|b| match b {
+            markdown::Block::ListItem { depth, .. } => *depth,
+            _ => 99,
+        })
+        .collect();
+    assert_eq!(depths, vec![0, 1, 1, 0]);
+    let s = markdown::spans("x **b *bi*** [t](u) ![img](p) [[T|lab]] \\*no\\*");
+    let txt: Vec<(String, bool, bool, Option<String>)> = s
+        .into_iter()
+        .map(|s| (s.text, s.bold, s.italic, s.link))
+        .collect();
+    assert_eq!(
+        txt,
+        vec![
+            ("x ".into(), false, false, None),
+            ("b ".into(), true, false, None),
+            ("bi".into(), true, true, None),
+            (" ".into(), false, false, None),
+            ("t".into(), false, false, Some("u".into())),
+            (" img ".into(), false, false, None),
+            ("lab".into(), false, false, Some("[[T]]".into())),
+            (" *no*".into(), false, false, None),
+        ]
+    );
+}
+
+#[test]
+fn joplin_import_rebuilds_notebooks() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "old", "f", "existing", "", 1, 0);
+    let nb = "Work\n\nid: aaaa1111\nparent_id: \ncreated_time: 2020-01-02T03:04:05.000Z\nupdated_time: 2020-01-02T03:04:05.000Z\ntype_: 2";
+    let n1 = "Meeting notes\n\n- item\n\ntodo: not metadata\n\nid: bbbb2222\nparent_id: aaaa1111\ncreated_time: 2021-05-06T07:08:09.123Z\nupdated_time: 2021-05-07T00:00:00.000Z\nis_todo: 0\ntype_: 1";
+    let n2 = "Loose\n\nbody\n\nid: cccc3333\nparent_id: zzzz\ncreated_time: 2021-01-01T00:00:00.000Z\nupdated_time: 2021-01-01T00:00:00.000Z\ntype_: 1\n";
+    let res = "res\n\nid: dddd4444\nmime: image/png\ntype_: 4";
+    let mut tar = archive::TarWriter::new(Vec::new());
+    tar.append("aaaa1111.md", nb.as_bytes(), 0).unwrap();
+    tar.append("bbbb2222.md", n1.as_bytes(), 0).unwrap();
+    tar.append("cccc3333.md", n2.as_bytes(), 0).unwrap();
+    tar.append("dddd4444.md", res.as_bytes(), 0).unwrap();
+    tar.append("resources/dddd4444.png", b"png", 0).unwrap();
+    let dir = TempDir::new("joplin");
+    let path = dir.0.join("export.jex");
+    std::fs::write(&path, tar.finish().unwrap()).unwrap();
+    assert_eq!(
+        import_joplin(st(&db), path.to_string_lossy().into(), "f".into()).unwrap(),
+        2
+    );
+    let conn = db.0.lock().unwrap();
+    let work: String = conn
+        .query_row(
+            "SELECT id FROM folders WHERE name = 'Work' AND parent_id = 'f'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    let (title, body, created, updated, folder_id): (String, String, i64, i64, String) = conn.query_row(
+        "SELECT title, body, created_at, updated_at, folder_id FROM notes WHERE title = 'Meeting notes'", [],
+        |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?))).unwrap();
+    assert_eq!(
+        (title.as_str(), body.as_str(), folder_id),
+        ("Meeting notes", "- item\n\ntodo: not metadata", work)
+    );
+    assert_eq!((created, updated), (1620284889123, 1620345600000));
+    let (loose_folder, loose_order): (String, i32) = conn
+        .query_row(
+            "SELECT folder_id, sort_order FROM notes WHERE title = 'Loose'",
+            [],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!((loose_folder.as_str(), loose_order), ("f", 1));
+    drop(conn);
+    // Round trip a long path through the tar reader
+    let long = format!("{}/file.md", "d".repeat(120));
+    let mut tar = archive::TarWriter::new(Vec::new());
+    tar.append(&long, b"x", 0).unwrap();
+    let files = archive::read_tar(&tar.finish().unwrap()).unwrap();
+    assert_eq!(files, vec![(long, b"x".to_vec())]);
+}
+
+#[test]
+fn tags_json_lists_notes_per_tag() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "a", "", 1, 0);
+    note(&db, "b", "f", "b", "", 1, 1);
+    add_note_tag(st(&db), "a".into(), "work".into()).unwrap();
+    add_note_tag(st(&db), "b".into(), "work".into()).unwrap();
+    add_note_tag(st(&db), "a".into(), "home".into()).unwrap();
+    db.0.lock()
+        .unwrap()
+        .execute(
+            "INSERT INTO tags (id, name, created_at) VALUES ('t9', 'zempty', 0)",
+            [],
+        )
+        .unwrap();
+    let dir = TempDir::new("tags-json");
+    let path = dir.0.join("tags.json");
+    assert_eq!(
+        export_tags_json(st(&db), path.to_string_lossy().into()).unwrap(),
+        3
+    );
+    let v: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let tags = v["tags"].as_array().unwrap();
+    let summary: Vec<(String, Vec<String>)> = tags
+        .iter()
+        .map(|t| {
+            (
+                t["name"].as_str().unwrap().to_string(),
+                t["notes"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|n| n.as_str().unwrap().to_string())
+                    .collect(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("home".into(), vec!["a".into()]),
+            ("work".into(), vec!["a".into(), "b".into()]),
+            ("zempty".into(), vec![])
+        ]
+    );
+    assert_eq!(tags[2]["id"], "t9");
+    assert!(tags[2]["color"].is_null());
+}
+
+#[test]
+fn split_text_import_creates_a_note_per_entry() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    let dir = TempDir::new("split-text");
+    let path = dir.0.join("entries.txt");
+    std::fs::write(
+        &path,
+        "# First\nalpha\n---\nSecond entry\nbeta --- inline\n\n---\n\n---\r\nThird\r\n",
+    )
+    .unwrap();
+    assert_eq!(
+        import_split_text(
+            st(&db),
+            path.to_string_lossy().into(),
+            "f".into(),
+            "---".into()
+        )
+        .unwrap(),
+        3
+    );
+    let titles: Vec<(String, String)> = order(&db, "f")
+        .iter()
+        .map(|id| {
+            db.0.lock()
+                .unwrap()
+                .query_row("SELECT title, body FROM notes WHERE id = ?1", [id], |r| {
+                    Ok((r.get(0)?, r.get(1)?))
+                })
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(
+        titles,
+        vec![
+            ("First".into(), "alpha".into()),
+            (
+                "Second entry".into(),
+                "Second entry\nbeta --- inline".into()
+            ),
+            ("Third".into(), "Third".into())
+        ]
+    );
+    std::fs::write(&path, "one\nline\n\n\ntwo\n\n\n\n\nthree").unwrap();
+    assert_eq!(
+        import_split_text(
+            st(&db),
+            path.to_string_lossy().into(),
+            "f".into(),
+            "\n\n\n".into()
+        )
+        .unwrap(),
+        3
+    );
+    assert!(import_split_text(
+        st(&db),
+        path.to_string_lossy().into(),
+        "f".into(),
+        "".into()
+    )
+    .is_err());
+}
+
+#[test]
+fn pdf_export_adds_a_cover_page() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    let body = "# Intro\n\nSome **bold** text (with parens) and a very long paragraph that keeps going and going so that it needs to wrap across several lines of the page width here.\n\n- one\n  1. nested\n\n```\ncode()\n```\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n---\n\n> quote — “curly” 日本\n";
+    note(&db, "n", "f", "Report", body, 1, 0);
+    let dir = TempDir::new("pdf");
+    let plain = dir.0.join("plain.pdf");
+    let covered = dir.0.join("cover.pdf");
+    export_note_pdf(
+        st(&db),
+        "n".into(),
+        plain.to_string_lossy().into(),
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+    export_note_pdf(
+        st(&db),
+        "n".into(),
+        covered.to_string_lossy().into(),
+        Some(true),
+        Some("Jane Doe".into()),
+        Some("Quarterly (Q3)".into()),
+    )
+    .unwrap();
+    let a = std::fs::read(&plain).unwrap();
+    let b = std::fs::read(&covered).unwrap();
+    let pages = |d: &[u8]| d.windows(13).filter(|w| w == b"/Type /Page /").count();
+    assert_eq!(pages(&a), 1);
+    assert_eq!(pages(&b), 2);
+    let text = String::from_utf8_lossy(&b);
+    assert!(text.contains("/Author (Jane Doe)"));
+    assert!(text.contains("/Subject (Quarterly \\(Q3\\))"));
+    assert!(!String::from_utf8_lossy(&a).contains("/Author"));
+    // xref offsets point at objects
+    let pos = b.windows(10).rposition(|w| w == b"startxref\n").unwrap();
+    let start: usize = std::str::from_utf8(&b[pos + 10..])
+        .unwrap()
+        .lines()
+        .next()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(b[start..].starts_with(b"xref"));
+    let table = std::str::from_utf8(&b[start..]).unwrap();
+    let entries: Vec<usize> = table
+        .lines()
+        .skip(3)
+        .take_while(|l| l.ends_with(" n "))
+        .map(|l| l[..10].parse().unwrap())
+        .collect();
+    assert!(entries.len() > 8);
+    for (i, off) in entries.iter().enumerate() {
+        assert!(b[*off..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+    }
+    // long docs paginate
+    let long = "word ".repeat(5000);
+    note(&db, "l", "f", "Long", &long, 1, 1);
+    export_note_pdf(
+        st(&db),
+        "l".into(),
+        plain.to_string_lossy().into(),
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+    assert!(pages(&std::fs::read(&plain).unwrap()) > 3);
+}
+
+#[test]
+fn newer_schema_versions_are_refused() {
+    let dir = TempDir::new("schema");
+    let path = dir.0.join("notes.db");
+    let conn = open_initialized_db(&path).unwrap();
+    let v: i32 = conn
+        .pragma_query_value(None, "user_version", |r| r.get(0))
+        .unwrap();
+    assert_eq!(v, REQUIRED_SCHEMA_VERSION);
+    drop(conn);
+    assert!(open_initialized_db(&path).is_ok());
+    Connection::open(&path)
+        .unwrap()
+        .pragma_update(None, "user_version", REQUIRED_SCHEMA_VERSION + 1)
+        .unwrap();
+    let err = open_initialized_db(&path).err().unwrap();
+    assert!(err.contains("newer"), "{}", err);
+}
+
+#[test]
+fn notes_markdown_export_avoids_name_clashes() {
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "a", "f", "Meeting", "one", 1, 0);
+    note(&db, "b", "f", "meeting", "two", 1, 1);
+    note(&db, "c", "f", "Ideas/Plans", "three", 1, 2);
+    let tmp = TempDir::new("notes-md");
+    let dir = tmp.0.join("out");
+    let out = dir.to_string_lossy().to_string();
+    assert!(export_notes_markdown(st(&db), vec!["a".into(), "zz".into()], out.clone()).is_err());
+    assert!(!dir.exists());
+    let paths = export_notes_markdown(
+        st(&db),
+        vec!["a".into(), "b".into(), "c".into()],
+        out.clone(),
+    )
+    .unwrap();
+    let names: Vec<String> = paths
+        .iter()
+        .map(|p| {
+            std::path::Path::new(p)
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    assert_eq!(
+        names,
+        vec!["Meeting.md", "meeting (b).md", "Ideas_Plans.md"]
+    );
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+    assert_eq!(
+        std::fs::read_to_string(&paths[1]).unwrap(),
+        "# meeting\n\ntwo"
+    );
+    // existing files on disk aren't overwritten
+    let again = export_notes_markdown(st(&db), vec!["c".into()], out).unwrap();
+    assert!(again[0].ends_with("Ideas_Plans (c).md"));
+}
+
+#[test]
+fn merge_database_resolves_conflicts() {
+    let dir = TempDir::new("merge");
+    let path = dir.0.join("other.db");
+    let oc = Connection::open(&path).unwrap();
+    init_db(&oc);
+    let other = test_db(oc);
+    folder(&other, "f", None);
+    folder(&other, "child", Some("f"));
+    note(&other, "shared_new", "f", "S", "other newer", 50, 0);
+    note(&other, "shared_old", "f", "S", "other older", 5, 1);
+    note(&other, "only", "child", "O", "only in other", 20, 0);
+    {
+        let c = other.0.lock().unwrap();
+        c.execute(
+            "INSERT INTO tags (id,name,created_at) VALUES ('t2','Work',1),('t3','home',1)",
+            [],
+        )
+        .unwrap();
+        c.execute(
+            "INSERT INTO note_tags (note_id,tag_id) VALUES ('only','t2'),('only','t3')",
+            [],
+        )
+        .unwrap();
+    }
+    drop(other);
+
+    let db = mem_db();
+    folder(&db, "f", None);
+    note(&db, "shared_new", "f", "S", "mine older", 10, 0);
+    note(&db, "shared_old", "f", "S", "mine newer", 10, 1);
+    db.0.lock()
+        .unwrap()
+        .execute(
+            "INSERT INTO tags (id,name,created_at) VALUES ('t1','work',1)",
+            [],
+        )
+        .unwrap();
+
+    assert!(merge_database(st(&db), path.to_string_lossy().into(), "bogus".into()).is_err());
+    assert!(merge_database(
+        st(&db),
+        dir.0.join("nope.db").to_string_lossy().into(),
+        "skip".into()
+    )
+    .is_err());
+    let r = merge_database(st(&db), path.to_string_lossy().into(), "newer-wins".into()).unwrap();
+    assert_eq!((r.folders, r.notes, r.tags, r.replaced), (1, 1, 1, 1));
+    assert_eq!(
+        r.conflicts,
+        vec!["shared_new".to_string(), "shared_old".to_string()]
+    );
+    let c = db.0.lock().unwrap();
+    let body = |id: &str| -> String {
+        c.query_row("SELECT body FROM notes WHERE id=?1", [id], |r| r.get(0))
+            .unwrap()
+    };
+    assert_eq!(body("shared_new"), "other newer");
+    assert_eq!(body("shared_old"), "mine newer");
+    assert_eq!(body("only"), "only in other");
+    let tags: Vec<String> = c
+        .prepare("SELECT tag_id FROM note_tags WHERE note_id='only' ORDER BY tag_id")
+        .unwrap()
+        .query_map([], |r| r.get(0))
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+    assert_eq!(tags, vec!["t1", "t3"]);
+    let hits: i64 = c
+        .query_row(
+            "SELECT COUNT(*) FROM notes_fts WHERE notes_fts MATCH 'other'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(hits, 2);
+    let total: i64 = c
+        .query_row(
+            "SELECT total_count FROM folder_counts WHERE folder_id='f'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(total, 3);
+    let attached: i64 = c
+        .query_row(
+            "SELECT COUNT(*) FROM pragma_database_list WHERE name = 'merge_src'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(attached, 0);
+}
@@ -0,0 +1,237 @@
+// Note thumbnails: plain text drawn with a built-in 5x7 bitmap font into a
+// grayscale PNG. No font files or image crates — glyphs are scaled 2x and
+// the PNG is encoded by hand with flate2 for the zlib stream.
+
+use std::io::Write;
+
+const SCALE: usize = 2;
+const GLYPH_W: usize = 5;
+const GLYPH_H: usize = 7;
+const CELL_W: usize = (GLYPH_W + 1) * SCALE;
+const CELL_H: usize = (GLYPH_H + 3) * SCALE;
+const MARGIN: usize = 12;
+const BACKGROUND: u8 = 0xff;
+const INK: u8 = 0x22;
+
+// Printable ASCII 0x20..=0x7e, one byte per column, bit 0 = top row
+const FONT: [[u8; GLYPH_W]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5f, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], // #
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1c, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1c, 0x00], // )
+    [0x14, 0x08, 0x3e, 0x08, 0x14], // *
+    [0x08, 0x08, 0x3e, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], // 0
+    [0x00, 0x42, 0x7f, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4b, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7f, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1e], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3e], // @
+    [0x7e, 0x11, 0x11, 0x11, 0x7e], // A
+    [0x7f, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3e, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], // D
+    [0x7f, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7f, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3e, 0x41, 0x49, 0x49, 0x7a], // G
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], // H
+    [0x00, 0x41, 0x7f, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3f, 0x01], // J
+    [0x7f, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7f, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7f, 0x02, 0x0c, 0x02, 0x7f], // M
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], // N
+    [0x3e, 0x41, 0x41, 0x41, 0x3e], // O
+    [0x7f, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3e, 0x41, 0x51, 0x21, 0x5e], // Q
+    [0x7f, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7f, 0x01, 0x01], // T
+    [0x3f, 0x40, 0x40, 0x40, 0x3f], // U
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], // V
+    [0x3f, 0x40, 0x38, 0x40, 0x3f], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7f, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7f, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7f, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7f], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7e, 0x09, 0x01, 0x02], // f
+    [0x0c, 0x52, 0x52, 0x52, 0x3e], // g
+    [0x7f, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7d, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3d, 0x00], // j
+    [0x7f, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7f, 0x40, 0x00], // l
+    [0x7c, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7c, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7c, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7c], // q
+    [0x7c, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3f, 0x44, 0x40, 0x20], // t
+    [0x3c, 0x40, 0x40, 0x20, 0x7c], // u
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], // v
+    [0x3c, 0x40, 0x30, 0x40, 0x3c], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0c, 0x50, 0x50, 0x50, 0x3c], // y
+    [0x44, 0x64, 0x54, 0x4c, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7f, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Canvas {
+            width,
+            height,
+            pixels: vec![BACKGROUND; width * height],
+        }
+    }
+
+    // Draws one glyph with its top-left corner at (x, y), clipped to the canvas.
+    // Characters outside printable ASCII are drawn as '?'.
+    fn glyph(&mut self, x: usize, y: usize, c: char, bold: bool) {
+        let idx = match c {
+            ' '..='~' => c as usize - 0x20,
+            _ => '?' as usize - 0x20,
+        };
+        // Bold is a second stroke one pixel to the right
+        let stroke = if bold { SCALE + 1 } else { SCALE };
+        for (col, bits) in FONT[idx].iter().enumerate() {
+            for row in 0..GLYPH_H {
+                if bits & (1 << row) == 0 {
+                    continue;
+                }
+                for dy in 0..SCALE {
+                    for dx in 0..stroke {
+                        let px = x + col * SCALE + dx;
+                        let py = y + row * SCALE + dy;
+                        if px < self.width && py < self.height {
+                            self.pixels[py * self.width + px] = INK;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Hard-wraps text to `cols` characters per line, breaking at spaces when possible.
+fn wrap(text: &str, cols: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for raw in text.lines() {
+        let mut line = String::new();
+        for word in raw.replace('\t', "    ").split(' ') {
+            let len = line.chars().count();
+            let word_len = word.chars().count();
+            if len > 0 && len + 1 + word_len > cols {
+                lines.push(std::mem::take(&mut line));
+            } else if len > 0 {
+                line.push(' ');
+            }
+            let mut chars: Vec<char> = word.chars().collect();
+            while chars.len() > cols {
+                let rest = chars.split_off(cols);
+                lines.push(chars.into_iter().collect());
+                chars = rest;
+            }
+            line.extend(chars);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+// Renders the title (bold) and as many body lines as fit, returning PNG bytes.
+pub fn render(title: &str, body: &str, width: u32, height: u32) -> std::io::Result<Vec<u8>> {
+    let (w, h) = (width as usize, height as usize);
+    let mut canvas = Canvas::new(w, h);
+    let cols = w.saturating_sub(MARGIN * 2) / CELL_W;
+    let rows = h.saturating_sub(MARGIN * 2) / CELL_H;
+    let title_lines = wrap(title.trim(), cols.max(1));
+    let body_lines = wrap(body, cols.max(1));
+    let lines = title_lines
+        .iter()
+        .map(|l| (l, true))
+        .chain(body_lines.iter().map(|l| (l, false)))
+        .take(rows);
+    for (row, (line, bold)) in lines.enumerate() {
+        for (col, c) in line.chars().take(cols).enumerate() {
+            canvas.glyph(MARGIN + col * CELL_W, MARGIN + row * CELL_H, c, bold);
+        }
+    }
+    encode_png(&canvas)
+}
+
+// 8-bit grayscale PNG, no filtering
+fn encode_png(canvas: &Canvas) -> std::io::Result<Vec<u8>> {
+    let mut raw = Vec::with_capacity((canvas.width + 1) * canvas.height);
+    for row in canvas.pixels.chunks(canvas.width) {
+        raw.push(0); // filter type: none
+        raw.extend_from_slice(row);
+    }
+    let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    zlib.write_all(&raw)?;
+    let idat = zlib.finish()?;
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(canvas.width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(canvas.height as u32).to_be_bytes());
+    // bit depth 8, color type 0 (grayscale), compression, filter, interlace
+    ihdr.extend_from_slice(&[8, 0, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(&mut png, b"IDAT", &idat);
+    write_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let mut crc = flate2::Crc::new();
+    crc.update(kind);
+    crc.update(data);
+    out.extend_from_slice(&crc.sum().to_be_bytes());
+}
@@ -1,6 +1,6 @@
 import { invoke } from '@tauri-apps/api/core';
 import { icons } from './icons.js';
-import { state, loadTheme, saveTheme, generateId, formatDate, escapeHtml, rebuildIndexes } from './state.js';
+import { state, DataLayer, loadTheme, saveTheme, generateId, formatDate, escapeHtml, rebuildIndexes } from './state.js';
 import { createEditor, destroyEditor, focusEditor, getEditorView, updateFindHighlights } from './editor.js';
 import { TextSelection } from '@milkdown/prose/state';
 
@@ -993,7 +993,15 @@ async function deleteFolder(id) {
     state.activeNoteId = null;
   }
   render();
-  await invoke('delete_folder', { id });
+  // The backend moves the folders' notes to the inbox (creating it if
+  // needed), so reload to pick them up there. Reload on failure too, so
+  // the optimistically removed folder comes back.
+  try {
+    await invoke('delete_folder', { id });
+  } finally {
+    await DataLayer.load();
+    render();
+  }
 }
 
 function getDescendantFolderIds(parentId) {
@@ -1080,12 +1088,14 @@ async function addNote() {
   state.activeNoteId = note.id;
   dirty.sidebar = true;
   dirty.notesList = true;
-  await render({ focusTitle: true });
-  invoke('create_note', {
+  // The backend fills an empty body with the default note template
+  note.body = await invoke('create_note', {
     id: note.id, folderId: note.folderId, title: note.title,
     body: note.body, createdAt: note.createdAt, updatedAt: note.updatedAt,
     pinned: note.pinned, sortOrder: note.sortOrder
   });
+  note.preview = note.body.slice(0, 200);
+  await render({ focusTitle: true });
   invoke('reorder_notes', { updates: unpinnedNotes.map(n => [n.id, n.sortOrder]) });
 }
 
@@ -1143,6 +1153,21 @@ function flushPendingSaves() {
   saveTimeouts.clear();
 }
 
+// Periodic snapshot of the open note into its version history, skipped when
+// it hasn't been edited since the last one (or since launch) or a save is
+// still pending
+const SNAPSHOT_INTERVAL_MS = 10 * 60 * 1000;
+const snapshotBaseline = Date.now();
+const lastSnapshotAt = new Map();
+
+setInterval(() => {
+  const note = state.notesById.get(state.activeNoteId);
+  if (!note || saveTimeouts.has(note.id)) return;
+  if (note.updatedAt <= (lastSnapshotAt.get(note.id) ?? snapshotBaseline)) return;
+  lastSnapshotAt.set(note.id, note.updatedAt);
+  invoke('snapshot_note', { id: note.id });
+}, SNAPSHOT_INTERVAL_MS);
+
 function updateNoteTitle(id, value) {
   const note = state.notesById.get(id);
   if (note) {
@@ -1476,7 +1501,10 @@ async function importBackup(event) {
       }
     }
 
-    await invoke('import_data', { folders: data.folders, notes: data.notes });
+    await invoke('import_data', {
+      folders: data.folders, notes: data.notes,
+      tags: data.tags, noteTags: data.note_tags, noteMeta: data.note_meta
+    });
 
     const existingFolderIds = new Set(state.data.folders.map(f => f.id));
     const existingNoteIds = new Set(state.data.notes.map(n => n.id));
@@ -57,7 +57,8 @@ export const DataLayer = {
         id: n.id, folderId: n.folder_id, title: n.title,
         preview: n.preview, body: null,
         createdAt: n.created_at, updatedAt: n.updated_at,
-        pinned: n.pinned || 0, sortOrder: n.sort_order || 0
+        pinned: n.pinned || 0, sortOrder: n.sort_order || 0,
+        starred: n.starred || 0
       }));
       rebuildIndexes();
     } catch (e) {